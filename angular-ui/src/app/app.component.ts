@@ -157,6 +157,7 @@ export class AppComponent implements OnInit {
   private formatStatus(s: MockStatusDto): string {
     const w = s.work_pos;
     const m = s.machine_pos;
-    return `State: ${s.state}\nWork:  X${w.x.toFixed(3)} Y${w.y.toFixed(3)} Z${w.z.toFixed(3)}\nMachine: X${m.x.toFixed(3)} Y${m.y.toFixed(3)} Z${m.z.toFixed(3)}\nFeed: ${s.feed_rate} mm/min  Spindle: ${s.spindle_speed} rpm`;
+    const p = s.part_pos;
+    return `State: ${s.state}\nPart:  X${p.x.toFixed(3)} Y${p.y.toFixed(3)} Z${p.z.toFixed(3)}\nWork:  X${w.x.toFixed(3)} Y${w.y.toFixed(3)} Z${w.z.toFixed(3)}\nMachine: X${m.x.toFixed(3)} Y${m.y.toFixed(3)} Z${m.z.toFixed(3)}\nFeed: ${s.feed_rate} mm/min  Spindle: ${s.spindle_speed} rpm`;
   }
 }
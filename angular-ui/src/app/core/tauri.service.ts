@@ -24,6 +24,8 @@ export interface MockStatusDto {
   state: string;
   work_pos: MockPositionDto;
   machine_pos: MockPositionDto;
+  /** Work position in part space (gantry Y + bed extension). */
+  part_pos: MockPositionDto;
   feed_rate: number;
   spindle_speed: number;
 }
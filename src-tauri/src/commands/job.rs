@@ -0,0 +1,59 @@
+//! Tauri commands for loaded job files (analysis for the file-information panel,
+//! toolpath and outline for the preview, start-from-line preamble for review).
+
+use grbl_rs::machines::grbl::{
+    analyze_file, outline_file, plan_start_from, toolpath_file, JobOutline, JobStats, MotionConfig,
+    OutlineShape, StartFromOptions, StartFromPlan, Toolpath, ToolpathOptions,
+};
+use std::path::Path;
+
+/// Analyze a G-code file: bounds (including bed-axis overflow), cut/rapid length,
+/// feed and spindle ranges, tool changes and unsupported words, with the active
+/// machine's bed extension config `motion`.
+#[tauri::command]
+pub fn analyze_gcode_file(path: String, motion: MotionConfig) -> Result<JobStats, String> {
+    analyze_file(Path::new(&path), &motion).map_err(|e| e.to_string())
+}
+
+/// Toolpath segment buffers for the 3D preview, after bed-extension translation
+/// with the active `motion` config. `max_segments` decimates huge files to
+/// roughly that many segments.
+#[tauri::command]
+pub fn gcode_toolpath(
+    path: String,
+    motion: MotionConfig,
+    max_segments: Option<usize>,
+) -> Result<Toolpath, String> {
+    let options = ToolpathOptions { max_segments };
+    toolpath_file(Path::new(&path), &motion, &options).map_err(|e| e.to_string())
+}
+
+/// XY outline (bounding box or convex hull) of the job's cutting moves, for
+/// drawing in the preview before tracing it on the machine. `motion` is the
+/// active machine's bed extension config.
+#[tauri::command]
+pub fn gcode_outline(
+    path: String,
+    motion: MotionConfig,
+    shape: Option<OutlineShape>,
+) -> Result<JobOutline, String> {
+    outline_file(Path::new(&path), shape.unwrap_or_default(), &motion)
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| "job has no cutting moves to outline".to_string())
+}
+
+/// Preamble and recovered modal state for starting the file at `line` (1-based),
+/// shown to the operator before the run is started. `motion` is the active
+/// machine's bed extension config.
+#[tauri::command]
+pub fn plan_start_from_line(
+    path: String,
+    line: usize,
+    motion: MotionConfig,
+    options: Option<StartFromOptions>,
+) -> Result<StartFromPlan, String> {
+    let content = std::fs::read_to_string(&path).map_err(|e| e.to_string())?;
+    let lines: Vec<&str> = content.lines().collect();
+    let options = options.unwrap_or_default();
+    plan_start_from(&lines, line, &options, &motion).map_err(|e| e.to_string())
+}
@@ -3,6 +3,17 @@
 //! Each submodule (port, machine, ...) owns the commands and DTOs for that area.
 //! Re-export command functions here so lib.rs can register them in one place.
 
+pub mod job;
 pub mod port;
+pub mod probe;
+pub mod profile;
+pub mod tools;
 
+pub use job::{analyze_gcode_file, gcode_outline, gcode_toolpath, plan_start_from_line};
 pub use port::list_serial_ports;
+pub use probe::{analyze_session_log, height_map_grid, load_height_map};
+pub use profile::{get_profile, record_job_event, save_profile};
+pub use tools::{
+    delete_tool, export_tools, feeds_and_speeds, import_tools, list_materials, list_tools,
+    save_tool,
+};
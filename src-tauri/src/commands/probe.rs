@@ -0,0 +1,26 @@
+//! Tauri commands for probing results shown in the UI (height map preview,
+//! probe repeatability from a session log).
+
+use grbl_rs::machines::grbl::{HeightMap, HeightMapOptions};
+use grbl_rs::machines::session::{analyze_session_probes, ProbeStats};
+use std::path::Path;
+
+/// Empty height map grid for `options`, so the UI can preview the points
+/// before probing.
+#[tauri::command]
+pub fn height_map_grid(options: HeightMapOptions) -> Result<HeightMap, String> {
+    HeightMap::new(&options).map_err(|e| e.to_string())
+}
+
+/// Load a saved height map for display.
+#[tauri::command]
+pub fn load_height_map(path: String) -> Result<HeightMap, String> {
+    HeightMap::load(Path::new(&path)).map_err(|e| e.to_string())
+}
+
+/// Probe repeatability statistics over the probe events of a session log;
+/// `None` if the log has no successful probe.
+#[tauri::command]
+pub fn analyze_session_log(path: String) -> Result<Option<ProbeStats>, String> {
+    analyze_session_probes(Path::new(&path)).map_err(|e| e.to_string())
+}
@@ -0,0 +1,52 @@
+//! Tauri commands for the active machine profile (work area, limits, spindle
+//! range and tool table).
+//!
+//! The profile is kept as `profile.json` in the app data directory; until one
+//! is saved, the PROVerXL 4030 profile is used.
+
+use grbl_rs::machines::grbl::JobEvent;
+use grbl_rs::machines::profiles::MachineProfile;
+use std::path::PathBuf;
+use tauri::{AppHandle, Manager};
+
+fn profile_path(app: &AppHandle) -> Result<PathBuf, String> {
+    let dir = app.path().app_data_dir().map_err(|e| e.to_string())?;
+    Ok(dir.join("profile.json"))
+}
+
+/// The saved profile, or the default one if none is saved yet.
+pub(crate) fn load_profile(app: &AppHandle) -> Result<MachineProfile, String> {
+    match MachineProfile::load(&profile_path(app)?) {
+        Ok(profile) => Ok(profile),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(MachineProfile::proverxl_4030()),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+pub(crate) fn store_profile(app: &AppHandle, profile: &MachineProfile) -> Result<(), String> {
+    profile.save(&profile_path(app)?).map_err(|e| e.to_string())
+}
+
+/// The active machine profile.
+#[tauri::command]
+pub fn get_profile(app: AppHandle) -> Result<MachineProfile, String> {
+    load_profile(&app)
+}
+
+/// Replace the active machine profile.
+#[tauri::command]
+pub fn save_profile(app: AppHandle, profile: MachineProfile) -> Result<(), String> {
+    store_profile(&app, &profile)
+}
+
+/// Apply a job event to the profile and save it if it changed: a
+/// `ToolMeasured` event stores the measured length in the tool table. Returns
+/// the profile so the UI can redraw from it.
+#[tauri::command]
+pub fn record_job_event(app: AppHandle, event: JobEvent) -> Result<MachineProfile, String> {
+    let mut profile = load_profile(&app)?;
+    if profile.record_job_event(&event) {
+        store_profile(&app, &profile)?;
+    }
+    Ok(profile)
+}
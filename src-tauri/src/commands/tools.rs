@@ -0,0 +1,88 @@
+//! Tauri commands for the tool library (Tools menu): list, edit, import, export
+//! and the feeds and speeds calculator.
+//!
+//! The library is the tool table of the active machine profile (see
+//! [`super::profile`]), so measured length offsets and edits share one list.
+//! Every edit returns the updated list so the UI can redraw from it.
+
+use super::profile::{load_profile, store_profile};
+use grbl_rs::machines::profiles::{
+    calculate_feeds, export_tools_csv, export_tools_json, find_material, import_tools_file,
+    materials, FeedsAndSpeeds, MachineProfile, Material, ToolEntry,
+};
+use std::path::Path;
+use tauri::AppHandle;
+
+/// Apply `edit` to the profile's tool table, save it and return the tools.
+fn edit_tools(
+    app: &AppHandle,
+    edit: impl FnOnce(&mut MachineProfile),
+) -> Result<Vec<ToolEntry>, String> {
+    let mut profile = load_profile(app)?;
+    edit(&mut profile);
+    store_profile(app, &profile)?;
+    Ok(profile.tools)
+}
+
+/// All tools in the library, sorted by number.
+#[tauri::command]
+pub fn list_tools(app: AppHandle) -> Result<Vec<ToolEntry>, String> {
+    Ok(load_profile(&app)?.tools)
+}
+
+/// Add a tool or replace the one with the same number.
+#[tauri::command]
+pub fn save_tool(app: AppHandle, tool: ToolEntry) -> Result<Vec<ToolEntry>, String> {
+    edit_tools(&app, |p| p.upsert_tool(tool))
+}
+
+/// Remove a tool by number.
+#[tauri::command]
+pub fn delete_tool(app: AppHandle, number: u8) -> Result<Vec<ToolEntry>, String> {
+    edit_tools(&app, |p| {
+        p.remove_tool(number);
+    })
+}
+
+/// Import a CSV, JSON, Fusion 360 `.json`/`.tools` or Vectric `.vtdb` file
+/// into the library. Imported tools replace same-numbered ones.
+#[tauri::command]
+pub fn import_tools(app: AppHandle, path: String) -> Result<Vec<ToolEntry>, String> {
+    let imported = import_tools_file(Path::new(&path)).map_err(|e| e.to_string())?;
+    edit_tools(&app, |p| p.merge_tools(imported))
+}
+
+/// Export the library to `path`; CSV if it ends in `.csv`, JSON otherwise.
+#[tauri::command]
+pub fn export_tools(app: AppHandle, path: String) -> Result<(), String> {
+    let tools = load_profile(&app)?.tools;
+    let text = if path.to_ascii_lowercase().ends_with(".csv") {
+        export_tools_csv(&tools)
+    } else {
+        export_tools_json(&tools).map_err(|e| e.to_string())?
+    };
+    std::fs::write(&path, text).map_err(|e| e.to_string())
+}
+
+/// Built-in materials for the calculator.
+#[tauri::command]
+pub fn list_materials() -> Vec<Material> {
+    materials()
+}
+
+/// Proposed RPM, feed, plunge, stepdown and stepover for tool `number` of the
+/// active machine `profile` in `material`, with notes on the limits that
+/// applied. The UI fills the profile's limits from `$$` when it has none.
+#[tauri::command]
+pub fn feeds_and_speeds(
+    number: u8,
+    material: String,
+    profile: MachineProfile,
+) -> Result<FeedsAndSpeeds, String> {
+    let tool = profile
+        .tool(number)
+        .ok_or_else(|| format!("tool {} is not in the library", number))?;
+    let material =
+        find_material(&material).ok_or_else(|| format!("unknown material {}", material))?;
+    calculate_feeds(tool, &material, &profile).map_err(|e| e.to_string())
+}
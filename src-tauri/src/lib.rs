@@ -6,7 +6,12 @@
 
 mod commands;
 
-use commands::list_serial_ports;
+use commands::{
+    analyze_gcode_file, analyze_session_log, delete_tool, export_tools, feeds_and_speeds,
+    gcode_outline, gcode_toolpath, get_profile, height_map_grid, import_tools, list_materials,
+    list_serial_ports, list_tools, load_height_map, plan_start_from_line, record_job_event,
+    save_profile, save_tool,
+};
 use serde::Serialize;
 
 /// Returns true if mock mode is enabled (MESHFORGE_MOCK=1). Used by command modules and UI.
@@ -26,6 +31,8 @@ pub struct MockStatusDto {
     pub state: String,
     pub work_pos: MockPositionDto,
     pub machine_pos: MockPositionDto,
+    /// Work position in part space (gantry Y + bed extension); what the UI shows as the tool position.
+    pub part_pos: MockPositionDto,
     pub feed_rate: f64,
     pub spindle_speed: f64,
 }
@@ -55,6 +62,12 @@ fn get_mock_status() -> MockStatusDto {
             z: 0.0,
             a: None,
         },
+        part_pos: MockPositionDto {
+            x: 0.0,
+            y: 0.0,
+            z: 0.0,
+            a: None,
+        },
         feed_rate: 0.0,
         spindle_speed: 0.0,
     }
@@ -65,6 +78,23 @@ pub fn run() {
     tauri::Builder::default()
         .invoke_handler(tauri::generate_handler![
             list_serial_ports,
+            analyze_gcode_file,
+            gcode_toolpath,
+            gcode_outline,
+            plan_start_from_line,
+            list_tools,
+            save_tool,
+            delete_tool,
+            import_tools,
+            export_tools,
+            list_materials,
+            feeds_and_speeds,
+            height_map_grid,
+            load_height_map,
+            analyze_session_log,
+            get_profile,
+            save_profile,
+            record_job_event,
             is_mock_mode,
             get_mock_status,
         ])
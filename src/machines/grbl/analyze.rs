@@ -0,0 +1,230 @@
+//! Static analysis of a G-code program for the file-information panel and CLI.
+//!
+//! Runs the program through the G-code [`Interpreter`] once and collects bounds,
+//! distances, feed/spindle ranges, tool changes and anything GRBL-HAL will not
+//! accept. No I/O beyond reading the file in [`analyze_file`].
+
+use super::gcode::{Interpreter, MotionKind, Point, SpindleState};
+use super::motion::MotionConfig;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// Min/max of a value over the program.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ValueRange {
+    pub min: f64,
+    pub max: f64,
+}
+
+impl ValueRange {
+    fn include(range: &mut Option<ValueRange>, v: f64) {
+        match range {
+            Some(r) => {
+                r.min = r.min.min(v);
+                r.max = r.max.max(v);
+            }
+            None => *range = Some(ValueRange { min: v, max: v }),
+        }
+    }
+}
+
+/// Work-coordinate extents of all motion (mm).
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct JobBounds {
+    pub x: ValueRange,
+    /// Part-space Y, as programmed.
+    pub y: ValueRange,
+    pub z: ValueRange,
+    /// Gantry Y after bed extension translation (capped at the gantry limit).
+    pub gantry_y: ValueRange,
+    /// Bed-axis travel after translation; `None` if the job never goes past the gantry limit.
+    pub bed: Option<ValueRange>,
+}
+
+/// A tool change (`M6`) and where it happens.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ToolChangePoint {
+    /// 1-based line number in the file.
+    pub line: usize,
+    pub tool: u32,
+}
+
+/// Summary of a G-code program.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct JobStats {
+    /// Lines in the file, including blanks and comments.
+    pub line_count: usize,
+    /// `None` if the program has no motion.
+    pub bounds: Option<JobBounds>,
+    /// Distance at feed (G1/G2/G3/G38.x), mm.
+    pub cut_length_mm: f64,
+    /// Distance in rapids (G0), mm.
+    pub rapid_length_mm: f64,
+    /// Feed rates used by cutting moves, mm/min.
+    pub feed_range: Option<ValueRange>,
+    /// Spindle speeds used while the spindle is on.
+    pub spindle_range: Option<ValueRange>,
+    /// Tools loaded by `M6`, in order of first use.
+    pub tools: Vec<u32>,
+    pub tool_changes: Vec<ToolChangePoint>,
+    /// Words GRBL-HAL does not support (e.g. `G81`), each listed once.
+    pub unsupported: Vec<String>,
+    /// 1-based line numbers that could not be parsed.
+    pub invalid_lines: Vec<usize>,
+    pub has_arcs: bool,
+    pub has_probing: bool,
+}
+
+/// Analyze a program given as lines (untranslated, part-space).
+pub fn analyze_lines(lines: &[impl AsRef<str>], config: &MotionConfig) -> JobStats {
+    let mut stats = JobStats {
+        line_count: lines.len(),
+        ..JobStats::default()
+    };
+    let mut interp = Interpreter::new();
+    let mut extent: [Option<ValueRange>; 3] = [None; 3];
+    let limit = config.gantry_y_limit_mm;
+
+    for (i, line) in lines.iter().enumerate() {
+        let exec = match interp.execute(line.as_ref()) {
+            Ok(exec) => exec,
+            Err(_) => {
+                stats.invalid_lines.push(i + 1);
+                continue;
+            }
+        };
+        for word in exec.unsupported {
+            if !stats.unsupported.contains(&word) {
+                stats.unsupported.push(word);
+            }
+        }
+        if let Some(tool) = exec.tool_change {
+            stats
+                .tool_changes
+                .push(ToolChangePoint { line: i + 1, tool });
+            if !stats.tools.contains(&tool) {
+                stats.tools.push(tool);
+            }
+        }
+        let Some(m) = exec.motion else {
+            continue;
+        };
+        let modal = interp.modal();
+        match m.kind {
+            MotionKind::Rapid => stats.rapid_length_mm += m.length(),
+            kind => {
+                stats.cut_length_mm += m.length();
+                stats.has_arcs |= matches!(kind, MotionKind::ArcCw | MotionKind::ArcCcw);
+                stats.has_probing |= kind == MotionKind::Probe;
+                if m.feed_mm_min > 0.0 {
+                    ValueRange::include(&mut stats.feed_range, m.feed_mm_min);
+                }
+            }
+        }
+        if modal.spindle != SpindleState::Off {
+            ValueRange::include(&mut stats.spindle_range, modal.spindle_speed);
+        }
+        let include = |extent: &mut [Option<ValueRange>; 3], p: Point| {
+            ValueRange::include(&mut extent[0], p.x);
+            ValueRange::include(&mut extent[1], p.y);
+            ValueRange::include(&mut extent[2], p.z);
+        };
+        include(&mut extent, m.start);
+        for p in m.points(config.arc_tolerance_mm) {
+            include(&mut extent, p);
+        }
+    }
+
+    if let [Some(x), Some(y), Some(z)] = extent {
+        let bed = (y.max > limit).then(|| ValueRange {
+            min: (y.min - limit).max(0.0),
+            max: y.max - limit,
+        });
+        stats.bounds = Some(JobBounds {
+            x,
+            y,
+            z,
+            gantry_y: ValueRange {
+                min: y.min.min(limit),
+                max: y.max.min(limit),
+            },
+            bed,
+        });
+    }
+    stats
+}
+
+/// Read and analyze a G-code file.
+pub fn analyze_file(path: &Path, config: &MotionConfig) -> std::io::Result<JobStats> {
+    let content = std::fs::read_to_string(path)?;
+    let lines: Vec<&str> = content.lines().collect();
+    Ok(analyze_lines(&lines, config))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_analyze_basic_program() {
+        let lines = [
+            "(job)",
+            "G21 G90",
+            "T1 M6",
+            "M3 S12000",
+            "G0 X0 Y0 Z5",
+            "G1 Z-1 F200",
+            "G1 X100 F800",
+            "G2 X100 Y700 I0 J350",
+            "G38.2 Z-10 F50",
+            "T2 M6",
+            "M3 S8000",
+            "G81 X1 Y1",
+            "G1 X1 Y1 Z",
+            "M30",
+        ];
+        let s = analyze_lines(&lines, &MotionConfig::default());
+        assert_eq!(s.line_count, 14);
+        assert_eq!(s.tools, vec![1, 2]);
+        assert_eq!(s.tool_changes[1], ToolChangePoint { line: 10, tool: 2 });
+        assert_eq!(s.unsupported, vec!["G81".to_string()]);
+        assert_eq!(s.invalid_lines, vec![13]);
+        assert!(s.has_arcs);
+        assert!(s.has_probing);
+        assert_eq!(
+            s.feed_range,
+            Some(ValueRange {
+                min: 50.0,
+                max: 800.0
+            })
+        );
+        assert_eq!(
+            s.spindle_range,
+            Some(ValueRange {
+                min: 8000.0,
+                max: 12000.0
+            })
+        );
+        let b = s.bounds.unwrap();
+        assert_eq!(b.z.min, -10.0);
+        assert_eq!(b.z.max, 5.0);
+        // Clockwise arc from (100, 0) to (100, 700) bulges out to X = 100 - 350.
+        assert!((b.x.min + 250.0).abs() < 0.01);
+        assert_eq!(b.x.max, 100.0);
+        assert_eq!(b.y.max, 700.0);
+        assert!((b.gantry_y.max - 609.6).abs() < 1e-9);
+        let bed = b.bed.unwrap();
+        assert!((bed.max - 90.4).abs() < 1e-9);
+        assert_eq!(bed.min, 0.0);
+        assert!(s.rapid_length_mm > 0.0);
+        assert!(s.cut_length_mm > 100.0);
+    }
+
+    #[test]
+    fn test_analyze_no_motion() {
+        let s = analyze_lines(&["; empty", "M5"], &MotionConfig::default());
+        assert!(s.bounds.is_none());
+        assert_eq!(s.cut_length_mm, 0.0);
+        assert!(s.tools.is_empty());
+    }
+}
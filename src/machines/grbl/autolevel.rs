@@ -0,0 +1,347 @@
+//! Auto-leveling: Z compensation of a program from a [`HeightMap`].
+//!
+//! Every XY feed motion (`G1`/`G2`/`G3`) is rewritten as explicit `G1` segments
+//! no longer than `max_segment_mm` (arcs are linearized first), each with its Z
+//! raised or lowered by the bilinearly interpolated surface height under it.
+//! Heights are used as probed, so Z zero should be set on the surface in the same
+//! WCS the map was probed in. Other words on a motion line (feed, spindle, modal
+//! codes) go on a line of their own before the segments.
+//!
+//! Rapids are not corrected: keep clearance heights above the map's range. A
+//! leveled segment outside the probed grid is an error rather than a guess.
+//! Segments carry X, Y and Z, so leveling only starts once the program has set
+//! all three absolutely; until then, and again after `G53` moves, probe moves,
+//! `G28`/`G30`, `G10` and WCS changes (which leave the work position unknown),
+//! lines pass through unchanged.
+//!
+//! Runs on part-space lines before the bed extension translator. Lines with
+//! rotary-axis words pass through unchanged.
+
+use super::gcode::{DistanceMode, Executed, Interpreter, MotionKind, Point};
+use super::heightmap::HeightMap;
+use super::motion::TranslatedLine;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+/// Leveling transform settings.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct AutoLevel {
+    pub map: HeightMap,
+    /// Longest XY segment (mm) between Z corrections.
+    pub max_segment_mm: f64,
+    /// Maximum chord deviation when linearizing arcs (mm).
+    pub arc_tolerance_mm: f64,
+}
+
+/// Errors from [`AutoLevel::apply`].
+#[derive(Debug, Error, PartialEq)]
+pub enum AutoLevelError {
+    #[error("line {line}: no height at X{x:.3} Y{y:.3} (probe point failed)")]
+    MissingHeight { line: usize, x: f64, y: f64 },
+    #[error("line {line}: X{x:.3} Y{y:.3} is outside the height map")]
+    OutsideMap { line: usize, x: f64, y: f64 },
+}
+
+/// Words replaced by the leveled segments: motion G-codes, axes and arc words.
+fn is_motion_word(letter: char, code: i32) -> bool {
+    match letter {
+        'G' => matches!(code, 10 | 20 | 30),
+        'X' | 'Y' | 'Z' | 'I' | 'J' | 'K' | 'R' => true,
+        _ => false,
+    }
+}
+
+impl AutoLevel {
+    /// Leveling with 1 mm segments and 0.01 mm arc tolerance.
+    pub fn new(map: HeightMap) -> Self {
+        Self {
+            map,
+            max_segment_mm: 1.0,
+            arc_tolerance_mm: 0.01,
+        }
+    }
+
+    fn height(&self, line: usize, p: &Point) -> Result<f64, AutoLevelError> {
+        if !self.map.contains(p.x, p.y) {
+            return Err(AutoLevelError::OutsideMap {
+                line,
+                x: p.x,
+                y: p.y,
+            });
+        }
+        self.map
+            .height_at(p.x, p.y)
+            .ok_or(AutoLevelError::MissingHeight {
+                line,
+                x: p.x,
+                y: p.y,
+            })
+    }
+
+    /// Level a program. Output lines keep the source line they came from.
+    pub fn apply(
+        &self,
+        lines: impl IntoIterator<Item = TranslatedLine>,
+    ) -> Result<Vec<TranslatedLine>, AutoLevelError> {
+        let mut interp = Interpreter::new();
+        // Whether the work position of X, Y and Z is known.
+        let mut known = [false; 3];
+        let mut out = Vec::new();
+        for line in lines {
+            let source_line = line.source_line;
+            let wcs = interp.modal().wcs;
+            let exec = match interp.execute(&line.text) {
+                Ok(e) => e,
+                Err(_) => {
+                    // The controller will reject it; leave that to the stream.
+                    out.push(line);
+                    continue;
+                }
+            };
+            let relative = interp.modal().distance == DistanceMode::Relative;
+            let leveled = match &exec.motion {
+                Some(m) => {
+                    let feed = matches!(
+                        m.kind,
+                        MotionKind::Linear | MotionKind::ArcCw | MotionKind::ArcCcw
+                    );
+                    let rotary = ['A', 'B', 'C'].iter().any(|&l| exec.has_word(l));
+                    feed && !m.machine_coords && !rotary && known.iter().all(|&k| k)
+                }
+                None => false,
+            };
+            update_known(&mut known, &exec, relative, interp.modal().wcs != wcs);
+            if !leveled {
+                out.push(line);
+                continue;
+            }
+            let motion = exec.motion.as_ref().expect("leveled line has a motion");
+            let mut push = |text: String| {
+                out.push(TranslatedLine { source_line, text });
+            };
+            let rest: Vec<&str> = exec
+                .words
+                .iter()
+                .filter(|w| !is_motion_word(w.letter, w.code()))
+                .map(|w| w.raw.as_str())
+                .collect();
+            if !rest.is_empty() {
+                push(rest.join(" "));
+            }
+            let units = interp.modal().units;
+            let g92 = interp.g92_offset();
+            let fmt = |v: f64| format!("{:.4}", units.from_mm(v));
+            let mut prev = motion.start;
+            let mut first = true;
+            for target in motion.points(self.arc_tolerance_mm) {
+                let xy = (target.x - prev.x).hypot(target.y - prev.y);
+                let n = ((xy / self.max_segment_mm.max(1e-3)).ceil() as usize).max(1);
+                for i in 1..=n {
+                    let p = prev.lerp(&target, i as f64 / n as f64);
+                    let z = p.z + self.height(source_line, &p)?;
+                    let prefix = if relative && first { "G90 " } else { "" };
+                    first = false;
+                    push(format!(
+                        "{}G1 X{} Y{} Z{}",
+                        prefix,
+                        fmt(p.x - g92.x),
+                        fmt(p.y - g92.y),
+                        fmt(z - g92.z)
+                    ));
+                }
+                prev = target;
+            }
+            if relative {
+                push("G91".to_string());
+            }
+        }
+        Ok(out)
+    }
+}
+
+/// Track which axes' work positions are known after `exec`. Absolute axis words
+/// make an axis known and relative ones keep it as it was; moves and codes whose
+/// end position in work coordinates the interpreter cannot tell make it unknown.
+fn update_known(known: &mut [bool; 3], exec: &Executed, relative: bool, wcs_changed: bool) {
+    let g = |code: i32| {
+        exec.words
+            .iter()
+            .any(|w| w.letter == 'G' && w.code() == code)
+    };
+    let axes = ['X', 'Y', 'Z'].map(|l| exec.has_word(l));
+    if wcs_changed || g(100) || g(280) || g(300) {
+        *known = [false; 3];
+        return;
+    }
+    if g(431) || g(490) {
+        known[2] = false;
+    }
+    if g(920) {
+        // G92 declares the current position on the given axes.
+        for (k, &a) in known.iter_mut().zip(&axes) {
+            *k |= a;
+        }
+        return;
+    }
+    let Some(motion) = &exec.motion else {
+        return;
+    };
+    let lost = motion.machine_coords || motion.kind == MotionKind::Probe;
+    for (k, &a) in known.iter_mut().zip(&axes) {
+        if a {
+            *k = !lost && (*k || !relative);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::machines::grbl::HeightMapOptions;
+
+    /// 20 x 10 map tilted along X: 0 at X0, 0.2 at X20.
+    fn tilted() -> AutoLevel {
+        let mut map = HeightMap::new(&HeightMapOptions {
+            min: [0.0, 0.0],
+            max: [20.0, 10.0],
+            spacing_mm: 10.0,
+            ..HeightMapOptions::default()
+        })
+        .unwrap();
+        for (c, r) in map.probe_order() {
+            map.set(c, r, Some(c as f64 * 0.1));
+        }
+        AutoLevel {
+            max_segment_mm: 5.0,
+            ..AutoLevel::new(map)
+        }
+    }
+
+    fn numbered(lines: &[&str]) -> Vec<TranslatedLine> {
+        lines
+            .iter()
+            .enumerate()
+            .map(|(i, l)| TranslatedLine {
+                source_line: i + 1,
+                text: l.to_string(),
+            })
+            .collect()
+    }
+
+    fn texts(lines: &[TranslatedLine]) -> Vec<&str> {
+        lines.iter().map(|l| l.text.as_str()).collect()
+    }
+
+    #[test]
+    fn test_segments_and_corrects_z() {
+        let out = tilted()
+            .apply(numbered(&[
+                "G21 G90",
+                "G0 X0 Y0 Z1",
+                "G1 Z-1 F300",
+                "G1 X20",
+            ]))
+            .unwrap();
+        assert_eq!(
+            texts(&out),
+            vec![
+                "G21 G90",
+                "G0 X0 Y0 Z1",
+                "F300",
+                "G1 X0.0000 Y0.0000 Z-1.0000",
+                "G1 X5.0000 Y0.0000 Z-0.9500",
+                "G1 X10.0000 Y0.0000 Z-0.9000",
+                "G1 X15.0000 Y0.0000 Z-0.8500",
+                "G1 X20.0000 Y0.0000 Z-0.8000",
+            ]
+        );
+        assert!(out[4..].iter().all(|l| l.source_line == 4));
+    }
+
+    #[test]
+    fn test_unknown_position_at_start() {
+        let out = tilted()
+            .apply(numbered(&["G0 X10 Y5", "G1 X20 F100", "G0 Z5", "G1 X10"]))
+            .unwrap();
+        let t = texts(&out);
+        // No Z until line 3: nothing is rewritten, rapids never are.
+        assert_eq!(t[..3], ["G0 X10 Y5", "G1 X20 F100", "G0 Z5"]);
+        assert_eq!(t[3], "G1 X15.0000 Y5.0000 Z5.1500");
+        assert_eq!(*t.last().unwrap(), "G1 X10.0000 Y5.0000 Z5.1000");
+    }
+
+    #[test]
+    fn test_g53_and_g28_make_position_unknown() {
+        let out = tilted()
+            .apply(numbered(&[
+                "G90 G0 X0 Y0 Z1",
+                "G1 X5 F100",
+                "G53 G0 Z-5",
+                "G0 X20",
+                "G1 X15",
+                "G0 Z1",
+                "G1 X10",
+                "G28",
+                "G1 X5",
+            ]))
+            .unwrap();
+        let t = texts(&out);
+        assert_eq!(
+            t[..3],
+            ["G90 G0 X0 Y0 Z1", "F100", "G1 X5.0000 Y0.0000 Z1.0500"]
+        );
+        // Z is unknown after the G53 retract until the program sets it again.
+        assert_eq!(t[3..7], ["G53 G0 Z-5", "G0 X20", "G1 X15", "G0 Z1"]);
+        assert_eq!(t[7], "G1 X10.0000 Y0.0000 Z1.1000");
+        assert_eq!(t[8..], ["G28", "G1 X5"]);
+    }
+
+    #[test]
+    fn test_relative_arcs_and_passthrough() {
+        let out = tilted()
+            .apply(numbered(&[
+                "G0 X0 Y0 Z0",
+                "G91 G1 X10 F100",
+                "G53 G0 Z0",
+                "M3 S1000",
+                "G90 G0 Z0",
+                "G2 X20 Y0 I5 J0",
+            ]))
+            .unwrap();
+        let t = texts(&out);
+        assert_eq!(t[0], "G0 X0 Y0 Z0");
+        assert_eq!(t[1], "G91 F100");
+        assert_eq!(t[2], "G90 G1 X5.0000 Y0.0000 Z0.0500");
+        assert_eq!(t[3], "G1 X10.0000 Y0.0000 Z0.1000");
+        assert_eq!(t[4], "G91");
+        assert_eq!(t[5..8], ["G53 G0 Z0", "M3 S1000", "G90 G0 Z0"]);
+        // Linearized arc ends on the target, corrected by the map there.
+        assert_eq!(*t.last().unwrap(), "G1 X20.0000 Y0.0000 Z0.2000");
+        assert!(t.len() > 12);
+    }
+
+    #[test]
+    fn test_missing_height() {
+        let mut level = tilted();
+        level.map.set(2, 0, None);
+        let err = level
+            .apply(numbered(&["G0 X0 Y0 Z0", "G1 X20 F100"]))
+            .unwrap_err();
+        assert!(matches!(err, AutoLevelError::MissingHeight { line: 2, .. }));
+    }
+
+    #[test]
+    fn test_outside_map() {
+        let err = tilted()
+            .apply(numbered(&["G0 X0 Y0 Z0", "G1 X10 F100", "G1 Y12"]))
+            .unwrap_err();
+        assert_eq!(
+            err,
+            AutoLevelError::OutsideMap {
+                line: 3,
+                x: 10.0,
+                y: 12.0
+            }
+        );
+    }
+}
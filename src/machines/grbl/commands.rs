@@ -14,10 +14,20 @@ pub enum GrblCommand {
     StatusRequest,
     /// Request all settings (sends `$$`).
     SettingsRequest,
+    /// Request the parser state (sends `$G`); answered with `[GC:...]`.
+    ParserState,
+    /// Request build info (sends `$I`); answered with `[VER:...]`, `[OPT:...]`, ...
+    BuildInfo,
+    /// Request offsets and the last probe (sends `$#`); answered with
+    /// `[G54:...]` .. `[G92:...]`, `[TLO:...]` and `[PRB:...]`.
+    Parameters,
     /// Run homing cycle (sends `$H`).
     Home,
     /// Unlock after alarm (sends `$X`).
     Unlock,
+    /// Toggle check mode (sends `$C`): lines are parsed but not executed.
+    /// Leaving check mode soft-resets the controller.
+    CheckMode,
     /// Jog: `$J=<gcode>`. Pass the full gcode part, e.g. `G21G91X10F500`.
     Jog(String),
     /// Probe cycle: G38.2 or G38.3 with axis, distance, feed. Stored as raw gcode line.
@@ -28,6 +38,8 @@ pub enum GrblCommand {
     ActivateWcs(u8),
     /// Raw g-code line (e.g. from file for streamer). Sent as-is.
     GcodeLine(String),
+    /// Write a controller setting: `$<number>=<value>`.
+    SetSetting { number: u16, value: String },
 }
 
 impl fmt::Display for GrblCommand {
@@ -35,8 +47,12 @@ impl fmt::Display for GrblCommand {
         match self {
             GrblCommand::StatusRequest => write!(f, "?"),
             GrblCommand::SettingsRequest => write!(f, "$$"),
+            GrblCommand::ParserState => write!(f, "$G"),
+            GrblCommand::BuildInfo => write!(f, "$I"),
+            GrblCommand::Parameters => write!(f, "$#"),
             GrblCommand::Home => write!(f, "$H"),
             GrblCommand::Unlock => write!(f, "$X"),
+            GrblCommand::CheckMode => write!(f, "$C"),
             GrblCommand::Jog(gcode) => write!(f, "$J={}", gcode),
             GrblCommand::ProbeCycle(line) => write!(f, "{}", line),
             GrblCommand::SetWcsZero { p, x, y, z } => {
@@ -59,6 +75,7 @@ impl fmt::Display for GrblCommand {
                 write!(f, "{}", s)
             }
             GrblCommand::GcodeLine(line) => write!(f, "{}", line),
+            GrblCommand::SetSetting { number, value } => write!(f, "${}={}", number, value),
         }
     }
 }
@@ -72,6 +89,10 @@ pub enum RealtimeCommand {
     SafetyDoor,
     /// Jog cancel. Byte 0x85.
     JogCancel,
+    /// Feed hold (`!`).
+    FeedHold,
+    /// Cycle start / resume (`~`).
+    CycleStart,
     /// Feed override 100%. Byte 0x90.
     FeedOverride100,
     /// Feed override +10%. Byte 0x91.
@@ -87,6 +108,8 @@ impl RealtimeCommand {
             RealtimeCommand::SoftReset => 0x18,
             RealtimeCommand::SafetyDoor => 0x84,
             RealtimeCommand::JogCancel => 0x85,
+            RealtimeCommand::FeedHold => b'!',
+            RealtimeCommand::CycleStart => b'~',
             RealtimeCommand::FeedOverride100 => 0x90,
             RealtimeCommand::FeedOverridePlus10 => 0x91,
             RealtimeCommand::FeedOverrideMinus10 => 0x92,
@@ -114,6 +137,30 @@ mod tests {
         assert_eq!(GrblCommand::SettingsRequest.to_string(), "$$");
     }
 
+    #[test]
+    fn test_parser_state_display() {
+        assert_eq!(GrblCommand::ParserState.to_string(), "$G");
+    }
+
+    #[test]
+    fn test_build_info_display() {
+        assert_eq!(GrblCommand::BuildInfo.to_string(), "$I");
+    }
+
+    #[test]
+    fn test_parameters_display() {
+        assert_eq!(GrblCommand::Parameters.to_string(), "$#");
+    }
+
+    #[test]
+    fn test_set_setting_display() {
+        let cmd = GrblCommand::SetSetting {
+            number: 110,
+            value: "5000.000".to_string(),
+        };
+        assert_eq!(cmd.to_string(), "$110=5000.000");
+    }
+
     #[test]
     fn test_home_display() {
         assert_eq!(GrblCommand::Home.to_string(), "$H");
@@ -124,6 +171,11 @@ mod tests {
         assert_eq!(GrblCommand::Unlock.to_string(), "$X");
     }
 
+    #[test]
+    fn test_check_mode_display() {
+        assert_eq!(GrblCommand::CheckMode.to_string(), "$C");
+    }
+
     #[test]
     fn test_jog_display() {
         assert_eq!(
@@ -186,6 +238,8 @@ mod tests {
     #[test]
     fn test_realtime_jog_cancel_byte() {
         assert_eq!(RealtimeCommand::JogCancel.as_byte(), 0x85);
+        assert_eq!(RealtimeCommand::FeedHold.as_byte(), b'!');
+        assert_eq!(RealtimeCommand::CycleStart.as_byte(), b'~');
     }
 
     #[test]
@@ -0,0 +1,364 @@
+//! Job time estimation.
+//!
+//! Replays a program through the G-code [`Interpreter`] and a model of the
+//! GRBL planner: per-axis max rates (`$110`–`$113`) and accelerations
+//! (`$120`–`$123`) limit each block, junction deviation (`$11`) limits the
+//! speed through corners, and every block gets a trapezoidal velocity profile.
+//! The planner buffer is treated as unbounded, so long runs of short segments
+//! come out slightly optimistic.
+
+use super::gcode::{Interpreter, MotionKind, Point};
+use super::motion::MotionConfig;
+use super::parser::GrblSettings;
+use serde::{Deserialize, Serialize};
+
+/// Axis order used by [`MachineLimits`] arrays: X, Y, Z, A.
+const AXES: usize = 4;
+
+/// Planner limits: the controller's `$110`–`$113`, `$120`–`$123` and `$11` settings.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct MachineLimits {
+    /// Max rate per axis (X, Y, Z, A) in mm/min (`$110`–`$113`).
+    pub max_rate_mm_min: [f64; AXES],
+    /// Acceleration per axis (X, Y, Z, A) in mm/s² (`$120`–`$123`).
+    pub accel_mm_s2: [f64; AXES],
+    /// Junction deviation in mm (`$11`).
+    pub junction_deviation_mm: f64,
+}
+
+impl Default for MachineLimits {
+    /// GRBL firmware defaults (500 mm/min, 10 mm/s², 0.01 mm).
+    fn default() -> Self {
+        Self {
+            max_rate_mm_min: [500.0; AXES],
+            accel_mm_s2: [10.0; AXES],
+            junction_deviation_mm: 0.01,
+        }
+    }
+}
+
+impl MachineLimits {
+    /// Read limits from a `$$` dump; settings that are missing keep the defaults.
+    pub fn from_settings(settings: &GrblSettings) -> Self {
+        let mut limits = Self::default();
+        for i in 0..AXES {
+            if let Some(v) = settings.get_f64(110 + i as u32) {
+                limits.max_rate_mm_min[i] = v;
+            }
+            if let Some(v) = settings.get_f64(120 + i as u32) {
+                limits.accel_mm_s2[i] = v;
+            }
+        }
+        if let Some(v) = settings.get_f64(11) {
+            limits.junction_deviation_mm = v;
+        }
+        limits
+    }
+}
+
+/// Estimated run time and distances for a program.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct JobEstimate {
+    /// Total estimated time in seconds.
+    pub total_secs: f64,
+    /// Estimated time per input line (same indexing as the lines passed in).
+    pub line_secs: Vec<f64>,
+    /// Distance travelled in rapids (G0), mm.
+    pub rapid_distance_mm: f64,
+    /// Distance travelled at feed (G1/G2/G3/G38.x), mm.
+    pub cut_distance_mm: f64,
+    /// Time spent in rapids, seconds.
+    pub rapid_secs: f64,
+    /// Time spent at feed, seconds.
+    pub cut_secs: f64,
+    /// Time spent in G4 dwells, seconds.
+    pub dwell_secs: f64,
+}
+
+impl JobEstimate {
+    /// Estimated time left once the first `lines_done` lines have completed.
+    pub fn remaining_secs(&self, lines_done: usize) -> f64 {
+        self.line_secs.iter().skip(lines_done).sum()
+    }
+}
+
+/// One straight planner block.
+struct Block {
+    line: usize,
+    length: f64,
+    unit: [f64; AXES],
+    rapid: bool,
+    /// Nominal speed, mm/s.
+    nominal: f64,
+    /// Acceleration along the block, mm/s².
+    accel: f64,
+    /// Max entry speed squared allowed by the junction with the previous block.
+    max_entry_sq: f64,
+}
+
+/// Smallest per-axis limit along `unit` (GRBL's `limit_value_by_axis_maximum`).
+fn limit_by_axis(limits: &[f64; AXES], unit: &[f64; AXES]) -> f64 {
+    let mut out = f64::INFINITY;
+    for i in 0..AXES {
+        if unit[i].abs() > 1e-12 {
+            out = out.min(limits[i] / unit[i].abs());
+        }
+    }
+    out
+}
+
+/// Time for a trapezoidal (or triangular) profile over `d` mm.
+fn block_time(d: f64, entry: f64, exit: f64, nominal: f64, accel: f64) -> f64 {
+    if d <= 0.0 {
+        return 0.0;
+    }
+    let accel_d = (nominal * nominal - entry * entry) / (2.0 * accel);
+    let decel_d = (nominal * nominal - exit * exit) / (2.0 * accel);
+    if accel_d + decel_d <= d {
+        (nominal - entry) / accel + (nominal - exit) / accel + (d - accel_d - decel_d) / nominal
+    } else {
+        let peak = (accel * d + (entry * entry + exit * exit) / 2.0).sqrt();
+        (peak - entry).max(0.0) / accel + (peak - exit).max(0.0) / accel
+    }
+}
+
+struct Planner<'a> {
+    limits: &'a MachineLimits,
+    config: &'a MotionConfig,
+    blocks: Vec<Block>,
+    estimate: JobEstimate,
+}
+
+impl<'a> Planner<'a> {
+    /// Adds a straight block. Y travel beyond the gantry limit is carried by the
+    /// bed axis, so it is limited by the A settings instead of Y.
+    fn push(&mut self, line: usize, from: Point, to: Point, rapid: bool, feed_mm_min: f64) {
+        let delta = [to.x - from.x, to.y - from.y, to.z - from.z];
+        let length = (delta[0] * delta[0] + delta[1] * delta[1] + delta[2] * delta[2]).sqrt();
+        if length < 1e-9 {
+            return;
+        }
+        let on_bed = from.y.max(to.y) > self.config.gantry_y_limit_mm;
+        let mut unit = [0.0; AXES];
+        unit[0] = delta[0] / length;
+        unit[if on_bed { 3 } else { 1 }] = delta[1] / length;
+        unit[2] = delta[2] / length;
+
+        let max_rate = limit_by_axis(&self.limits.max_rate_mm_min, &unit);
+        let nominal = if rapid || feed_mm_min <= 0.0 {
+            max_rate
+        } else {
+            feed_mm_min.min(max_rate)
+        } / 60.0;
+        let accel = limit_by_axis(&self.limits.accel_mm_s2, &unit);
+
+        let max_entry_sq = match self.blocks.last() {
+            None => 0.0,
+            Some(prev) => {
+                let junction = self.junction_speed_sq(&prev.unit, &unit);
+                junction
+                    .min(nominal * nominal)
+                    .min(prev.nominal * prev.nominal)
+            }
+        };
+        self.blocks.push(Block {
+            line,
+            length,
+            unit,
+            rapid,
+            nominal,
+            accel,
+            max_entry_sq,
+        });
+    }
+
+    /// Max junction speed squared from the junction deviation model.
+    fn junction_speed_sq(&self, prev: &[f64; AXES], unit: &[f64; AXES]) -> f64 {
+        let cos_theta: f64 = -(0..AXES).map(|i| prev[i] * unit[i]).sum::<f64>();
+        if cos_theta > 0.999_999 {
+            return 0.0;
+        }
+        if cos_theta < -0.999_999 {
+            return f64::INFINITY;
+        }
+        let mut junction_unit = [0.0; AXES];
+        for i in 0..AXES {
+            junction_unit[i] = unit[i] - prev[i];
+        }
+        let norm = junction_unit.iter().map(|v| v * v).sum::<f64>().sqrt();
+        for v in &mut junction_unit {
+            *v /= norm;
+        }
+        let accel = limit_by_axis(&self.limits.accel_mm_s2, &junction_unit);
+        let sin_theta_d2 = (0.5 * (1.0 - cos_theta)).sqrt();
+        accel * self.limits.junction_deviation_mm * sin_theta_d2 / (1.0 - sin_theta_d2)
+    }
+
+    /// Plans all pending blocks to a full stop and accounts their time.
+    fn flush(&mut self) {
+        let n = self.blocks.len();
+        if n == 0 {
+            return;
+        }
+        let mut v_sq = vec![0.0; n + 1];
+        for i in (0..n).rev() {
+            let b = &self.blocks[i];
+            v_sq[i] = b.max_entry_sq.min(v_sq[i + 1] + 2.0 * b.accel * b.length);
+        }
+        for i in 0..n {
+            let b = &self.blocks[i];
+            v_sq[i + 1] = v_sq[i + 1].min(v_sq[i] + 2.0 * b.accel * b.length);
+        }
+        for (i, b) in self.blocks.drain(..).enumerate() {
+            let t = block_time(
+                b.length,
+                v_sq[i].sqrt(),
+                v_sq[i + 1].sqrt(),
+                b.nominal,
+                b.accel,
+            );
+            self.estimate.line_secs[b.line] += t;
+            self.estimate.total_secs += t;
+            if b.rapid {
+                self.estimate.rapid_secs += t;
+                self.estimate.rapid_distance_mm += b.length;
+            } else {
+                self.estimate.cut_secs += t;
+                self.estimate.cut_distance_mm += b.length;
+            }
+        }
+    }
+}
+
+/// Estimate run time for a program (untranslated, part-space lines).
+///
+/// Moves stop fully wherever GRBL synchronizes the planner: M-codes (spindle,
+/// coolant, tool change, program flow), G4 dwells and probe moves. Probe moves are
+/// timed as if they ran their full distance.
+pub fn estimate_lines(
+    lines: &[impl AsRef<str>],
+    limits: &MachineLimits,
+    config: &MotionConfig,
+) -> JobEstimate {
+    let mut planner = Planner {
+        limits,
+        config,
+        blocks: Vec::new(),
+        estimate: JobEstimate {
+            line_secs: vec![0.0; lines.len()],
+            ..JobEstimate::default()
+        },
+    };
+    let mut interp = Interpreter::new();
+    for (i, line) in lines.iter().enumerate() {
+        let Ok(exec) = interp.execute(line.as_ref()) else {
+            continue;
+        };
+        let sync = exec.has_word('M') || exec.dwell_secs.is_some();
+        if sync {
+            planner.flush();
+        }
+        if let Some(m) = &exec.motion {
+            let probe = m.kind == MotionKind::Probe;
+            if probe {
+                planner.flush();
+            }
+            let mut from = m.start;
+            for p in m.points(config.arc_tolerance_mm) {
+                planner.push(i, from, p, m.kind == MotionKind::Rapid, m.feed_mm_min);
+                from = p;
+            }
+            if probe {
+                planner.flush();
+            }
+        }
+        if let Some(secs) = exec.dwell_secs {
+            planner.estimate.line_secs[i] += secs;
+            planner.estimate.total_secs += secs;
+            planner.estimate.dwell_secs += secs;
+        }
+    }
+    planner.flush();
+    planner.estimate
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn limits() -> MachineLimits {
+        MachineLimits {
+            max_rate_mm_min: [6000.0; 4],
+            accel_mm_s2: [100.0; 4],
+            junction_deviation_mm: 0.01,
+        }
+    }
+
+    fn approx(a: f64, b: f64) -> bool {
+        (a - b).abs() < 1e-6
+    }
+
+    #[test]
+    fn test_from_settings() {
+        let s = super::super::parser::parse_settings("$11=0.02\n$110=3000\n$121=250\nok").unwrap();
+        let l = MachineLimits::from_settings(&s);
+        assert_eq!(l.junction_deviation_mm, 0.02);
+        assert_eq!(l.max_rate_mm_min[0], 3000.0);
+        assert_eq!(l.max_rate_mm_min[1], 500.0);
+        assert_eq!(l.accel_mm_s2[1], 250.0);
+    }
+
+    #[test]
+    fn test_single_move_trapezoid() {
+        // 10 mm/s, 100 mm/s²: 0.1 s up, 0.1 s down, 99 mm cruise.
+        let e = estimate_lines(&["G1 X100 F600"], &limits(), &MotionConfig::default());
+        assert!(approx(e.total_secs, 10.1));
+        assert!(approx(e.cut_distance_mm, 100.0));
+        assert_eq!(e.rapid_distance_mm, 0.0);
+    }
+
+    #[test]
+    fn test_collinear_moves_do_not_stop() {
+        let e = estimate_lines(
+            &["G1 X50 F600", "X100"],
+            &limits(),
+            &MotionConfig::default(),
+        );
+        assert!(approx(e.total_secs, 10.1));
+        assert_eq!(e.line_secs.len(), 2);
+        assert!(approx(e.remaining_secs(1), e.line_secs[1]));
+    }
+
+    #[test]
+    fn test_corner_slows_down() {
+        let straight = estimate_lines(&["G1 X100 F600"], &limits(), &MotionConfig::default());
+        let corner = estimate_lines(&["G1 X50 F600", "Y50"], &limits(), &MotionConfig::default());
+        assert!(corner.total_secs > straight.total_secs);
+        assert!(corner.total_secs < straight.total_secs + 0.2);
+    }
+
+    #[test]
+    fn test_rapid_dwell_and_sync() {
+        let e = estimate_lines(
+            &["G0 X100", "G4 P2", "M3 S1000", "G1 X0 F600"],
+            &limits(),
+            &MotionConfig::default(),
+        );
+        // Rapid at 100 mm/s: 1 s up to speed over 50 mm, 1 s down: triangle.
+        assert!(approx(e.rapid_secs, 2.0));
+        assert!(approx(e.dwell_secs, 2.0));
+        assert!(approx(e.line_secs[1], 2.0));
+        assert!(approx(e.cut_secs, 10.1));
+        assert!(approx(e.total_secs, 14.1));
+    }
+
+    #[test]
+    fn test_bed_axis_limits_apply_beyond_gantry_limit() {
+        let mut l = limits();
+        l.max_rate_mm_min[3] = 600.0;
+        let config = MotionConfig::default();
+        let gantry = estimate_lines(&["G0 Y100"], &l, &config);
+        let bed = estimate_lines(&["G0 Y700", "G0 Y800"], &l, &config);
+        assert!(bed.line_secs[1] > gantry.total_secs * 5.0);
+    }
+}
@@ -0,0 +1,888 @@
+//! G-code modal interpreter.
+//!
+//! Pure and synchronous: tokenizes a line into words and tracks the modal state
+//! GRBL-HAL keeps between lines (units, distance mode, plane, WCS, feed, spindle,
+//! coolant, tool, G92 offset). Each executed line yields the resulting motion
+//! (if any) in millimetres and work coordinates, so translators and analyzers
+//! work on geometry instead of re-parsing strings.
+//!
+//! Positions are in the active work coordinate system with the G92 offset applied
+//! (program value + G92 offset). `G53` targets are converted using the machine
+//! offset set with [`Interpreter::set_machine_offset`] (zero by default).
+
+use serde::{Deserialize, Serialize};
+use std::f64::consts::PI;
+use thiserror::Error;
+
+/// Millimetres per inch (G20 conversion).
+pub const MM_PER_INCH: f64 = 25.4;
+
+/// Default chord tolerance in mm when linearizing arcs.
+pub const DEFAULT_ARC_TOLERANCE_MM: f64 = 0.01;
+
+/// Errors produced when tokenizing a G-code line.
+#[derive(Debug, Error, PartialEq)]
+pub enum GcodeError {
+    #[error("invalid word '{0}'")]
+    InvalidWord(String),
+    #[error("unterminated comment")]
+    UnterminatedComment,
+}
+
+/// One letter/value word, e.g. `G1`, `X10.5`. `raw` keeps the original text
+/// (letter uppercased) so pass-through output preserves formatting.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Word {
+    pub letter: char,
+    pub value: f64,
+    pub raw: String,
+}
+
+impl Word {
+    /// G/M code number scaled by 10 (`G38.2` -> 382, `G1` -> 10) for exact matching.
+    pub(crate) fn code(&self) -> i32 {
+        (self.value * 10.0).round() as i32
+    }
+}
+
+/// True if GRBL-HAL accepts the M-code (`code` as returned by [`Word::code`]).
+pub(crate) fn is_supported_mcode(code: i32) -> bool {
+    matches!(
+        code,
+        0 | 10 | 20 | 300 | 30 | 40 | 50 | 60 | 70 | 80 | 90 | 610 | 480..=560 | 620..=680
+    )
+}
+
+/// Point in 3D (mm).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Point {
+    pub x: f64,
+    pub y: f64,
+    pub z: f64,
+}
+
+impl Point {
+    pub const fn new(x: f64, y: f64, z: f64) -> Self {
+        Self { x, y, z }
+    }
+
+    fn axis(&self, i: usize) -> f64 {
+        match i {
+            0 => self.x,
+            1 => self.y,
+            _ => self.z,
+        }
+    }
+
+    fn set_axis(&mut self, i: usize, v: f64) {
+        match i {
+            0 => self.x = v,
+            1 => self.y = v,
+            _ => self.z = v,
+        }
+    }
+
+    /// Euclidean distance to `other`.
+    pub fn distance(&self, other: &Point) -> f64 {
+        ((self.x - other.x).powi(2) + (self.y - other.y).powi(2) + (self.z - other.z).powi(2))
+            .sqrt()
+    }
+
+    /// Linear interpolation: `self + t * (other - self)`.
+    pub fn lerp(&self, other: &Point, t: f64) -> Point {
+        Point {
+            x: self.x + t * (other.x - self.x),
+            y: self.y + t * (other.y - self.y),
+            z: self.z + t * (other.z - self.z),
+        }
+    }
+}
+
+/// Length units (G20/G21).
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Units {
+    Mm,
+    Inch,
+}
+
+impl Units {
+    /// Convert a value in these units to mm.
+    pub fn to_mm(self, v: f64) -> f64 {
+        match self {
+            Units::Mm => v,
+            Units::Inch => v * MM_PER_INCH,
+        }
+    }
+
+    /// Convert a value in mm to these units.
+    pub fn from_mm(self, mm: f64) -> f64 {
+        match self {
+            Units::Mm => mm,
+            Units::Inch => mm / MM_PER_INCH,
+        }
+    }
+
+    /// The G-code word selecting these units.
+    pub fn word(self) -> &'static str {
+        match self {
+            Units::Mm => "G21",
+            Units::Inch => "G20",
+        }
+    }
+}
+
+/// Distance mode (G90/G91).
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DistanceMode {
+    Absolute,
+    Relative,
+}
+
+/// Arc plane (G17/G18/G19).
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Plane {
+    XY,
+    ZX,
+    YZ,
+}
+
+impl Plane {
+    /// (first in-plane axis, second in-plane axis, linear axis) as indices x=0, y=1, z=2.
+    fn axes(self) -> (usize, usize, usize) {
+        match self {
+            Plane::XY => (0, 1, 2),
+            Plane::ZX => (2, 0, 1),
+            Plane::YZ => (1, 2, 0),
+        }
+    }
+}
+
+/// Modal motion mode (G0/G1/G2/G3/G38.x/G80).
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum MotionMode {
+    Rapid,
+    Linear,
+    ArcCw,
+    ArcCcw,
+    /// G38.2–G38.5 probe move.
+    Probe,
+    /// G80: motion mode cancelled.
+    None,
+}
+
+impl MotionMode {
+    /// G-code word that selects this mode (probe maps to `G38.2`).
+    pub fn word(self) -> &'static str {
+        match self {
+            MotionMode::Rapid => "G0",
+            MotionMode::Linear => "G1",
+            MotionMode::ArcCw => "G2",
+            MotionMode::ArcCcw => "G3",
+            MotionMode::Probe => "G38.2",
+            MotionMode::None => "G80",
+        }
+    }
+}
+
+/// Spindle direction (M3/M4/M5).
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SpindleState {
+    Off,
+    Cw,
+    Ccw,
+}
+
+/// Coolant outputs (M7/M8/M9).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Coolant {
+    pub mist: bool,
+    pub flood: bool,
+}
+
+/// Program flow words (M0/M1/M2/M30).
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ProgramFlow {
+    /// M0: unconditional pause.
+    Pause,
+    /// M1: optional stop.
+    OptionalStop,
+    /// M2: program end.
+    End,
+    /// M30: program end and rewind.
+    EndRewind,
+}
+
+/// Modal state carried between lines.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ModalState {
+    pub motion: MotionMode,
+    pub units: Units,
+    pub distance: DistanceMode,
+    /// Arc IJK distance mode: `true` for G91.1 (incremental, default), `false` for G90.1.
+    pub arc_incremental: bool,
+    pub plane: Plane,
+    /// Active work coordinate system, 1 = G54 .. 9 = G59.3.
+    pub wcs: u8,
+    /// Feed rate in mm/min.
+    pub feed_mm_min: f64,
+    pub spindle: SpindleState,
+    pub spindle_speed: f64,
+    pub coolant: Coolant,
+    /// Tool selected by the last `T` word.
+    pub selected_tool: Option<u32>,
+    /// Tool loaded by the last `M6`.
+    pub tool: Option<u32>,
+    /// Dynamic tool length offset (`G43.1 Z`) in mm; `None` after `G49`.
+    pub tool_length_offset_mm: Option<f64>,
+}
+
+impl Default for ModalState {
+    /// GRBL power-on defaults: G0 G54 G17 G21 G90 G91.1 G94 M5 M9 T0 F0 S0.
+    fn default() -> Self {
+        Self {
+            motion: MotionMode::Rapid,
+            units: Units::Mm,
+            distance: DistanceMode::Absolute,
+            arc_incremental: true,
+            plane: Plane::XY,
+            wcs: 1,
+            feed_mm_min: 0.0,
+            spindle: SpindleState::Off,
+            spindle_speed: 0.0,
+            coolant: Coolant::default(),
+            selected_tool: None,
+            tool: None,
+            tool_length_offset_mm: None,
+        }
+    }
+}
+
+impl ModalState {
+    /// Line that puts units, distance mode and feed back to this state, for
+    /// after a routine (probing, tool change) sent its own `G21 G90` and feed.
+    pub fn restore_line(&self) -> String {
+        let mut line = self.units.word().to_string();
+        line.push_str(match self.distance {
+            DistanceMode::Absolute => " G90",
+            DistanceMode::Relative => " G91",
+        });
+        if self.feed_mm_min > 0.0 {
+            line.push_str(&format!(" F{:.4}", self.units.from_mm(self.feed_mm_min)));
+        }
+        line
+    }
+}
+
+/// Kind of a motion produced by a line.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum MotionKind {
+    Rapid,
+    Linear,
+    ArcCw,
+    ArcCcw,
+    Probe,
+}
+
+/// Arc geometry for G2/G3 motions.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ArcGeometry {
+    pub center: Point,
+    pub plane: Plane,
+    pub radius: f64,
+    /// Swept angle in radians (always positive; direction from the motion kind).
+    pub sweep: f64,
+}
+
+/// A single motion from `start` to `end` (mm, work coordinates).
+#[derive(Clone, Debug, PartialEq)]
+pub struct Motion {
+    pub kind: MotionKind,
+    pub start: Point,
+    pub end: Point,
+    /// Present for G2/G3.
+    pub arc: Option<ArcGeometry>,
+    /// Feed rate in mm/min at the time of the move (rapids use the machine max).
+    pub feed_mm_min: f64,
+    /// Target was given in machine coordinates (`G53`).
+    pub machine_coords: bool,
+}
+
+impl Motion {
+    /// True for G2/G3.
+    pub fn is_arc(&self) -> bool {
+        self.arc.is_some()
+    }
+
+    /// Path length in mm (arc length for helices included).
+    pub fn length(&self) -> f64 {
+        match &self.arc {
+            Some(arc) => {
+                let (_, _, lin) = arc.plane.axes();
+                let planar = arc.radius * arc.sweep;
+                let linear = self.end.axis(lin) - self.start.axis(lin);
+                (planar * planar + linear * linear).sqrt()
+            }
+            None => self.start.distance(&self.end),
+        }
+    }
+
+    /// Points along the motion after `start`, ending exactly at `end`.
+    /// Lines yield `[end]`; arcs are linearized so no chord deviates more than
+    /// `tolerance_mm` from the true arc.
+    pub fn points(&self, tolerance_mm: f64) -> Vec<Point> {
+        let Some(arc) = &self.arc else {
+            return vec![self.end];
+        };
+        let (a0, a1, lin) = arc.plane.axes();
+        let tol = tolerance_mm.max(1e-6);
+        let step = if tol >= arc.radius {
+            PI / 2.0
+        } else {
+            2.0 * (1.0 - tol / arc.radius).acos()
+        };
+        let n = ((arc.sweep / step).ceil() as usize).max(1);
+        let start_angle = (self.start.axis(a1) - arc.center.axis(a1))
+            .atan2(self.start.axis(a0) - arc.center.axis(a0));
+        let dir = if self.kind == MotionKind::ArcCw {
+            -1.0
+        } else {
+            1.0
+        };
+        let lin_start = self.start.axis(lin);
+        let lin_delta = self.end.axis(lin) - lin_start;
+        let mut out = Vec::with_capacity(n);
+        for i in 1..n {
+            let t = i as f64 / n as f64;
+            let angle = start_angle + dir * arc.sweep * t;
+            let mut p = Point::default();
+            p.set_axis(a0, arc.center.axis(a0) + arc.radius * angle.cos());
+            p.set_axis(a1, arc.center.axis(a1) + arc.radius * angle.sin());
+            p.set_axis(lin, lin_start + lin_delta * t);
+            out.push(p);
+        }
+        out.push(self.end);
+        out
+    }
+}
+
+/// Everything a single line did, as seen by the interpreter.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Executed {
+    /// Words on the line (comments removed).
+    pub words: Vec<Word>,
+    /// Motion produced by the line, if any.
+    pub motion: Option<Motion>,
+    /// The line carries its own motion word (G0/G1/G2/G3/G38.x/G80).
+    pub explicit_motion: bool,
+    /// Tool requested by `M6` (with the `T` word on the same or an earlier line).
+    pub tool_change: Option<u32>,
+    /// M0/M1/M2/M30 on this line.
+    pub program_flow: Option<ProgramFlow>,
+    /// `G4` dwell in seconds.
+    pub dwell_secs: Option<f64>,
+    /// Words GRBL-HAL does not support or this interpreter does not model (e.g. `G81`, `M98`).
+    pub unsupported: Vec<String>,
+}
+
+impl Executed {
+    /// True if the line has a word with this letter.
+    pub fn has_word(&self, letter: char) -> bool {
+        self.words.iter().any(|w| w.letter == letter)
+    }
+}
+
+/// Splits a line into words, dropping `( ... )` and `;` comments and `%` markers.
+/// Letters are uppercased. System commands (`$...`) and empty lines yield no words.
+pub fn parse_words(line: &str) -> Result<Vec<Word>, GcodeError> {
+    let line = line.trim();
+    if line.starts_with('$') {
+        return Ok(Vec::new());
+    }
+    let chars: Vec<char> = line.chars().collect();
+    let mut words = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        if c.is_whitespace() || c == '%' {
+            i += 1;
+            continue;
+        }
+        if c == ';' {
+            break;
+        }
+        if c == '(' {
+            match chars[i..].iter().position(|&c| c == ')') {
+                Some(end) => {
+                    i += end + 1;
+                    continue;
+                }
+                None => return Err(GcodeError::UnterminatedComment),
+            }
+        }
+        if !c.is_ascii_alphabetic() {
+            return Err(GcodeError::InvalidWord(c.to_string()));
+        }
+        let letter = c.to_ascii_uppercase();
+        i += 1;
+        let mut num = String::new();
+        while i < chars.len() {
+            let d = chars[i];
+            if d.is_ascii_digit() || d == '.' || d == '-' || d == '+' {
+                num.push(d);
+                i += 1;
+            } else if d == ' ' || d == '\t' {
+                // GRBL ignores whitespace inside numbers ("X 10" == "X10").
+                if num.is_empty() || chars.get(i + 1).is_some_and(|n| n.is_ascii_digit()) {
+                    i += 1;
+                } else {
+                    break;
+                }
+            } else {
+                break;
+            }
+        }
+        let value: f64 = num
+            .parse()
+            .map_err(|_| GcodeError::InvalidWord(format!("{}{}", letter, num)))?;
+        words.push(Word {
+            letter,
+            value,
+            raw: format!("{}{}", letter, num),
+        });
+    }
+    Ok(words)
+}
+
+/// Tracks modal state and position across lines.
+#[derive(Clone, Debug)]
+pub struct Interpreter {
+    modal: ModalState,
+    position: Point,
+    g92_offset: Point,
+    g92_suspended: Option<Point>,
+    machine_offset: Point,
+}
+
+impl Default for Interpreter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Interpreter {
+    /// Interpreter at power-on defaults with the tool at work zero.
+    pub fn new() -> Self {
+        Self {
+            modal: ModalState::default(),
+            position: Point::default(),
+            g92_offset: Point::default(),
+            g92_suspended: None,
+            machine_offset: Point::default(),
+        }
+    }
+
+    /// Current modal state.
+    pub fn modal(&self) -> &ModalState {
+        &self.modal
+    }
+
+    /// Current position (mm, work coordinates).
+    pub fn position(&self) -> Point {
+        self.position
+    }
+
+    /// Active G92 offset (mm).
+    pub fn g92_offset(&self) -> Point {
+        self.g92_offset
+    }
+
+    /// Set the starting position (mm, work coordinates).
+    pub fn set_position(&mut self, position: Point) {
+        self.position = position;
+    }
+
+    /// Machine position of work zero (mm), used to convert `G53` targets.
+    pub fn set_machine_offset(&mut self, offset: Point) {
+        self.machine_offset = offset;
+    }
+
+    /// Machine position of work zero (mm).
+    pub fn machine_offset(&self) -> Point {
+        self.machine_offset
+    }
+
+    /// Execute one line: update modal state and position, return what it did.
+    pub fn execute(&mut self, line: &str) -> Result<Executed, GcodeError> {
+        let words = parse_words(line)?;
+        let mut exec = Executed::default();
+        if words.is_empty() {
+            return Ok(exec);
+        }
+
+        let mut motion_word: Option<MotionMode> = None;
+        let mut non_modal: Option<i32> = None;
+        let mut g53 = false;
+        let mut axes: [Option<f64>; 3] = [None; 3];
+        let mut offsets: [Option<f64>; 3] = [None; 3];
+        let mut radius: Option<f64> = None;
+        let mut feed: Option<f64> = None;
+        let mut p_word: Option<f64> = None;
+        let mut q_word: Option<f64> = None;
+        let mut m6 = false;
+        let mut m61 = false;
+
+        // Modal groups that must be applied before values are interpreted (units, distance).
+        for w in &words {
+            match (w.letter, w.code()) {
+                ('G', 200) => self.modal.units = Units::Inch,
+                ('G', 210) => self.modal.units = Units::Mm,
+                ('G', 900) => self.modal.distance = DistanceMode::Absolute,
+                ('G', 910) => self.modal.distance = DistanceMode::Relative,
+                ('G', 901) => self.modal.arc_incremental = false,
+                ('G', 911) => self.modal.arc_incremental = true,
+                _ => {}
+            }
+        }
+        let units = self.modal.units;
+
+        for w in &words {
+            match w.letter {
+                'G' => match w.code() {
+                    0 => motion_word = Some(MotionMode::Rapid),
+                    10 => motion_word = Some(MotionMode::Linear),
+                    20 => motion_word = Some(MotionMode::ArcCw),
+                    30 => motion_word = Some(MotionMode::ArcCcw),
+                    382..=385 => motion_word = Some(MotionMode::Probe),
+                    800 => motion_word = Some(MotionMode::None),
+                    40 => non_modal = Some(40),
+                    100 | 280 | 281 | 300 | 301 | 920 => non_modal = Some(w.code()),
+                    921 => self.g92_offset = Point::default(),
+                    922 => {
+                        self.g92_suspended = Some(self.g92_offset);
+                        self.g92_offset = Point::default();
+                    }
+                    923 => {
+                        if let Some(saved) = self.g92_suspended.take() {
+                            self.g92_offset = saved;
+                        }
+                    }
+                    530 => g53 = true,
+                    170 => self.modal.plane = Plane::XY,
+                    180 => self.modal.plane = Plane::ZX,
+                    190 => self.modal.plane = Plane::YZ,
+                    540..=590 if w.code() % 10 == 0 => {
+                        self.modal.wcs = ((w.code() - 540) / 10 + 1) as u8;
+                    }
+                    591..=593 => self.modal.wcs = (w.code() - 591 + 7) as u8,
+                    431 => non_modal = Some(431),
+                    490 => self.modal.tool_length_offset_mm = None,
+                    200 | 210 | 900 | 910 | 901 | 911 | 940 | 400 | 610 | 640 => {}
+                    _ => exec.unsupported.push(w.raw.clone()),
+                },
+                'M' => match w.code() {
+                    0 => exec.program_flow = Some(ProgramFlow::Pause),
+                    10 => exec.program_flow = Some(ProgramFlow::OptionalStop),
+                    20 => exec.program_flow = Some(ProgramFlow::End),
+                    300 => exec.program_flow = Some(ProgramFlow::EndRewind),
+                    30 => self.modal.spindle = SpindleState::Cw,
+                    40 => self.modal.spindle = SpindleState::Ccw,
+                    50 => self.modal.spindle = SpindleState::Off,
+                    60 => m6 = true,
+                    70 => self.modal.coolant.mist = true,
+                    80 => self.modal.coolant.flood = true,
+                    90 => self.modal.coolant = Coolant::default(),
+                    610 => m61 = true,
+                    c if is_supported_mcode(c) => {}
+                    _ => exec.unsupported.push(w.raw.clone()),
+                },
+                'X' => axes[0] = Some(units.to_mm(w.value)),
+                'Y' => axes[1] = Some(units.to_mm(w.value)),
+                'Z' => axes[2] = Some(units.to_mm(w.value)),
+                'I' => offsets[0] = Some(units.to_mm(w.value)),
+                'J' => offsets[1] = Some(units.to_mm(w.value)),
+                'K' => offsets[2] = Some(units.to_mm(w.value)),
+                'R' => radius = Some(units.to_mm(w.value)),
+                'F' => feed = Some(units.to_mm(w.value)),
+                'S' => self.modal.spindle_speed = w.value,
+                'T' => self.modal.selected_tool = Some(w.value.max(0.0) as u32),
+                'P' => p_word = Some(w.value),
+                'Q' => q_word = Some(w.value),
+                'N' | 'L' | 'H' | 'D' | 'A' | 'B' | 'C' | 'E' | 'U' | 'V' | 'W' => {}
+                _ => exec.unsupported.push(w.raw.clone()),
+            }
+        }
+
+        if let Some(f) = feed {
+            self.modal.feed_mm_min = f;
+        }
+        if m6 {
+            self.modal.tool = self.modal.selected_tool;
+            exec.tool_change = self.modal.selected_tool.or(Some(0));
+        }
+        if let (true, Some(q)) = (m61, q_word) {
+            // M61 Qn: set the current tool without a change.
+            self.modal.tool = Some(q.max(0.0) as u32);
+            self.modal.selected_tool = self.modal.tool;
+        }
+        if let Some(m) = motion_word {
+            self.modal.motion = m;
+            exec.explicit_motion = true;
+        }
+
+        let has_axes = axes.iter().any(Option::is_some);
+        match non_modal {
+            Some(40) => exec.dwell_secs = p_word,
+            Some(920) => {
+                for (i, v) in axes.iter().enumerate() {
+                    if let Some(v) = v {
+                        self.g92_offset.set_axis(i, self.position.axis(i) - v);
+                    }
+                }
+            }
+            Some(431) => self.modal.tool_length_offset_mm = axes[2],
+            // G10/G28/G30 consume axis words; G28/G30 move to positions we do not track.
+            Some(_) => {}
+            None if has_axes && self.modal.motion != MotionMode::None => {
+                exec.motion = Some(self.motion_to(axes, offsets, radius, g53));
+            }
+            None => {}
+        }
+        exec.words = words;
+        Ok(exec)
+    }
+
+    /// Build the motion for the current motion mode and update position.
+    fn motion_to(
+        &mut self,
+        axes: [Option<f64>; 3],
+        offsets: [Option<f64>; 3],
+        radius: Option<f64>,
+        g53: bool,
+    ) -> Motion {
+        let start = self.position;
+        let mut end = start;
+        for (i, v) in axes.iter().enumerate() {
+            let Some(v) = *v else { continue };
+            let target = if g53 {
+                v - self.machine_offset.axis(i)
+            } else if self.modal.distance == DistanceMode::Absolute {
+                v + self.g92_offset.axis(i)
+            } else {
+                start.axis(i) + v
+            };
+            end.set_axis(i, target);
+        }
+        let kind = match self.modal.motion {
+            MotionMode::Rapid => MotionKind::Rapid,
+            MotionMode::ArcCw => MotionKind::ArcCw,
+            MotionMode::ArcCcw => MotionKind::ArcCcw,
+            MotionMode::Probe => MotionKind::Probe,
+            MotionMode::Linear | MotionMode::None => MotionKind::Linear,
+        };
+        let arc = match kind {
+            MotionKind::ArcCw | MotionKind::ArcCcw => {
+                self.arc_geometry(start, end, offsets, radius, kind == MotionKind::ArcCw)
+            }
+            _ => None,
+        };
+        // An arc with no usable center degrades to a straight move (GRBL would reject it).
+        let kind = if arc.is_none() && matches!(kind, MotionKind::ArcCw | MotionKind::ArcCcw) {
+            MotionKind::Linear
+        } else {
+            kind
+        };
+        self.position = end;
+        Motion {
+            kind,
+            start,
+            end,
+            arc,
+            feed_mm_min: self.modal.feed_mm_min,
+            machine_coords: g53,
+        }
+    }
+
+    /// Arc center, radius and sweep from IJK offsets or an R word.
+    fn arc_geometry(
+        &self,
+        start: Point,
+        end: Point,
+        offsets: [Option<f64>; 3],
+        radius: Option<f64>,
+        clockwise: bool,
+    ) -> Option<ArcGeometry> {
+        let plane = self.modal.plane;
+        let (a0, a1, _) = plane.axes();
+        let (s0, s1) = (start.axis(a0), start.axis(a1));
+        let (e0, e1) = (end.axis(a0), end.axis(a1));
+        let (c0, c1) = if let Some(r) = radius {
+            // R format: center on the perpendicular bisector; negative R selects the long arc.
+            let (dx, dy) = (e0 - s0, e1 - s1);
+            let d = (dx * dx + dy * dy).sqrt();
+            if d < 1e-9 || r.abs() * 2.0 < d - 1e-6 {
+                return None;
+            }
+            let h = (r * r - d * d / 4.0).max(0.0).sqrt();
+            let mut h_signed = if clockwise { -h } else { h };
+            if r < 0.0 {
+                h_signed = -h_signed;
+            }
+            let (mx, my) = (s0 + dx / 2.0, s1 + dy / 2.0);
+            (mx - h_signed * dy / d, my + h_signed * dx / d)
+        } else {
+            let off = |i: usize| offsets[i].unwrap_or(0.0);
+            if offsets[a0].is_none() && offsets[a1].is_none() {
+                return None;
+            }
+            if self.modal.arc_incremental {
+                (s0 + off(a0), s1 + off(a1))
+            } else {
+                let g92 = self.g92_offset;
+                (off(a0) + g92.axis(a0), off(a1) + g92.axis(a1))
+            }
+        };
+        let r = ((s0 - c0).powi(2) + (s1 - c1).powi(2)).sqrt();
+        if r < 1e-9 {
+            return None;
+        }
+        let ang_s = (s1 - c1).atan2(s0 - c0);
+        let ang_e = (e1 - c1).atan2(e0 - c0);
+        let mut sweep = if clockwise {
+            ang_s - ang_e
+        } else {
+            ang_e - ang_s
+        };
+        while sweep < 0.0 {
+            sweep += 2.0 * PI;
+        }
+        if sweep < 1e-9 {
+            // Same start and end point: full circle.
+            sweep = 2.0 * PI;
+        }
+        let mut center = start;
+        center.set_axis(a0, c0);
+        center.set_axis(a1, c1);
+        Some(ArcGeometry {
+            center,
+            plane,
+            radius: r,
+            sweep,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn approx(a: f64, b: f64) -> bool {
+        (a - b).abs() < 1e-6
+    }
+
+    #[test]
+    fn test_parse_words() {
+        let w = parse_words("g1 x10.5 Y-2.5 (comment) F300 ; tail").unwrap();
+        let letters: Vec<char> = w.iter().map(|w| w.letter).collect();
+        assert_eq!(letters, vec!['G', 'X', 'Y', 'F']);
+        assert_eq!(w[1].value, 10.5);
+        assert_eq!(w[2].value, -2.5);
+        assert_eq!(w[3].raw, "F300");
+        assert!(parse_words("G1Y100").unwrap().len() == 2);
+        assert!(parse_words("; comment").unwrap().is_empty());
+        assert!(parse_words("$H").unwrap().is_empty());
+        assert!(parse_words("G1 X").is_err());
+        assert!(parse_words("G1 (open").is_err());
+    }
+
+    #[test]
+    fn test_linear_motion_absolute_relative() {
+        let mut it = Interpreter::new();
+        let e = it.execute("G0 Y10").unwrap();
+        assert_eq!(e.motion.unwrap().kind, MotionKind::Rapid);
+        it.execute("G91").unwrap();
+        let m = it.execute("G1 Y5 F100").unwrap().motion.unwrap();
+        assert_eq!(m.kind, MotionKind::Linear);
+        assert_eq!(m.start.y, 10.0);
+        assert_eq!(m.end.y, 15.0);
+        assert_eq!(it.modal().feed_mm_min, 100.0);
+        assert!(it.execute("G28").unwrap().motion.is_none());
+        assert!(it.execute("M3 S1000").unwrap().motion.is_none());
+    }
+
+    #[test]
+    fn test_inch_units() {
+        let mut it = Interpreter::new();
+        let m = it.execute("G20 G1 Y1 F10").unwrap().motion.unwrap();
+        assert!(approx(m.end.y, 25.4));
+        assert!(approx(it.modal().feed_mm_min, 254.0));
+        it.execute("G91").unwrap();
+        assert_eq!(it.modal().restore_line(), "G20 G91 F10.0000");
+        assert_eq!(ModalState::default().restore_line(), "G21 G90");
+    }
+
+    #[test]
+    fn test_g92_and_g53() {
+        let mut it = Interpreter::new();
+        it.execute("G0 Y100").unwrap();
+        it.execute("G92 Y0").unwrap();
+        let m = it.execute("G0 Y50").unwrap().motion.unwrap();
+        assert!(approx(m.end.y, 150.0));
+        it.execute("G92.1").unwrap();
+        it.set_machine_offset(Point::new(0.0, 20.0, 0.0));
+        let m = it.execute("G53 G0 Y30").unwrap().motion.unwrap();
+        assert!(m.machine_coords);
+        assert!(approx(m.end.y, 10.0));
+    }
+
+    #[test]
+    fn test_arc_ijk_and_linearize() {
+        let mut it = Interpreter::new();
+        it.execute("G0 X10 Y0").unwrap();
+        // Quarter circle CCW around origin to (0, 10).
+        let m = it
+            .execute("G3 X0 Y10 I-10 J0 F100")
+            .unwrap()
+            .motion
+            .unwrap();
+        let arc = m.arc.unwrap();
+        assert!(approx(arc.radius, 10.0));
+        assert!(approx(arc.sweep, PI / 2.0));
+        assert!(approx(m.length(), 10.0 * PI / 2.0));
+        let pts = m.points(0.01);
+        assert!(pts.len() > 4);
+        assert_eq!(*pts.last().unwrap(), m.end);
+        for p in &pts {
+            assert!(approx((p.x * p.x + p.y * p.y).sqrt(), 10.0));
+        }
+    }
+
+    #[test]
+    fn test_arc_radius_format() {
+        let mut it = Interpreter::new();
+        it.execute("G0 X0 Y0").unwrap();
+        let m = it.execute("G2 X10 Y0 R5").unwrap().motion.unwrap();
+        let arc = m.arc.unwrap();
+        assert!(approx(arc.center.x, 5.0));
+        assert!(approx(arc.center.y, 0.0));
+        // Clockwise from (0,0) to (10,0) around (5,0) passes through y = +5.
+        let max_y = m.points(0.01).iter().map(|p| p.y).fold(f64::MIN, f64::max);
+        assert!(max_y > 4.99);
+    }
+
+    #[test]
+    fn test_modal_words() {
+        let mut it = Interpreter::new();
+        let e = it.execute("T2 M6").unwrap();
+        assert_eq!(e.tool_change, Some(2));
+        let e = it.execute("M0").unwrap();
+        assert_eq!(e.program_flow, Some(ProgramFlow::Pause));
+        let e = it.execute("G55 M8 G4 P1.5").unwrap();
+        assert_eq!(e.dwell_secs, Some(1.5));
+        assert_eq!(it.modal().wcs, 2);
+        assert!(it.modal().coolant.flood);
+        it.execute("M61 Q4").unwrap();
+        assert_eq!(it.modal().tool, Some(4));
+        let e = it.execute("G81 X1 Y1 M98").unwrap();
+        assert_eq!(e.unsupported, vec!["G81".to_string(), "M98".to_string()]);
+    }
+}
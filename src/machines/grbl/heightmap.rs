@@ -0,0 +1,345 @@
+//! Surface height map for PCB milling and engraving on warped stock.
+//!
+//! Probes a rectangular XY grid in work coordinates with `G38.3` (a miss is
+//! reported, not an alarm, so one bad point does not end the run). Points are
+//! visited in a serpentine order; each one rapids to the clearance height, moves
+//! over the point and probes down to the probe depth. Failed points stay `None`.
+//! Coordinates are sent as they are (no bed-axis split), so keep the grid within
+//! gantry Y travel.
+
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use thiserror::Error;
+
+/// Most points a map may have (a 100 x 100 grid).
+pub const MAX_HEIGHT_MAP_POINTS: usize = 10_000;
+
+/// Errors from [`HeightMap::new`] and [`HeightMap::load`].
+#[derive(Debug, Error, PartialEq)]
+pub enum HeightMapError {
+    #[error("grid spacing must be above 0 mm, got {0}")]
+    InvalidSpacing(f64),
+    #[error("grid bounds must be finite")]
+    InvalidBounds,
+    #[error("grid has {cols}x{rows} points (at most {max})")]
+    TooManyPoints {
+        cols: usize,
+        rows: usize,
+        max: usize,
+    },
+    #[error("map has {cols}x{rows} points but {len} heights")]
+    SizeMismatch {
+        cols: usize,
+        rows: usize,
+        len: usize,
+    },
+}
+
+/// Grid and probing parameters (mm, work coordinates).
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct HeightMapOptions {
+    /// Lower-left corner (X, Y).
+    pub min: [f64; 2],
+    /// Upper-right corner (X, Y).
+    pub max: [f64; 2],
+    /// Target distance between points; the grid spans the bounds exactly, so
+    /// actual spacing is at most this.
+    pub spacing_mm: f64,
+    /// Work Z for moves between points.
+    pub clearance_z_mm: f64,
+    /// Lowest work Z a probe may reach.
+    pub probe_depth_mm: f64,
+    pub probe_feed_mm_min: f64,
+}
+
+impl Default for HeightMapOptions {
+    fn default() -> Self {
+        Self {
+            min: [0.0, 0.0],
+            max: [100.0, 100.0],
+            spacing_mm: 10.0,
+            clearance_z_mm: 2.0,
+            probe_depth_mm: -2.0,
+            probe_feed_mm_min: 50.0,
+        }
+    }
+}
+
+impl HeightMapOptions {
+    /// Lines to probe the point at (`x`, `y`); the last is the `G38.3`.
+    pub fn point_lines(&self, x: f64, y: f64) -> [String; 3] {
+        [
+            format!("G21 G90 G0 Z{:.4}", self.clearance_z_mm),
+            format!("G0 X{:.4} Y{:.4}", x, y),
+            format!(
+                "G38.3 Z{:.4} F{:.1}",
+                self.probe_depth_mm, self.probe_feed_mm_min
+            ),
+        ]
+    }
+
+    /// Line to leave the last point at the clearance height.
+    pub fn retract_line(&self) -> String {
+        format!("G21 G90 G0 Z{:.4}", self.clearance_z_mm)
+    }
+}
+
+/// Probed surface heights on a regular grid.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct HeightMap {
+    /// Position of grid point (0, 0) (X, Y, work mm).
+    pub origin: [f64; 2],
+    /// Distance between columns and rows (mm).
+    pub step: [f64; 2],
+    pub cols: usize,
+    pub rows: usize,
+    /// Work Z per point, row-major from `origin`; `None` where probing failed.
+    pub z: Vec<Option<f64>>,
+}
+
+fn divisions(span: f64, spacing: f64) -> (usize, f64) {
+    // Saturates for huge spans; the point count check rejects those.
+    let n = (span.abs() / spacing).ceil().max(1.0) as usize;
+    (n.saturating_add(1), span.abs() / n as f64)
+}
+
+impl HeightMap {
+    /// Empty map covering `options`' bounds.
+    pub fn new(options: &HeightMapOptions) -> Result<Self, HeightMapError> {
+        if options.spacing_mm.is_nan() || options.spacing_mm <= 0.0 {
+            return Err(HeightMapError::InvalidSpacing(options.spacing_mm));
+        }
+        if !options
+            .min
+            .iter()
+            .chain(&options.max)
+            .all(|v| v.is_finite())
+        {
+            return Err(HeightMapError::InvalidBounds);
+        }
+        let (cols, sx) = divisions(options.max[0] - options.min[0], options.spacing_mm);
+        let (rows, sy) = divisions(options.max[1] - options.min[1], options.spacing_mm);
+        if cols.saturating_mul(rows) > MAX_HEIGHT_MAP_POINTS {
+            return Err(HeightMapError::TooManyPoints {
+                cols,
+                rows,
+                max: MAX_HEIGHT_MAP_POINTS,
+            });
+        }
+        Ok(Self {
+            origin: [
+                options.min[0].min(options.max[0]),
+                options.min[1].min(options.max[1]),
+            ],
+            step: [sx, sy],
+            cols,
+            rows,
+            z: vec![None; cols * rows],
+        })
+    }
+
+    /// XY of grid point (`col`, `row`).
+    pub fn point(&self, col: usize, row: usize) -> [f64; 2] {
+        [
+            self.origin[0] + col as f64 * self.step[0],
+            self.origin[1] + row as f64 * self.step[1],
+        ]
+    }
+
+    /// Grid indices in probing order: rows alternate direction so each move is short.
+    pub fn probe_order(&self) -> Vec<(usize, usize)> {
+        (0..self.rows)
+            .flat_map(|row| {
+                let cols: Vec<usize> = if row % 2 == 0 {
+                    (0..self.cols).collect()
+                } else {
+                    (0..self.cols).rev().collect()
+                };
+                cols.into_iter().map(move |col| (col, row))
+            })
+            .collect()
+    }
+
+    pub fn get(&self, col: usize, row: usize) -> Option<f64> {
+        self.z.get(row * self.cols + col).copied().flatten()
+    }
+
+    pub fn set(&mut self, col: usize, row: usize, z: Option<f64>) {
+        if col < self.cols && row < self.rows {
+            self.z[row * self.cols + col] = z;
+        }
+    }
+
+    /// Number of points where probing failed.
+    pub fn failed(&self) -> usize {
+        self.z.iter().filter(|z| z.is_none()).count()
+    }
+
+    /// Lowest and highest probed Z.
+    pub fn range(&self) -> Option<(f64, f64)> {
+        self.z.iter().flatten().fold(None, |acc, &z| match acc {
+            None => Some((z, z)),
+            Some((lo, hi)) => Some((lo.min(z), hi.max(z))),
+        })
+    }
+
+    /// True if (`x`, `y`) lies within the probed grid,
+    /// `origin..=origin + step * (n - 1)` on both axes.
+    pub fn contains(&self, x: f64, y: f64) -> bool {
+        const EPS_MM: f64 = 1e-6;
+        let within = |v: f64, origin: f64, step: f64, n: usize| {
+            let end = origin + step * n.saturating_sub(1) as f64;
+            v >= origin - EPS_MM && v <= end + EPS_MM
+        };
+        within(x, self.origin[0], self.step[0], self.cols)
+            && within(y, self.origin[1], self.step[1], self.rows)
+    }
+
+    /// Bilinear height at (`x`, `y`), clamped to the grid (see
+    /// [`HeightMap::contains`]). `None` if a surrounding point failed.
+    pub fn height_at(&self, x: f64, y: f64) -> Option<f64> {
+        let cell = |v: f64, origin: f64, step: f64, n: usize| {
+            let t = if step > 0.0 { (v - origin) / step } else { 0.0 };
+            let t = t.clamp(0.0, (n - 1) as f64);
+            let i = (t.floor() as usize).min(n.saturating_sub(2));
+            (i, t - i as f64)
+        };
+        let (c, fx) = cell(x, self.origin[0], self.step[0], self.cols);
+        let (r, fy) = cell(y, self.origin[1], self.step[1], self.rows);
+        let c1 = (c + 1).min(self.cols - 1);
+        let r1 = (r + 1).min(self.rows - 1);
+        let bottom = self.get(c, r)? * (1.0 - fx) + self.get(c1, r)? * fx;
+        let top = self.get(c, r1)? * (1.0 - fx) + self.get(c1, r1)? * fx;
+        Some(bottom * (1.0 - fy) + top * fy)
+    }
+
+    /// Save as JSON.
+    pub fn save(&self, path: &Path) -> std::io::Result<()> {
+        let json = serde_json::to_string_pretty(self).map_err(std::io::Error::other)?;
+        std::fs::write(path, json)
+    }
+
+    /// Load a map saved with [`HeightMap::save`]. Maps without points, or with
+    /// a height count that does not match the grid, are rejected as invalid data.
+    pub fn load(path: &Path) -> std::io::Result<Self> {
+        let text = std::fs::read_to_string(path)?;
+        let map: Self = serde_json::from_str(&text)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+        if map.cols == 0 || map.rows == 0 || map.cols.checked_mul(map.rows) != Some(map.z.len()) {
+            let err = HeightMapError::SizeMismatch {
+                cols: map.cols,
+                rows: map.rows,
+                len: map.z.len(),
+            };
+            return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, err));
+        }
+        Ok(map)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn options() -> HeightMapOptions {
+        HeightMapOptions {
+            min: [0.0, 0.0],
+            max: [20.0, 9.0],
+            spacing_mm: 10.0,
+            ..HeightMapOptions::default()
+        }
+    }
+
+    #[test]
+    fn test_grid_and_order() {
+        let map = HeightMap::new(&options()).unwrap();
+        assert_eq!((map.cols, map.rows), (3, 2));
+        assert_eq!(map.step, [10.0, 9.0]);
+        assert_eq!(map.point(2, 1), [20.0, 9.0]);
+        assert_eq!(
+            map.probe_order(),
+            vec![(0, 0), (1, 0), (2, 0), (2, 1), (1, 1), (0, 1)]
+        );
+        assert_eq!(
+            options().point_lines(10.0, 0.0),
+            [
+                "G21 G90 G0 Z2.0000",
+                "G0 X10.0000 Y0.0000",
+                "G38.3 Z-2.0000 F50.0"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_height_at() {
+        let mut map = HeightMap::new(&options()).unwrap();
+        for (c, r) in map.probe_order() {
+            map.set(c, r, Some(c as f64 * 0.1 + r as f64));
+        }
+        assert!((map.height_at(5.0, 4.5).unwrap() - 0.55).abs() < 1e-9);
+        // Clamped outside the grid.
+        assert!((map.height_at(30.0, 20.0).unwrap() - 1.2).abs() < 1e-9);
+        assert!(map.contains(20.0, 9.0));
+        assert!(!map.contains(30.0, 20.0));
+        assert!(!map.contains(-0.1, 0.0));
+        assert_eq!(map.range(), Some((0.0, 1.2)));
+        map.set(1, 0, None);
+        assert_eq!(map.failed(), 1);
+        assert!(map.height_at(5.0, 4.5).is_none());
+        assert!(map.height_at(15.0, 4.5).is_none());
+    }
+
+    #[test]
+    fn test_save_load() {
+        let mut map = HeightMap::new(&options()).unwrap();
+        map.set(0, 0, Some(-0.125));
+        let path =
+            std::env::temp_dir().join(format!("grbl-rs-heightmap-{}.json", std::process::id()));
+        map.save(&path).unwrap();
+        let loaded = HeightMap::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(loaded, map);
+    }
+
+    #[test]
+    fn test_invalid_grid() {
+        for spacing in [0.0, -1.0, f64::NAN] {
+            let bad = HeightMapOptions {
+                spacing_mm: spacing,
+                ..options()
+            };
+            assert!(matches!(
+                HeightMap::new(&bad),
+                Err(HeightMapError::InvalidSpacing(_))
+            ));
+        }
+        let bad = HeightMapOptions {
+            max: [f64::INFINITY, 9.0],
+            ..options()
+        };
+        assert_eq!(HeightMap::new(&bad), Err(HeightMapError::InvalidBounds));
+        let bad = HeightMapOptions {
+            spacing_mm: 1e-6,
+            ..options()
+        };
+        assert!(matches!(
+            HeightMap::new(&bad),
+            Err(HeightMapError::TooManyPoints { .. })
+        ));
+    }
+
+    #[test]
+    fn test_load_rejects_bad_dimensions() {
+        let path =
+            std::env::temp_dir().join(format!("grbl-rs-heightmap-bad-{}.json", std::process::id()));
+        for json in [
+            r#"{"origin":[0,0],"step":[1,1],"cols":0,"rows":0,"z":[]}"#,
+            r#"{"origin":[0,0],"step":[1,1],"cols":2,"rows":2,"z":[0.0,null,1.0]}"#,
+        ] {
+            std::fs::write(&path, json).unwrap();
+            let err = HeightMap::load(&path).unwrap_err();
+            assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+        }
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -1,24 +1,48 @@
 //! Public API: single interface to the GRBL-HAL controller.
 //!
 //! `GrblMachine` owns the connection, runs the status poller, and exposes
-//! connect, disconnect, jog, home, run_file, get_status, and probe_z.
+//! connect, disconnect, jog, home, run_file (and run_file_from), validate_file,
+//! trace_outline, set_tool_change, set_auto_level, get_status, probe_z,
+//! zero_z_with_plate, probe (edge, corner, bore/boss center), probe_repeatability,
+//! probe_height_map, write_setting, session_header and start_status_recording.
 //! Everything else (port, poller, streamer, parser, motion) is internal.
 
 #![cfg(feature = "serial")]
 
+use super::autolevel::{AutoLevel, AutoLevelError};
 use super::commands::{GrblCommand, RealtimeCommand};
-use super::motion::{translate_lines, MotionConfig};
-use super::port::{Port, PortError, DEFAULT_BAUD};
+use super::heightmap::{HeightMap, HeightMapOptions};
+use super::motion::{translate_numbered, MotionConfig, TranslatedLine};
+use super::outline::{job_outline, outline_jogs, JobOutline, OutlineOptions, OutlineShape};
+use super::parser::{parse_active_wcs, parse_settings, GrblSettings};
 use super::poller::{run_poller, PollerHandle, STATUS_READ_TIMEOUT_MS};
-use super::state::MachineStatus;
-use super::streamer::{stream_lines, StreamResult, LINE_RESPONSE_TIMEOUT_MS};
+use super::port::{Port, PortError, DEFAULT_BAUD};
+use super::preprocess::{preprocess_lines, PreprocessOptions};
+use super::prober::{active_units, restore_line, run_height_map, run_probe_routine, touch};
+use super::probing::{ProbeDirection, ProbeResult, ProbeRoutine, ProbeSettings};
+use super::resume::{plan_start_from, StartFromOptions, StartFromPlan};
+use super::state::{JobEvent, MachineState, MachineStatus, Position};
+use super::streamer::{
+    check_lines, record, send_all, send_command, settled_status, stream_lines, stream_translated,
+    ErrorPolicy, LineResult, OperatorDecision, StreamHooks, StreamResult, ValidationReport,
+    LINE_RESPONSE_TIMEOUT_MS,
+};
+use super::toolchange::ToolChangeSession;
+use crate::machines::profiles::MachineProfile;
+use crate::machines::session::{
+    self, now_secs, probe_result, probe_stats, status_snapshot_from, RecordReason,
+    RepeatabilityReport, SessionEvent, SessionHeader, SessionRecorder, StatusRecordOptions,
+    StatusThrottle,
+};
+use sha2::{Digest, Sha256};
 use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
-use std::time::Duration;
-use tokio::sync::{broadcast, Mutex};
-use tokio::task::JoinHandle;
+use std::time::{Duration, Instant};
 use thiserror::Error;
-use tracing::info;
+use tokio::sync::{broadcast, mpsc, Mutex};
+use tokio::task::JoinHandle;
+use tracing::{info, warn};
 
 /// Errors from the public GrblMachine API.
 #[derive(Debug, Error)]
@@ -29,6 +53,20 @@ pub enum GrblError {
     Streamer(#[from] super::streamer::StreamerError),
     #[error("I/O: {0}")]
     Io(#[from] std::io::Error),
+    #[error("start from line: {0}")]
+    StartFrom(#[from] super::resume::StartFromError),
+    #[error("job has no cutting moves to outline")]
+    NothingToOutline,
+    #[error("check mode: {0}")]
+    CheckMode(String),
+    #[error("no parser state ([GC:...]) in reply to $G")]
+    NoParserState,
+    #[error("auto-level: {0}")]
+    AutoLevel(#[from] AutoLevelError),
+    #[error("parse: {0}")]
+    Parse(#[from] super::parser::ParseError),
+    #[error("height map: {0}")]
+    HeightMap(#[from] super::heightmap::HeightMapError),
 }
 
 /// Single public interface to a GRBL-HAL controller.
@@ -41,6 +79,17 @@ pub struct GrblMachine {
     _broadcast_tx: broadcast::Sender<MachineStatus>,
     poller_handle: JoinHandle<Result<(), super::poller::PollerError>>,
     motion_config: Arc<Mutex<MotionConfig>>,
+    outline_cancel: Arc<AtomicBool>,
+    preprocess: Arc<Mutex<PreprocessOptions>>,
+    error_policy: Arc<Mutex<ErrorPolicy>>,
+    decision_tx: mpsc::Sender<OperatorDecision>,
+    decision_rx: Arc<Mutex<mpsc::Receiver<OperatorDecision>>>,
+    job_events: broadcast::Sender<JobEvent>,
+    tool_change: Arc<Mutex<Option<ToolChangeSession>>>,
+    recorder: Arc<Mutex<Option<SessionRecorder>>>,
+    status_recording: Arc<Mutex<Option<JoinHandle<()>>>>,
+    port_name: String,
+    auto_level: Arc<Mutex<Option<AutoLevel>>>,
 }
 
 impl GrblMachine {
@@ -51,11 +100,14 @@ impl GrblMachine {
         let port = Arc::new(Mutex::new(port));
         let state = Arc::new(Mutex::new(MachineStatus::idle()));
         let (tx, _rx) = broadcast::channel(16);
+        let motion_config = Arc::new(Mutex::new(MotionConfig::default()));
+        let (decision_tx, decision_rx) = mpsc::channel(1);
 
         let handle = PollerHandle {
             port: Arc::clone(&port),
             state: Arc::clone(&state),
             tx: tx.clone(),
+            motion_config: Arc::clone(&motion_config),
         };
         let poller_handle = tokio::spawn(async move {
             run_poller(
@@ -72,12 +124,37 @@ impl GrblMachine {
             state,
             _broadcast_tx: tx,
             poller_handle,
-            motion_config: Arc::new(Mutex::new(MotionConfig::default())),
+            motion_config,
+            outline_cancel: Arc::new(AtomicBool::new(false)),
+            preprocess: Arc::new(Mutex::new(PreprocessOptions::default())),
+            error_policy: Arc::new(Mutex::new(ErrorPolicy::default())),
+            decision_tx,
+            decision_rx: Arc::new(Mutex::new(decision_rx)),
+            job_events: broadcast::channel(16).0,
+            tool_change: Arc::new(Mutex::new(None)),
+            recorder: Arc::new(Mutex::new(None)),
+            status_recording: Arc::new(Mutex::new(None)),
+            port_name: port_name.to_string(),
+            auto_level: Arc::new(Mutex::new(None)),
         })
     }
 
-    /// Disconnect: stop the poller and close the port.
+    /// Disconnect: stop the poller and close the port. An attached session
+    /// recorder gets a `disconnected` event and is finished.
     pub async fn disconnect(self) {
+        self.stop_status_recording().await;
+        self.record(SessionEvent::Disconnected {
+            port: self.port_name.clone(),
+            ts_secs: now_secs(),
+        })
+        .await;
+        if let Some(rec) = self.recorder.lock().await.take() {
+            match tokio::task::spawn_blocking(move || rec.finish()).await {
+                Ok(Err(e)) => warn!("session recorder: {}", e),
+                Err(e) => warn!("session recorder: {}", e),
+                Ok(Ok(())) => {}
+            }
+        }
         self.poller_handle.abort();
         // Cannot move poller_handle out (GrblMachine implements Drop). Abort is enough; Drop will run on exit.
         info!("GrblMachine disconnected");
@@ -110,21 +187,246 @@ impl GrblMachine {
         Ok(())
     }
 
-    /// Run a g-code file: translate Y moves (bed extension) then stream with flow control.
-    /// Pauses on Hold, resumes on Idle. Returns stream result (lines sent, first error if any).
+    /// Level Z from the height map (if set), translate Y moves (bed extension),
+    /// then run the preprocessing pipeline. Lines dropped by preprocessing are logged.
+    async fn prepare_lines(
+        &self,
+        lines: &[impl AsRef<str>],
+    ) -> Result<Vec<TranslatedLine>, GrblError> {
+        let config = self.motion_config.lock().await.clone();
+        let options = self.preprocess.lock().await.clone();
+        let numbered = lines.iter().enumerate().map(|(i, line)| TranslatedLine {
+            source_line: i + 1,
+            text: line.as_ref().to_string(),
+        });
+        let leveled = match self.auto_level.lock().await.as_ref() {
+            Some(level) => level.apply(numbered)?,
+            None => numbered.collect(),
+        };
+        let (prepared, warnings) = preprocess_lines(translate_numbered(leveled, &config), &options);
+        for w in warnings {
+            warn!("preprocess: line {}: {}", w.line, w.message);
+        }
+        Ok(prepared)
+    }
+
+    /// Run a g-code file: translate Y moves (bed extension), preprocess, then stream
+    /// with flow control. Pauses on Hold, resumes on Idle. Returns stream result
+    /// (lines sent, first error if any). Refuses to run while in check mode.
     pub async fn run_file(&self, path: &Path) -> Result<StreamResult, GrblError> {
         let content = tokio::fs::read_to_string(path).await?;
         let lines: Vec<&str> = content.lines().collect();
-        let config = self.motion_config.lock().await.clone();
-        let translated = translate_lines(&lines, &config);
+        let prepared = self.prepare_lines(&lines).await?;
+        self.stream_job(path, &content, 1, prepared).await
+    }
+
+    /// Stream a job's prepared lines, recording its start and end to the session
+    /// recorder, if one is attached.
+    async fn stream_job(
+        &self,
+        path: &Path,
+        content: &str,
+        start_line: usize,
+        lines: Vec<TranslatedLine>,
+    ) -> Result<StreamResult, GrblError> {
+        if matches!(self.state.lock().await.state, MachineState::Check) {
+            return Err(GrblError::CheckMode(
+                "controller is in check mode; leave it ($C) before running a job".to_string(),
+            ));
+        }
+        let file = path.display().to_string();
+        self.record(SessionEvent::JobStarted {
+            file: file.clone(),
+            file_hash: format!("{:x}", Sha256::digest(content.as_bytes())),
+            lines: content.lines().count(),
+            start_line,
+            ts_secs: now_secs(),
+        })
+        .await;
+        let result = self.stream_prepared(lines).await;
+        let summary = result.as_ref().cloned().unwrap_or_default();
+        self.record(SessionEvent::JobEnded {
+            file,
+            lines_sent: summary.lines_sent,
+            lines_ok: summary.lines_ok,
+            first_error: summary.first_error,
+            error_lines: summary.errors.iter().map(|e| e.line).collect(),
+            failure: result.as_ref().err().map(|e| e.to_string()),
+            ts_secs: now_secs(),
+        })
+        .await;
+        result
+    }
+
+    /// Stream prepared lines under the current [`ErrorPolicy`], publishing program
+    /// pauses and tool changes on the job event channel.
+    async fn stream_prepared(&self, lines: Vec<TranslatedLine>) -> Result<StreamResult, GrblError> {
+        let policy = *self.error_policy.lock().await;
+        let mut decisions = self.decision_rx.lock().await;
+        let mut tool_change = self.tool_change.lock().await;
+        // Drop answers given while no stream was waiting.
+        while decisions.try_recv().is_ok() {}
         let port = Arc::clone(&self.port);
         let state = Arc::clone(&self.state);
         let timeout = Duration::from_millis(LINE_RESPONSE_TIMEOUT_MS);
-        let result =
-            stream_lines(port, state, translated.into_iter(), timeout).await?;
+        let result = stream_translated(
+            port,
+            state,
+            lines.into_iter(),
+            timeout,
+            policy,
+            Some(StreamHooks {
+                decisions: &mut decisions,
+                events: self.job_events.clone(),
+                tool_change: tool_change.as_mut(),
+                recorder: Some(Arc::clone(&self.recorder)),
+            }),
+        )
+        .await?;
         Ok(result)
     }
 
+    /// Answer a job waiting for the operator (a [`JobEvent`] pause or tool change,
+    /// or an [`ErrorPolicy::Pause`] error): continue or abort.
+    pub async fn resolve_operator_request(
+        &self,
+        decision: OperatorDecision,
+    ) -> Result<(), GrblError> {
+        self.decision_tx
+            .send(decision)
+            .await
+            .map_err(|e| GrblError::Io(std::io::Error::other(e)))
+    }
+
+    /// Dry-run a job in check mode (`$C`): the controller parses every line without
+    /// moving, and every `error:N` is collected with its source line. Check mode is
+    /// left and the controller soft-reset afterwards, even if the run fails.
+    pub async fn validate_file(&self, path: &Path) -> Result<ValidationReport, GrblError> {
+        let content = tokio::fs::read_to_string(path).await?;
+        let lines: Vec<&str> = content.lines().collect();
+        let prepared = self.prepare_lines(&lines).await?;
+        let timeout = Duration::from_millis(LINE_RESPONSE_TIMEOUT_MS);
+
+        self.enter_check_mode(timeout).await?;
+        // From here on `$C` has been accepted, so check mode is left on every path.
+        let report = match self.wait_for_check_state().await {
+            Ok(()) => check_lines(Arc::clone(&self.port), prepared, timeout)
+                .await
+                .map_err(GrblError::from),
+            Err(e) => Err(e),
+        };
+        let check_mode = GrblCommand::CheckMode.to_string();
+        let left = send_command(Arc::clone(&self.port), &check_mode, timeout).await;
+        self.send_realtime(RealtimeCommand::SoftReset).await?;
+        left?;
+        report
+    }
+
+    /// Send `$C` unless already in check mode. Fails only if `$C` was not accepted.
+    async fn enter_check_mode(&self, timeout: Duration) -> Result<(), GrblError> {
+        if matches!(self.state.lock().await.state, MachineState::Check) {
+            return Ok(());
+        }
+        let line = GrblCommand::CheckMode.to_string();
+        let result = send_command(Arc::clone(&self.port), &line, timeout).await?;
+        if let LineResult::Error(code) = result {
+            return Err(GrblError::CheckMode(format!(
+                "$C rejected with error {}",
+                code
+            )));
+        }
+        Ok(())
+    }
+
+    /// Wait up to 2 s for the poller to report `MachineState::Check`.
+    async fn wait_for_check_state(&self) -> Result<(), GrblError> {
+        for _ in 0..20 {
+            if matches!(self.state.lock().await.state, MachineState::Check) {
+                return Ok(());
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+        Err(GrblError::CheckMode(
+            "controller did not report Check state".to_string(),
+        ))
+    }
+
+    /// Plan a run starting at `line` (1-based) without sending anything, so the
+    /// operator can review the generated preamble.
+    pub async fn plan_run_from(
+        &self,
+        path: &Path,
+        line: usize,
+        options: &StartFromOptions,
+    ) -> Result<StartFromPlan, GrblError> {
+        let content = tokio::fs::read_to_string(path).await?;
+        let lines: Vec<&str> = content.lines().collect();
+        let config = self.motion_config.lock().await.clone();
+        Ok(plan_start_from(&lines, line, options, &config)?)
+    }
+
+    /// Run a g-code file from `line` (1-based): send the modal-state preamble (see
+    /// [`GrblMachine::plan_run_from`]), then the rest of the file. Translated and
+    /// streamed like [`GrblMachine::run_file`].
+    pub async fn run_file_from(
+        &self,
+        path: &Path,
+        line: usize,
+        options: &StartFromOptions,
+    ) -> Result<StreamResult, GrblError> {
+        let content = tokio::fs::read_to_string(path).await?;
+        let lines: Vec<&str> = content.lines().collect();
+        let config = self.motion_config.lock().await.clone();
+        let plan = plan_start_from(&lines, line, options, &config)?;
+        let mut prepared = self.prepare_lines(&plan.program(&lines)).await?;
+        // Number lines as in the file; the preamble counts as the start line.
+        let preamble = plan.preamble.len();
+        for l in &mut prepared {
+            l.source_line = line + l.source_line.saturating_sub(preamble + 1);
+        }
+        self.stream_job(path, &content, line, prepared).await
+    }
+
+    /// Trace the outline of a job with `$J=` jogs at a safe Z, spindle off (`M5`
+    /// is sent first), so the operator can check stock placement. Returns the
+    /// outline that was traced. Stop it with [`GrblMachine::cancel_outline`].
+    pub async fn trace_outline(
+        &self,
+        path: &Path,
+        shape: OutlineShape,
+        options: &OutlineOptions,
+    ) -> Result<JobOutline, GrblError> {
+        let content = tokio::fs::read_to_string(path).await?;
+        let lines: Vec<&str> = content.lines().collect();
+        let config = self.motion_config.lock().await.clone();
+        let outline = job_outline(&lines, shape, &config).ok_or(GrblError::NothingToOutline)?;
+        let mut commands = vec!["M5".to_string()];
+        commands.extend(
+            outline_jogs(&outline, options, &config)
+                .iter()
+                .map(|c| c.to_string()),
+        );
+
+        self.outline_cancel.store(false, Ordering::SeqCst);
+        let cancel = Arc::clone(&self.outline_cancel);
+        let port = Arc::clone(&self.port);
+        let state = Arc::clone(&self.state);
+        let timeout = Duration::from_millis(LINE_RESPONSE_TIMEOUT_MS);
+        // Checked before each jog so nothing is queued after a cancel.
+        let lines = commands
+            .into_iter()
+            .take_while(move |_| !cancel.load(Ordering::SeqCst));
+        stream_lines(port, state, lines, timeout).await?;
+        Ok(outline)
+    }
+
+    /// Stop an outline trace: no further jogs are sent and `JogCancel` flushes
+    /// the ones already queued.
+    pub async fn cancel_outline(&self) -> Result<(), GrblError> {
+        self.outline_cancel.store(true, Ordering::SeqCst);
+        self.send_realtime(RealtimeCommand::JogCancel).await
+    }
+
     /// Current machine status (from the poller). Clone of the shared state.
     pub async fn get_status(&self) -> MachineStatus {
         self.state.lock().await.clone()
@@ -136,6 +438,13 @@ impl GrblMachine {
         self._broadcast_tx.subscribe()
     }
 
+    /// Subscribe to job events (program pauses, tool changes, program end) raised
+    /// while `run_file` streams. Answer pauses with
+    /// [`GrblMachine::resolve_operator_request`].
+    pub fn subscribe_job_events(&self) -> broadcast::Receiver<JobEvent> {
+        self.job_events.subscribe()
+    }
+
     /// Probe Z: send G38.2 Z toward negative (e.g. `G38.2 Z-10 F50`). Fails if probe not triggered.
     pub async fn probe_z(&self, distance_mm: f64, feed_mm_min: f64) -> Result<(), GrblError> {
         let line = format!("G38.2 Z-{:.4} F{:.4}", distance_mm, feed_mm_min);
@@ -152,6 +461,282 @@ impl GrblMachine {
         Ok(())
     }
 
+    /// Run a probing routine (edge, corner, bore or boss center) with fast and slow
+    /// touches. With `settings.set_wcs` the result becomes the active WCS zero.
+    /// Start the probe where the routine's documentation says.
+    pub async fn probe(
+        &self,
+        routine: &ProbeRoutine,
+        settings: &ProbeSettings,
+    ) -> Result<ProbeResult, GrblError> {
+        let timeout = Duration::from_millis(LINE_RESPONSE_TIMEOUT_MS);
+        Ok(run_probe_routine(&self.port, &self.state, routine, settings, timeout).await?)
+    }
+
+    /// Active work coordinate system as its `G10` P number (G54 = 1), read with `$G`.
+    pub async fn active_wcs(&self) -> Result<u8, GrblError> {
+        let timeout = Duration::from_millis(LINE_RESPONSE_TIMEOUT_MS);
+        let feedback =
+            send_all(&self.port, &[GrblCommand::ParserState.to_string()], timeout).await?;
+        feedback
+            .iter()
+            .find_map(|l| parse_active_wcs(l))
+            .ok_or(GrblError::NoParserState)
+    }
+
+    /// Zero Z on a touch plate: seek down at `seek_feed`, back off `retract`,
+    /// latch at `latch_feed`, then set the active WCS so the plate top reads
+    /// `plate_thickness` and back off `retract` again. The contact (and a failed
+    /// touch) is recorded to the session recorder, if one is attached. Arguments
+    /// are in mm whatever the active units, which are restored afterwards.
+    /// Returns the stock top in machine coordinates.
+    pub async fn zero_z_with_plate(
+        &self,
+        plate_thickness: f64,
+        seek_feed: f64,
+        latch_feed: f64,
+        retract: f64,
+    ) -> Result<ProbeResult, GrblError> {
+        let settings = ProbeSettings {
+            seek_feed_mm_min: seek_feed,
+            latch_feed_mm_min: latch_feed,
+            retract_mm: retract,
+            plate_thickness_mm: plate_thickness,
+            set_wcs: false,
+            ..ProbeSettings::default()
+        };
+        let timeout = Duration::from_millis(LINE_RESPONSE_TIMEOUT_MS);
+        let wcs = self.active_wcs().await?;
+        let units = active_units(&self.port, timeout).await?;
+        let touched = touch(
+            &self.port,
+            &self.state,
+            ProbeDirection::ZMinus,
+            false,
+            &settings,
+            timeout,
+        )
+        .await;
+        let contact = match touched {
+            Ok(contact) => contact,
+            Err(e) => {
+                let _ = send_all(&self.port, &[restore_line(units)], timeout).await;
+                let status = self.get_status().await;
+                self.record_probe(probe_result(false, status.work_pos, status.machine_pos))
+                    .await;
+                return Err(e.into());
+            }
+        };
+        let status = settled_status(&self.state, Instant::now()).await;
+        // Backed off `retract` from the contact; use where it actually stopped.
+        let above = status.machine_pos.z - contact[2];
+        let line = format!("G21 G10 L20 P{} Z{:.4}", wcs, plate_thickness + above);
+        let set = send_all(&self.port, &[line], timeout).await;
+        let restore = send_all(&self.port, &[restore_line(units)], timeout).await;
+        set?;
+        restore?;
+        let (work_pos, machine_pos) = contact_positions(contact, &status);
+        self.record_probe(probe_result(true, work_pos, machine_pos))
+            .await;
+        info!(
+            "zero_z_with_plate: P{} contact Z{:.4} (machine)",
+            wcs, contact[2]
+        );
+        Ok(ProbeResult {
+            position: [None, None, Some(contact[2] - plate_thickness)],
+            diameter_mm: None,
+            contacts: vec![contact],
+        })
+    }
+
+    /// Probe the same point `count` times in `direction` to measure probe
+    /// repeatability: fast and slow touch, back to the start between touches.
+    /// Every touch is recorded to the session recorder, if one is attached; a
+    /// failed touch ends the test.
+    pub async fn probe_repeatability(
+        &self,
+        direction: ProbeDirection,
+        count: usize,
+        settings: &ProbeSettings,
+    ) -> Result<RepeatabilityReport, GrblError> {
+        let timeout = Duration::from_millis(LINE_RESPONSE_TIMEOUT_MS);
+        let units = active_units(&self.port, timeout).await?;
+        let mut results = Vec::new();
+        let mut outcome = Ok(());
+        for _ in 0..count {
+            match touch(&self.port, &self.state, direction, true, settings, timeout).await {
+                Ok(contact) => {
+                    let status = settled_status(&self.state, Instant::now()).await;
+                    let (work_pos, machine_pos) = contact_positions(contact, &status);
+                    let result = probe_result(true, work_pos, machine_pos);
+                    self.record_probe(result.clone()).await;
+                    results.push(result);
+                }
+                Err(e) => {
+                    let status = self.get_status().await;
+                    self.record_probe(probe_result(false, status.work_pos, status.machine_pos))
+                        .await;
+                    outcome = Err(e);
+                    break;
+                }
+            }
+        }
+        let restore = send_all(&self.port, &[restore_line(units)], timeout).await;
+        outcome?;
+        restore?;
+        let stats = probe_stats(&results);
+        if let Some(s) = &stats {
+            let i = direction.axis();
+            info!(
+                "probe repeatability: {} touches, std dev {:.4}, spread {:.4}",
+                s.count, s.std_dev[i], s.spread[i]
+            );
+        }
+        Ok(RepeatabilityReport { results, stats })
+    }
+
+    /// Probe a height map over the grid in `options` (work coordinates). Points
+    /// without contact stay `None`. Each point is recorded to the session
+    /// recorder as a probe event, if one is attached.
+    pub async fn probe_height_map(
+        &self,
+        options: &HeightMapOptions,
+    ) -> Result<HeightMap, GrblError> {
+        let map = HeightMap::new(options)?;
+        let timeout = Duration::from_millis(LINE_RESPONSE_TIMEOUT_MS);
+        let status = self.get_status().await;
+        let (m, w) = (status.machine_pos, status.work_pos);
+        let mut events = Vec::new();
+        let map = run_height_map(
+            &self.port,
+            &self.state,
+            options,
+            map,
+            timeout,
+            |_, _, report| {
+                let p = &report.position;
+                let work_pos = Position {
+                    x: p.x + w.x - m.x,
+                    y: p.y + w.y - m.y,
+                    z: p.z + w.z - m.z,
+                    a: w.a,
+                };
+                events.push(probe_result(report.success, work_pos, p.clone()));
+            },
+        )
+        .await;
+        for event in events {
+            self.record_probe(event).await;
+        }
+        Ok(map?)
+    }
+
+    /// Attach a session recorder (or detach with `None`); it starts with a
+    /// `connected` event. Returns the previous recorder so the caller can `finish` it.
+    pub async fn set_session_recorder(
+        &self,
+        recorder: Option<SessionRecorder>,
+    ) -> Option<SessionRecorder> {
+        let previous = std::mem::replace(&mut *self.recorder.lock().await, recorder);
+        self.record(SessionEvent::Connected {
+            port: self.port_name.clone(),
+            ts_secs: now_secs(),
+        })
+        .await;
+        previous
+    }
+
+    async fn record_probe(&self, result: session::ProbeResult) {
+        self.record(result.into()).await;
+    }
+
+    async fn record(&self, event: SessionEvent) {
+        record(Some(&self.recorder), event).await;
+    }
+
+    /// Record status reports to the attached session recorder in the background:
+    /// one per `options.interval`, plus every state change, alarm and position
+    /// jump. Replaces a recording already running; stop it with
+    /// [`GrblMachine::stop_status_recording`]. Writes nothing while no recorder
+    /// is attached.
+    pub async fn start_status_recording(&self, options: StatusRecordOptions) {
+        let rx = self.subscribe_status();
+        let recorder = Arc::clone(&self.recorder);
+        let task = tokio::spawn(run_status_recording(rx, recorder, options));
+        if let Some(previous) = self.status_recording.lock().await.replace(task) {
+            previous.abort();
+        }
+    }
+
+    /// Stop the background status recording, if running.
+    pub async fn stop_status_recording(&self) {
+        if let Some(task) = self.status_recording.lock().await.take() {
+            task.abort();
+        }
+    }
+
+    /// Write controller setting `$number=value`. Accepted changes are recorded
+    /// to the session recorder, if one is attached.
+    pub async fn write_setting(&self, number: u16, value: &str) -> Result<(), GrblError> {
+        let value = value.trim().to_string();
+        let line = GrblCommand::SetSetting {
+            number,
+            value: value.clone(),
+        }
+        .to_string();
+        let timeout = Duration::from_millis(LINE_RESPONSE_TIMEOUT_MS);
+        send_all(&self.port, &[line], timeout).await?;
+        info!("setting ${} = {}", number, value);
+        self.record(SessionEvent::SettingChanged {
+            number,
+            value,
+            ts_secs: now_secs(),
+        })
+        .await;
+        Ok(())
+    }
+
+    /// Build info lines from `$I` (`[VER:...]`, `[OPT:...]`, ...).
+    pub async fn build_info(&self) -> Result<Vec<String>, GrblError> {
+        let timeout = Duration::from_millis(LINE_RESPONSE_TIMEOUT_MS);
+        let feedback = send_all(&self.port, &[GrblCommand::BuildInfo.to_string()], timeout).await?;
+        Ok(feedback
+            .into_iter()
+            .filter(|l| l.starts_with('['))
+            .collect())
+    }
+
+    /// Controller settings, read with `$$`.
+    pub async fn read_settings(&self) -> Result<GrblSettings, GrblError> {
+        let timeout = Duration::from_millis(LINE_RESPONSE_TIMEOUT_MS);
+        let feedback = send_all(
+            &self.port,
+            &[GrblCommand::SettingsRequest.to_string()],
+            timeout,
+        )
+        .await?;
+        Ok(parse_settings(&feedback.join("\n"))?)
+    }
+
+    /// Header for a new session on this machine: `$I` build info and the hash
+    /// of the `$$` settings, plus the operator's job name and the profile name.
+    /// Pass it to `SessionRecorder::start_session_with`.
+    pub async fn session_header(
+        &self,
+        job_name: Option<&str>,
+        profile: Option<&MachineProfile>,
+    ) -> Result<SessionHeader, GrblError> {
+        let mut header = SessionHeader::new().with_settings(&self.read_settings().await?);
+        header.controller_info = self.build_info().await?;
+        if let Some(name) = job_name {
+            header = header.with_job_name(name);
+        }
+        if let Some(profile) = profile {
+            header = header.with_profile(profile);
+        }
+        Ok(header)
+    }
+
     /// Unlock after alarm (send `$X`).
     pub async fn unlock(&self) -> Result<(), GrblError> {
         let line = GrblCommand::Unlock.to_string();
@@ -167,6 +752,11 @@ impl GrblMachine {
 
     /// Send a real-time command (single byte, no newline): e.g. jog cancel, feed override.
     pub async fn send_realtime(&self, cmd: RealtimeCommand) -> Result<(), GrblError> {
+        self.record(SessionEvent::Realtime {
+            command: format!("{:?}", cmd),
+            ts_secs: now_secs(),
+        })
+        .await;
         let byte = cmd.as_byte();
         let port = Arc::clone(&self.port);
         tokio::task::spawn_blocking(move || {
@@ -178,12 +768,93 @@ impl GrblMachine {
         Ok(())
     }
 
-    /// Set the motion config (gantry Y limit and bed axis) used by `run_file`.
+    /// Set how `run_file` and `run_file_from` handle lines the controller rejects.
+    pub async fn set_error_policy(&self, policy: ErrorPolicy) {
+        *self.error_policy.lock().await = policy;
+    }
+
+    /// Enable the guided tool change on `M6` (park, wait for the operator, probe the
+    /// new tool on the setter, compensate), or `None` to only wait for the operator.
+    /// The session keeps the reference height across jobs; build it with
+    /// [`ToolChangeSession::for_profile`] to seed it from the profile's tool table.
+    pub async fn set_tool_change(&self, session: Option<ToolChangeSession>) {
+        *self.tool_change.lock().await = session;
+    }
+
+    /// Current tool change session (reference height and measured offsets).
+    pub async fn tool_change(&self) -> Option<ToolChangeSession> {
+        self.tool_change.lock().await.clone()
+    }
+
+    /// Level Z on a non-flat bed from a probed height map (see
+    /// [`GrblMachine::probe_height_map`]) in `run_file`, `run_file_from` and
+    /// `validate_file`, or `None` to stop leveling. Z zero must be set in the WCS the
+    /// map was probed in.
+    pub async fn set_auto_level(&self, level: Option<AutoLevel>) {
+        *self.auto_level.lock().await = level;
+    }
+
+    /// Set the preprocessing stages applied by `run_file` and `run_file_from`.
+    pub async fn set_preprocess_options(&self, options: PreprocessOptions) {
+        *self.preprocess.lock().await = options;
+    }
+
+    /// Set the motion config (gantry Y limit and bed axis) used by `run_file` and
+    /// by the poller to report `MachineStatus::part_pos`.
     pub async fn set_motion_config(&self, config: MotionConfig) {
         *self.motion_config.lock().await = config;
     }
 }
 
+/// Write the status reports `throttle` selects until the channel closes. Lagged
+/// reports are skipped; the next one is checked as usual.
+async fn run_status_recording(
+    mut rx: broadcast::Receiver<MachineStatus>,
+    recorder: Arc<Mutex<Option<SessionRecorder>>>,
+    options: StatusRecordOptions,
+) {
+    let mut throttle = StatusThrottle::new(options);
+    loop {
+        let status = match rx.recv().await {
+            Ok(status) => status,
+            Err(broadcast::error::RecvError::Lagged(_)) => continue,
+            Err(broadcast::error::RecvError::Closed) => break,
+        };
+        let Some(reason) = throttle.check(&status, Instant::now()) else {
+            continue;
+        };
+        if let (RecordReason::Alarm, MachineState::Alarm(code)) = (reason, &status.state) {
+            let event = SessionEvent::Alarm {
+                code: code.number().to_string(),
+                line: None,
+                ts_secs: now_secs(),
+            };
+            record(Some(&recorder), event).await;
+        }
+        let event = status_snapshot_from(&status).into();
+        record(Some(&recorder), event).await;
+    }
+}
+
+/// Work and machine position of a probe contact (machine coordinates), using
+/// the work offset in `status`.
+fn contact_positions(contact: [f64; 3], status: &MachineStatus) -> (Position, Position) {
+    let (m, w) = (&status.machine_pos, &status.work_pos);
+    let work_pos = Position {
+        x: contact[0] + w.x - m.x,
+        y: contact[1] + w.y - m.y,
+        z: contact[2] + w.z - m.z,
+        a: w.a,
+    };
+    let machine_pos = Position {
+        x: contact[0],
+        y: contact[1],
+        z: contact[2],
+        a: m.a,
+    };
+    (work_pos, machine_pos)
+}
+
 impl Drop for GrblMachine {
     fn drop(&mut self) {
         self.poller_handle.abort();
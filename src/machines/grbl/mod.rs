@@ -5,28 +5,54 @@
 //!
 //! Types used by the API (state, commands, motion config) are re-exported.
 
+mod analyze;
+mod autolevel;
 mod commands;
+mod estimate;
+mod gcode;
+mod heightmap;
 mod motion;
+mod outline;
 mod parser;
+mod preprocess;
+mod probing;
+mod resume;
 mod state;
+mod toolchange;
+mod toolpath;
 
 #[cfg(feature = "serial")]
 mod machine;
 #[cfg(feature = "serial")]
+mod poller;
+#[cfg(feature = "serial")]
 mod port;
 #[cfg(feature = "serial")]
-mod poller;
+mod prober;
 #[cfg(feature = "serial")]
 mod streamer;
 
+pub use analyze::*;
+pub use autolevel::*;
 pub use commands::*;
+pub use estimate::*;
+pub use gcode::*;
+pub use heightmap::*;
 pub use motion::*;
+pub use outline::*;
 pub use parser::*;
+pub use preprocess::*;
+pub use probing::*;
+pub use resume::*;
 pub use state::*;
+pub use toolchange::*;
+pub use toolpath::*;
 
 #[cfg(feature = "serial")]
 pub use machine::*;
 #[cfg(feature = "serial")]
 pub use port::PortInfo;
 #[cfg(feature = "serial")]
-pub use streamer::StreamResult;
+pub use streamer::{
+    ErrorPolicy, LineError, OperatorDecision, StreamHooks, StreamResult, ValidationReport,
+};
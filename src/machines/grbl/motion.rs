@@ -4,18 +4,48 @@
 //! first segment moves gantry Y to the limit, then the bed axis (MOTOR4, e.g. A)
 //! carries the overflow. Transparent to the caller — they get a list of commands
 //! to send; no other module needs to know about the bed extension.
+//!
+//! Lines are interpreted with the modal [`Interpreter`](super::gcode::Interpreter),
+//! so the limit is checked against the resolved work-coordinate Y in mm regardless
+//! of units, distance mode, G92 offsets or G53 machine moves.
+
+use super::gcode::{
+    DistanceMode, Executed, Interpreter, Motion, MotionKind, MotionMode, Point,
+    DEFAULT_ARC_TOLERANCE_MM,
+};
+use super::state::{MachineStatus, Position};
+use serde::{Deserialize, Serialize};
 
 /// Default gantry Y limit in mm (24 inches). Moves beyond this are split;
 /// overflow is sent as bed-axis (A) moves.
 pub const DEFAULT_GANTRY_Y_LIMIT_MM: f64 = 609.6;
 
-/// Configuration for the bed extension translator.
-#[derive(Clone, Debug)]
+/// How a move that crosses the gantry limit is split between gantry and bed.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SplitMode {
+    /// Gantry move (carrying X/Z) to the limit, then a pure bed-axis move.
+    /// Simple, but XY cuts that cross the limit become dog-legged.
+    #[default]
+    Sequential,
+    /// Split at the exact crossing point along the XYZ vector; the second piece
+    /// carries the remaining X/Z together with the bed axis, preserving the
+    /// programmed toolpath.
+    Coordinated,
+}
+
+/// Configuration for the bed extension translator. Missing fields deserialize
+/// to their defaults.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
 pub struct MotionConfig {
     /// Gantry Y travel limit in mm. Y moves beyond this are split.
     pub gantry_y_limit_mm: f64,
     /// G-code axis letter for the bed rail (MOTOR4). Typically 'A'.
     pub bed_axis: char,
+    /// Chord tolerance in mm for arcs that have to be linearized to be split.
+    pub arc_tolerance_mm: f64,
+    /// How moves crossing the limit are split.
+    pub split_mode: SplitMode,
 }
 
 impl Default for MotionConfig {
@@ -23,165 +53,421 @@ impl Default for MotionConfig {
         Self {
             gantry_y_limit_mm: DEFAULT_GANTRY_Y_LIMIT_MM,
             bed_axis: 'A',
+            arc_tolerance_mm: DEFAULT_ARC_TOLERANCE_MM,
+            split_mode: SplitMode::default(),
         }
     }
 }
 
-/// Extracts a numeric value after a given axis letter (e.g. 'Y' -> 10.5 from "Y10.5").
-fn parse_axis_value(line: &str, axis: char) -> Option<f64> {
-    let upper = axis.to_uppercase().next().unwrap_or(axis);
-    let lower = axis.to_lowercase().next().unwrap_or(axis);
-    for (i, c) in line.chars().enumerate() {
-        if c == upper || c == lower {
-            let rest = line.get(i + 1..)?;
-            let end = rest
-                .find(|c: char| !c.is_ascii_digit() && c != '.' && c != '-')
-                .map(|i| i)
-                .unwrap_or(rest.len());
-            let num_str = rest.get(..end)?.trim();
-            return num_str.parse().ok();
-        }
-    }
-    None
+/// A gantry/bed target: gantry position (Y is gantry travel, not part space)
+/// and bed-axis extension, both in mm.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Piece {
+    gantry: Point,
+    bed: f64,
 }
 
-/// Returns true if the line is a rapid (G0) or linear (G1) move.
-fn is_move_line(line: &str) -> bool {
-    let trimmed = line.trim();
-    if trimmed.starts_with(';') {
-        return false;
-    }
-    // Match G0 or G1 as whole word (at start or after space)
-    let mut i = 0;
-    while i < trimmed.len() {
-        if trimmed.get(i..i + 2) == Some("G0") || trimmed.get(i..i + 2) == Some("G1") {
-            let after = trimmed.get(i + 2..).unwrap_or("");
-            if after.is_empty() || after.starts_with(' ') || after.starts_with('\t')
-                || after.starts_with('X') || after.starts_with('x')
-                || after.starts_with('Y') || after.starts_with('y')
-                || after.starts_with('Z') || after.starts_with('z')
-                || after.starts_with('F') || after.starts_with('f')
-                || after.starts_with('A') || after.starts_with('a')
-            {
-                return true;
-            }
-        }
-        i += 1;
-    }
-    false
-}
+const EPS_MM: f64 = 1e-6;
 
-/// Replaces the Y value in a g-code line with a new value (keeps format roughly).
-fn replace_y_in_line(line: &str, new_y: f64) -> String {
-    let mut result = String::with_capacity(line.len() + 16);
-    let mut i = 0;
-    let bytes = line.as_bytes();
-    while i < bytes.len() {
-        if (bytes[i] == b'Y' || bytes[i] == b'y') && i + 1 < bytes.len() {
-            let next = bytes[i + 1] as char;
-            if next == '-' || next == '.' || next.is_ascii_digit() {
-                result.push(bytes[i] as char);
-                i += 1;
-                while i < bytes.len()
-                    && (bytes[i] == b'-' || bytes[i] == b'.' || (bytes[i] as char).is_ascii_digit())
-                {
-                    i += 1;
-                }
-                result.push_str(&format!("{:.4}", new_y));
-                continue;
-            }
-        }
-        result.push(bytes[i] as char);
-        i += 1;
+/// Rounds to the 4 decimals used in generated lines; avoids emitting `-0.0000`.
+fn round4(v: f64) -> f64 {
+    let r = (v * 10_000.0).round() / 10_000.0;
+    if r == 0.0 {
+        0.0
+    } else {
+        r
     }
-    result
 }
 
-/// Builds a bed-axis move line (e.g. "G1 A10.5 F300").
-fn bed_axis_line(config: &MotionConfig, distance_mm: f64, feed: Option<f64>) -> String {
-    let ax = config.bed_axis.to_uppercase().next().unwrap_or(config.bed_axis);
-    let mut s = format!("G1 {}{:.4}", ax, distance_mm);
-    if let Some(f) = feed {
-        s.push_str(&format!(" F{:.4}", f));
-    }
-    s
+/// Streaming translator: a modal interpreter plus the bed extension it has commanded.
+struct BedTranslator<'a> {
+    config: &'a MotionConfig,
+    interp: Interpreter,
+    /// Current bed-axis extension in mm (0 = retracted).
+    bed_mm: f64,
+    /// Motion mode the controller is in after the lines emitted so far.
+    emitted_motion: MotionMode,
+    /// Bed extension when the G92 Y offset was set. The controller applies G92
+    /// to gantry Y, so its offset is the interpreter's (part-space) one minus this.
+    g92_bed_mm: f64,
+    /// `g92_bed_mm` saved by `G92.2`, restored by `G92.3`.
+    g92_bed_suspended: Option<f64>,
 }
 
-/// Translation state (modal and current position).
-struct TranslateState {
-    absolute: bool,
-    current_y_mm: f64,
-}
+impl<'a> BedTranslator<'a> {
+    fn new(config: &'a MotionConfig) -> Self {
+        Self {
+            config,
+            interp: Interpreter::new(),
+            bed_mm: 0.0,
+            emitted_motion: MotionMode::Rapid,
+            g92_bed_mm: 0.0,
+            g92_bed_suspended: None,
+        }
+    }
 
-/// Translates a sequence of g-code lines: splits Y moves that exceed the gantry limit
-/// into gantry move + bed-axis move. Returns the new list of lines to send.
-///
-/// Tracks G90/G91 (absolute/relative) and current Y position. Non-move lines and
-/// moves without Y are passed through unchanged.
-pub fn translate_lines(lines: &[impl AsRef<str>], config: &MotionConfig) -> Vec<String> {
-    let mut state = TranslateState {
-        absolute: true,
-        current_y_mm: 0.0,
-    };
-    let limit = config.gantry_y_limit_mm;
-    let mut out: Vec<String> = Vec::new();
+    fn bed_letter(&self) -> char {
+        self.config.bed_axis.to_ascii_uppercase()
+    }
 
-    for line in lines {
-        let line = line.as_ref().trim();
+    fn translate_line(&mut self, line: &str, out: &mut Vec<String>) {
+        let line = line.trim();
         if line.is_empty() || line.starts_with(';') {
             out.push(line.to_string());
-            continue;
+            return;
+        }
+        let Ok(exec) = self.interp.execute(line) else {
+            // Let the controller report malformed lines.
+            out.push(line.to_string());
+            return;
+        };
+        self.track_g92(&exec);
+        let Some(motion) = exec.motion.clone() else {
+            if exec.explicit_motion {
+                self.emitted_motion = self.interp.modal().motion;
+            }
+            out.push(line.to_string());
+            return;
+        };
+        if motion.kind == MotionKind::Probe {
+            self.pass_through(line, &exec, out);
+            return;
         }
 
-        // Modal: G90 / G91
-        if line.contains("G90") || line.contains("g90") {
-            state.absolute = true;
+        let limit = self.config.gantry_y_limit_mm;
+        let points = motion.points(self.config.arc_tolerance_mm);
+        let y_changes = points.iter().any(|p| (p.y - motion.start.y).abs() > EPS_MM);
+        let max_y = points.iter().fold(motion.start.y, |m, p| m.max(p.y));
+        let untouched =
+            self.bed_mm.abs() < EPS_MM && self.g92_bed_mm.abs() < EPS_MM && max_y <= limit + EPS_MM;
+        if untouched || (!y_changes && !exec.has_word('Y')) {
+            self.pass_through(line, &exec, out);
+            return;
         }
-        if line.contains("G91") || line.contains("g91") {
-            state.absolute = false;
+
+        let mut pieces = Vec::new();
+        let mut from = motion.start;
+        let mut bed = self.bed_mm;
+        for p in points {
+            for piece in self.split_segment(from, p, bed) {
+                bed = piece.bed;
+                pieces.push(piece);
+            }
+            from = p;
         }
+        self.emit_pieces(&exec, &motion, pieces, out);
+    }
 
-        if !is_move_line(line) {
+    /// Follows the bed extension behind the controller's G92 Y offset.
+    fn track_g92(&mut self, exec: &Executed) {
+        for w in exec.words.iter().filter(|w| w.letter == 'G') {
+            match w.code() {
+                920 if exec.has_word('Y') => self.g92_bed_mm = self.bed_mm,
+                921 => self.g92_bed_mm = 0.0,
+                922 => self.g92_bed_suspended = Some(std::mem::take(&mut self.g92_bed_mm)),
+                923 => {
+                    if let Some(saved) = self.g92_bed_suspended.take() {
+                        self.g92_bed_mm = saved;
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Sends the line unchanged, restoring the motion mode first if an earlier
+    /// rewrite (e.g. a linearized arc) left the controller in a different one.
+    fn pass_through(&mut self, line: &str, exec: &Executed, out: &mut Vec<String>) {
+        let modal = self.interp.modal().motion;
+        if !exec.explicit_motion && self.emitted_motion != modal {
+            out.push(format!("{} {}", modal.word(), line));
+        } else {
             out.push(line.to_string());
-            continue;
         }
+        self.emitted_motion = modal;
+    }
 
-        let y_opt = parse_axis_value(line, 'Y');
-        let feed = parse_axis_value(line, 'F');
+    /// Gantry/bed target for a part-space point: the bed carries whatever
+    /// part-space Y lies beyond the gantry limit.
+    fn piece_at(&self, p: Point) -> Piece {
+        let bed = (p.y - self.config.gantry_y_limit_mm).max(0.0);
+        Piece {
+            gantry: Point { y: p.y - bed, ..p },
+            bed,
+        }
+    }
 
-        let Some(y_value) = y_opt else {
-            out.push(line.to_string());
-            continue;
-        };
+    /// Splits a straight part-space segment into gantry/bed pieces per [`SplitMode`].
+    fn split_segment(&self, from: Point, to: Point, bed_from: f64) -> Vec<Piece> {
+        match self.config.split_mode {
+            SplitMode::Sequential => self.split_sequential(from, to, bed_from),
+            SplitMode::Coordinated => self.split_coordinated(from, to),
+        }
+    }
 
-        let target_y = if state.absolute {
-            y_value
+    /// When extending, the gantry (with X/Z) moves first and the bed follows; when
+    /// retracting, the bed moves first so the gantry never runs past its limit.
+    fn split_sequential(&self, from: Point, to: Point, bed_from: f64) -> Vec<Piece> {
+        let target = self.piece_at(to);
+        let gantry_from_y = from.y - bed_from;
+        if (target.bed - bed_from).abs() < EPS_MM
+            || (target.gantry.y - gantry_from_y).abs() < EPS_MM
+        {
+            return vec![target];
+        }
+        if target.bed > bed_from {
+            vec![
+                Piece {
+                    bed: bed_from,
+                    ..target
+                },
+                target,
+            ]
         } else {
-            state.current_y_mm + y_value
-        };
+            vec![
+                Piece {
+                    gantry: Point {
+                        y: gantry_from_y,
+                        ..from
+                    },
+                    bed: target.bed,
+                },
+                target,
+            ]
+        }
+    }
 
-        if target_y <= limit {
-            out.push(line.to_string());
-            state.current_y_mm = target_y;
-            continue;
+    /// Splits at the point where the XYZ vector crosses the limit, so each piece
+    /// moves all axes together and the tool stays on the programmed line.
+    fn split_coordinated(&self, from: Point, to: Point) -> Vec<Piece> {
+        let limit = self.config.gantry_y_limit_mm;
+        let crosses = (from.y - limit) * (to.y - limit) < 0.0;
+        if !crosses {
+            return vec![self.piece_at(to)];
         }
+        let t = (limit - from.y) / (to.y - from.y);
+        let mut crossing = self.piece_at(from.lerp(&to, t));
+        // Snap exactly onto the limit so the first piece has no bed component.
+        crossing.gantry.y = limit;
+        crossing.bed = 0.0;
+        vec![crossing, self.piece_at(to)]
+    }
 
-        // Split: move gantry to limit, then bed for the rest
-        let to_limit = limit - state.current_y_mm;
-        let overflow = target_y - limit;
+    /// Gantry position in the line's output frame and units (program or machine coordinates).
+    fn output_point(&self, gantry: Point, machine: bool) -> [f64; 3] {
+        let units = self.interp.modal().units;
+        let frame = if machine {
+            let m = self.interp.machine_offset();
+            Point::new(gantry.x + m.x, gantry.y + m.y, gantry.z + m.z)
+        } else {
+            let g = self.interp.g92_offset();
+            let gy = g.y - self.g92_bed_mm;
+            Point::new(gantry.x - g.x, gantry.y - gy, gantry.z - g.z)
+        };
+        [
+            round4(units.from_mm(frame.x)),
+            round4(units.from_mm(frame.y)),
+            round4(units.from_mm(frame.z)),
+        ]
+    }
+
+    /// Axis words moving from one piece to the next, in the program's distance mode.
+    fn axis_words(&self, from: Piece, to: Piece, machine: bool) -> Vec<String> {
+        let modal = self.interp.modal();
+        let absolute = machine || modal.distance == DistanceMode::Absolute;
+        let prev = self.output_point(from.gantry, machine);
+        let next = self.output_point(to.gantry, machine);
+        let mut words = Vec::new();
+        for (i, letter) in ['X', 'Y', 'Z'].into_iter().enumerate() {
+            if prev[i] != next[i] {
+                let v = if absolute {
+                    next[i]
+                } else {
+                    round4(next[i] - prev[i])
+                };
+                words.push(format!("{}{:.4}", letter, v));
+            }
+        }
+        let bed_prev = round4(modal.units.from_mm(from.bed));
+        let bed_next = round4(modal.units.from_mm(to.bed));
+        if bed_prev != bed_next {
+            let v = if absolute {
+                bed_next
+            } else {
+                round4(bed_next - bed_prev)
+            };
+            words.push(format!("{}{:.4}", self.bed_letter(), v));
+        }
+        words
+    }
 
-        if to_limit > 0.0 {
-            let first_line = if state.absolute {
-                replace_y_in_line(line, limit)
+    fn emit_pieces(
+        &mut self,
+        exec: &Executed,
+        motion: &Motion,
+        pieces: Vec<Piece>,
+        out: &mut Vec<String>,
+    ) {
+        let kind_word = if motion.kind == MotionKind::Rapid {
+            MotionMode::Rapid
+        } else {
+            MotionMode::Linear
+        };
+        let feed = exec
+            .words
+            .iter()
+            .find(|w| w.letter == 'F')
+            .map(|w| w.raw.clone());
+        let mut prev = Piece {
+            gantry: Point {
+                y: motion.start.y - self.bed_mm,
+                ..motion.start
+            },
+            bed: self.bed_mm,
+        };
+        let mut first = true;
+        for piece in pieces {
+            let axes = self.axis_words(prev, piece, motion.machine_coords);
+            if axes.is_empty() {
+                continue;
+            }
+            let line = if first {
+                self.rewrite_first(exec, kind_word, axes)
             } else {
-                replace_y_in_line(line, to_limit)
+                let mut parts = Vec::new();
+                if motion.machine_coords {
+                    parts.push("G53".to_string());
+                }
+                parts.push(kind_word.word().to_string());
+                parts.extend(axes);
+                parts.extend(feed.clone());
+                parts.join(" ")
             };
-            out.push(first_line);
+            out.push(line);
+            first = false;
+            prev = piece;
         }
-        out.push(bed_axis_line(config, overflow, feed));
-        state.current_y_mm = target_y;
+        self.bed_mm = prev.bed;
+        self.emitted_motion = kind_word;
     }
 
+    /// The original line with its axis (and arc) words replaced by `axes`; other
+    /// words (N, F, S, M, modal G codes) keep their original text and order.
+    fn rewrite_first(&self, exec: &Executed, kind_word: MotionMode, axes: Vec<String>) -> String {
+        let bed = self.bed_letter();
+        let mut parts: Vec<String> = Vec::new();
+        let mut axes = Some(axes);
+        let mut has_motion_word = false;
+        for w in &exec.words {
+            match w.letter {
+                'X' | 'Y' | 'Z' | 'I' | 'J' | 'K' | 'R' => {
+                    if let Some(a) = axes.take() {
+                        parts.extend(a);
+                    }
+                }
+                l if l == bed => {}
+                'G' if matches!(w.code(), 20 | 30) => {
+                    parts.push(kind_word.word().to_string());
+                    has_motion_word = true;
+                }
+                'G' if matches!(w.code(), 0 | 10) => {
+                    parts.push(w.raw.clone());
+                    has_motion_word = true;
+                }
+                _ => parts.push(w.raw.clone()),
+            }
+        }
+        if let Some(a) = axes {
+            parts.extend(a);
+        }
+        if !has_motion_word && self.emitted_motion != kind_word {
+            parts.insert(0, kind_word.word().to_string());
+        }
+        parts.join(" ")
+    }
+}
+
+/// Maps a reported position (MPos or WPos) into part space: the tool's Y on the
+/// part is gantry Y plus the bed-axis extension. Inverse of the split done by
+/// [`translate_lines`].
+///
+/// Status reports only carry a fourth axis as `a`, so the mapping applies when
+/// the bed axis is `A`; otherwise, or when no fourth axis is reported, the
+/// position is returned unchanged.
+pub fn part_position(pos: &Position, config: &MotionConfig) -> Position {
+    let bed = match pos.a {
+        Some(a) if config.bed_axis.eq_ignore_ascii_case(&'A') => a,
+        _ => 0.0,
+    };
+    Position {
+        y: pos.y + bed,
+        ..pos.clone()
+    }
+}
+
+/// Sets `status.part_pos` from its work position (see [`part_position`]).
+pub fn apply_part_position(status: &mut MachineStatus, config: &MotionConfig) {
+    status.part_pos = part_position(&status.work_pos, config);
+}
+
+/// Translates a sequence of g-code lines for the bed extension. Returns the new
+/// list of lines to send.
+///
+/// Runs every line through the modal [`Interpreter`], so inch programs (G20),
+/// G90/G91, G92 offsets and G53 machine moves are resolved before the gantry
+/// limit is checked. Moves that go beyond the limit are split: the gantry stops
+/// at the limit and the bed axis carries the overflow; moves that come back
+/// retract the bed. [`MotionConfig::split_mode`] selects whether the split is
+/// sequential or coordinated along the whole move. Arcs that reach past the limit are
+/// linearized and each chord is split the same way. Generated lines use the
+/// program's units and distance mode. Lines that stay within the gantry travel
+/// are passed through unchanged.
+pub fn translate_lines(lines: &[impl AsRef<str>], config: &MotionConfig) -> Vec<String> {
+    let mut translator = BedTranslator::new(config);
+    let mut out: Vec<String> = Vec::new();
+    for line in lines {
+        translator.translate_line(line.as_ref(), &mut out);
+    }
+    out
+}
+
+/// A translated line and the source line it was generated from.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TranslatedLine {
+    /// 1-based line number in the original program.
+    pub source_line: usize,
+    pub text: String,
+}
+
+/// Same as [`translate_lines`], keeping track of which source line produced each
+/// output line (a split move yields several lines with the same `source_line`).
+pub fn translate_lines_with_source(
+    lines: &[impl AsRef<str>],
+    config: &MotionConfig,
+) -> Vec<TranslatedLine> {
+    let numbered = lines.iter().enumerate().map(|(i, line)| TranslatedLine {
+        source_line: i + 1,
+        text: line.as_ref().to_string(),
+    });
+    translate_numbered(numbered, config)
+}
+
+/// Same as [`translate_lines_with_source`] for lines that already carry their
+/// source line (e.g. the output of another transform).
+pub fn translate_numbered(
+    lines: impl IntoIterator<Item = TranslatedLine>,
+    config: &MotionConfig,
+) -> Vec<TranslatedLine> {
+    let mut translator = BedTranslator::new(config);
+    let mut out: Vec<TranslatedLine> = Vec::new();
+    let mut buf: Vec<String> = Vec::new();
+    for line in lines {
+        translator.translate_line(&line.text, &mut buf);
+        out.extend(buf.drain(..).map(|text| TranslatedLine {
+            source_line: line.source_line,
+            text,
+        }));
+    }
     out
 }
 
@@ -194,23 +480,11 @@ mod tests {
         let c = MotionConfig::default();
         assert_eq!(c.gantry_y_limit_mm, DEFAULT_GANTRY_Y_LIMIT_MM);
         assert_eq!(c.bed_axis, 'A');
-    }
-
-    #[test]
-    fn test_parse_axis_value() {
-        assert_eq!(parse_axis_value("G1 Y10.5 F300", 'Y'), Some(10.5));
-        assert_eq!(parse_axis_value("G1 X1 Y-2.5 Z0", 'Y'), Some(-2.5));
-        assert_eq!(parse_axis_value("G1 X1 Z0", 'Y'), None);
-        assert_eq!(parse_axis_value("F500", 'F'), Some(500.0));
-    }
-
-    #[test]
-    fn test_is_move_line() {
-        assert!(is_move_line("G0 Y10"));
-        assert!(is_move_line("G1 X10 Y20 F300"));
-        assert!(is_move_line("G1Y100"));
-        assert!(!is_move_line("G28"));
-        assert!(!is_move_line("; comment"));
+        let c: MotionConfig =
+            serde_json::from_str(r#"{"bed_axis":"B","split_mode":"Coordinated"}"#).unwrap();
+        assert_eq!(c.bed_axis, 'B');
+        assert_eq!(c.split_mode, SplitMode::Coordinated);
+        assert_eq!(c.gantry_y_limit_mm, DEFAULT_GANTRY_Y_LIMIT_MM);
     }
 
     #[test]
@@ -261,4 +535,200 @@ mod tests {
         assert_eq!(out[1], "G0 X10");
         assert_eq!(out[2], "G1 Y500 F300");
     }
+
+    #[test]
+    fn test_translate_inch_units() {
+        let config = MotionConfig::default();
+        // 30 in = 762 mm; limit 609.6 mm = 24 in.
+        let lines = ["G20 G90", "G1 Y30 F10"];
+        let out = translate_lines(&lines, &config);
+        assert_eq!(out, vec!["G20 G90", "G1 Y24.0000 F10", "G1 A6.0000 F10"]);
+    }
+
+    #[test]
+    fn test_translate_return_retracts_bed() {
+        let config = MotionConfig::default();
+        let lines = ["G90", "G1 Y700 F300", "G1 Y650", "G1 Y100"];
+        let out = translate_lines(&lines, &config);
+        assert_eq!(out[1], "G1 Y609.6000 F300");
+        assert_eq!(out[2], "G1 A90.4000 F300");
+        // Still beyond the limit: only the bed moves back.
+        assert_eq!(out[3], "G1 A40.4000");
+        // Coming back under the limit: bed retracts first, then the gantry.
+        assert_eq!(out[4], "G1 A0.0000");
+        assert_eq!(out[5], "G1 Y100.0000");
+        assert_eq!(out.len(), 6);
+    }
+
+    #[test]
+    fn test_translate_start_beyond_limit_relative() {
+        let config = MotionConfig::default();
+        let lines = ["G91", "G1 Y700 F200", "G1 Y-200"];
+        let out = translate_lines(&lines, &config);
+        assert_eq!(out[1], "G1 Y609.6000 F200");
+        assert_eq!(out[2], "G1 A90.4000 F200");
+        assert_eq!(out[3], "G1 A-90.4000");
+        assert_eq!(out[4], "G1 Y-109.6000");
+    }
+
+    #[test]
+    fn test_translate_rewrites_y_while_bed_extended() {
+        let config = MotionConfig::default();
+        let lines = ["G1 Y700 F300", "G1 X10 Y700", "G0 Z5"];
+        let out = translate_lines(&lines, &config);
+        assert_eq!(out[2], "G1 X10.0000");
+        assert_eq!(out[3], "G0 Z5");
+    }
+
+    #[test]
+    fn test_translate_g92_offset() {
+        let config = MotionConfig::default();
+        // After G92 Y0 at Y500, program Y200 is work Y700.
+        let lines = ["G0 Y500", "G92 Y0", "G1 Y200 F100"];
+        let out = translate_lines(&lines, &config);
+        assert_eq!(out[1], "G92 Y0");
+        assert_eq!(out[2], "G1 Y109.6000 F100");
+        assert_eq!(out[3], "G1 A90.4000 F100");
+    }
+
+    #[test]
+    fn test_translate_g92_while_bed_extended() {
+        let config = MotionConfig::default();
+        // G92 Y0 at part Y700 makes the controller's offset gantry Y609.6.
+        let lines = [
+            "G0 Y700",
+            "G92 Y0",
+            "G1 Y-100 F100",
+            "G1 Y-200",
+            "G92.1",
+            "G0 Y0",
+        ];
+        let out = translate_lines(&lines, &config);
+        assert_eq!(out[2], "G92 Y0");
+        // Part Y600: the bed retracts first, then gantry 600 is 9.6 below the
+        // offset origin.
+        assert_eq!(out[3], "G1 A0.0000 F100");
+        assert_eq!(out[4], "G1 Y-9.6000 F100");
+        // Still translated after the bed is home: part Y500 is gantry 500.
+        assert_eq!(out[5], "G1 Y-109.6000");
+        assert_eq!(out[6], "G92.1");
+        assert_eq!(out[7], "G0 Y0");
+    }
+
+    #[test]
+    fn test_translate_g53_machine_move() {
+        let config = MotionConfig::default();
+        let lines = ["G53 G0 Y700"];
+        let out = translate_lines(&lines, &config);
+        assert_eq!(out, vec!["G53 G0 Y609.6000", "G53 G0 A90.4000"]);
+    }
+
+    #[test]
+    fn test_translate_arc_crossing_limit_is_linearized() {
+        let config = MotionConfig::default();
+        // Half circle centered at Y600, radius 50: peaks at Y650.
+        let lines = ["G0 X0 Y600", "G2 X100 Y600 I50 J0 F500", "G1 X0"];
+        let out = translate_lines(&lines, &config);
+        assert_eq!(out[0], "G0 X0 Y600");
+        assert!(out.len() > 10);
+        assert!(out[1].starts_with("G1 X"));
+        assert!(out.iter().any(|l| l.contains('A')));
+        assert!(out.iter().all(|l| !l.contains("G2") && !l.contains('I')));
+        // Ends with the bed retracted and the arc end on the gantry.
+        let last_a = out.iter().rev().find(|l| l.contains(" A")).unwrap();
+        assert!(last_a.contains("A0.0000"));
+        // The following G1 move matches the emitted mode and passes through.
+        assert_eq!(out.last().unwrap(), "G1 X0");
+    }
+
+    #[test]
+    fn test_translate_arc_within_limit_passthrough() {
+        let config = MotionConfig::default();
+        let lines = ["G0 X0 Y0", "G2 X100 Y0 I50 J0 F500", "X0 Y0 I-50 J0"];
+        let out = translate_lines(&lines, &config);
+        assert_eq!(
+            out,
+            vec!["G0 X0 Y0", "G2 X100 Y0 I50 J0 F500", "X0 Y0 I-50 J0"]
+        );
+    }
+
+    #[test]
+    fn test_translate_restores_arc_mode_after_linearizing() {
+        let config = MotionConfig::default();
+        let lines = ["G0 X0 Y600", "G2 X100 Y600 I50 J0 F500", "X0 Y0 I-50 J-300"];
+        let out = translate_lines(&lines, &config);
+        assert!(out.last().unwrap().starts_with("G2 X0 Y0"));
+    }
+
+    #[test]
+    fn test_translate_coordinated_split_keeps_toolpath() {
+        let config = MotionConfig {
+            split_mode: SplitMode::Coordinated,
+            ..MotionConfig::default()
+        };
+        // From (0, 509.6) to (100, 709.6): crosses the limit halfway.
+        let lines = ["G0 X0 Y509.6", "G1 X100 Y709.6 Z-2 F600"];
+        let out = translate_lines(&lines, &config);
+        assert_eq!(out.len(), 3);
+        assert_eq!(out[1], "G1 X50.0000 Y609.6000 Z-1.0000 F600");
+        assert_eq!(out[2], "G1 X100.0000 Z-2.0000 A100.0000 F600");
+    }
+
+    #[test]
+    fn test_translate_coordinated_split_return() {
+        let config = MotionConfig {
+            split_mode: SplitMode::Coordinated,
+            ..MotionConfig::default()
+        };
+        let lines = ["G91", "G1 Y709.6 F600", "G1 X100 Y-200"];
+        let out = translate_lines(&lines, &config);
+        // Back to the crossing with the bed retracting while X moves, then gantry.
+        assert_eq!(out[3], "G1 X50.0000 A-100.0000");
+        assert_eq!(out[4], "G1 X50.0000 Y-100.0000");
+    }
+
+    #[test]
+    fn test_translate_sequential_beyond_limit_is_single_line() {
+        let config = MotionConfig::default();
+        let lines = ["G1 Y700 F300", "G1 X20 Y720"];
+        let out = translate_lines(&lines, &config);
+        assert_eq!(out[2], "G1 X20.0000 A110.4000");
+    }
+
+    #[test]
+    fn test_part_position_adds_bed_extension() {
+        let config = MotionConfig::default();
+        let pos = Position {
+            x: 1.0,
+            y: 609.6,
+            z: -2.0,
+            a: Some(90.4),
+        };
+        let part = part_position(&pos, &config);
+        assert!((part.y - 700.0).abs() < 1e-9);
+        assert_eq!(part.x, 1.0);
+        assert_eq!(part.a, Some(90.4));
+
+        let no_bed = Position {
+            a: None,
+            ..pos.clone()
+        };
+        assert_eq!(part_position(&no_bed, &config), no_bed);
+
+        let other_axis = MotionConfig {
+            bed_axis: 'B',
+            ..MotionConfig::default()
+        };
+        assert_eq!(part_position(&pos, &other_axis), pos);
+    }
+
+    #[test]
+    fn test_translate_with_source_lines() {
+        let config = MotionConfig::default();
+        let lines = ["G90", "G1 Y700 F300", "G1 X5"];
+        let out = translate_lines_with_source(&lines, &config);
+        let sources: Vec<usize> = out.iter().map(|l| l.source_line).collect();
+        assert_eq!(sources, vec![1, 2, 2, 3]);
+        assert_eq!(out[2].text, "G1 A90.4000 F300");
+    }
 }
@@ -0,0 +1,252 @@
+//! Job outline (perimeter trace) for checking stock placement.
+//!
+//! Computes the XY bounding box or convex hull of a program's cutting moves and
+//! turns it into `$J=` jog commands that trace it at a safe Z with the spindle
+//! off. Jogs can be stopped at any point with `RealtimeCommand::JogCancel`.
+//! Corners are in part space; each jog splits part-space Y into gantry Y and
+//! bed-axis travel so the tool lands over the same spot on the part (unless
+//! the bed axis is turned off in [`OutlineOptions`]).
+
+use super::commands::GrblCommand;
+use super::gcode::{Interpreter, MotionKind};
+use super::motion::MotionConfig;
+use super::resume::SafeZ;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// Shape traced around the job.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum OutlineShape {
+    /// Axis-aligned XY bounding box.
+    #[default]
+    BoundingBox,
+    /// Convex hull of all cutting points (tighter for round or angled parts).
+    ConvexHull,
+}
+
+/// Options for [`outline_jogs`].
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct OutlineOptions {
+    /// Height to trace at. Applied before the first XY move.
+    pub safe_z: SafeZ,
+    /// Jog feed rate (mm/min).
+    pub feed_mm_min: f64,
+    /// Split part-space Y into gantry Y and bed-axis travel. Disable on machines
+    /// without the bed axis; Y is then jogged as it is.
+    pub use_bed_axis: bool,
+}
+
+impl Default for OutlineOptions {
+    fn default() -> Self {
+        Self {
+            safe_z: SafeZ::Machine(-1.0),
+            feed_mm_min: 2000.0,
+            use_bed_axis: true,
+        }
+    }
+}
+
+/// Closed XY outline of a job in part-space work coordinates (mm).
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct JobOutline {
+    pub shape: OutlineShape,
+    /// Corners, counter-clockwise. The trace returns to the first corner.
+    pub points: Vec<[f64; 2]>,
+}
+
+/// Outline of the cutting moves (G1/G2/G3/G38.x) of a program, as loaded from
+/// the file. `None` if the program does not cut anything.
+pub fn job_outline(
+    lines: &[impl AsRef<str>],
+    shape: OutlineShape,
+    config: &MotionConfig,
+) -> Option<JobOutline> {
+    let mut interp = Interpreter::new();
+    let mut points: Vec<[f64; 2]> = Vec::new();
+    for line in lines {
+        let Some(m) = interp.execute(line.as_ref()).ok().and_then(|e| e.motion) else {
+            continue;
+        };
+        if m.kind == MotionKind::Rapid {
+            continue;
+        }
+        points.push([m.start.x, m.start.y]);
+        points.extend(
+            m.points(config.arc_tolerance_mm)
+                .into_iter()
+                .map(|p| [p.x, p.y]),
+        );
+    }
+    if points.is_empty() {
+        return None;
+    }
+    let points = match shape {
+        OutlineShape::BoundingBox => bounding_box(&points),
+        OutlineShape::ConvexHull => convex_hull(points),
+    };
+    Some(JobOutline { shape, points })
+}
+
+/// Read a G-code file and compute its outline.
+pub fn outline_file(
+    path: &Path,
+    shape: OutlineShape,
+    config: &MotionConfig,
+) -> std::io::Result<Option<JobOutline>> {
+    let content = std::fs::read_to_string(path)?;
+    let lines: Vec<&str> = content.lines().collect();
+    Ok(job_outline(&lines, shape, config))
+}
+
+fn bounding_box(points: &[[f64; 2]]) -> Vec<[f64; 2]> {
+    let mut min = [f64::MAX; 2];
+    let mut max = [f64::MIN; 2];
+    for p in points {
+        for i in 0..2 {
+            min[i] = min[i].min(p[i]);
+            max[i] = max[i].max(p[i]);
+        }
+    }
+    vec![min, [max[0], min[1]], max, [min[0], max[1]]]
+}
+
+/// Andrew's monotone chain; counter-clockwise, collinear points dropped.
+fn convex_hull(mut points: Vec<[f64; 2]>) -> Vec<[f64; 2]> {
+    points.sort_by(|a, b| a[0].total_cmp(&b[0]).then(a[1].total_cmp(&b[1])));
+    points.dedup();
+    if points.len() < 3 {
+        return points;
+    }
+    let cross = |o: [f64; 2], a: [f64; 2], b: [f64; 2]| {
+        (a[0] - o[0]) * (b[1] - o[1]) - (a[1] - o[1]) * (b[0] - o[0])
+    };
+    let mut hull: Vec<[f64; 2]> = Vec::with_capacity(points.len() * 2);
+    for pass in [points.clone(), points.into_iter().rev().collect()] {
+        let floor = hull.len();
+        for p in pass {
+            while hull.len() >= floor + 2
+                && cross(hull[hull.len() - 2], hull[hull.len() - 1], p) <= 0.0
+            {
+                hull.pop();
+            }
+            hull.push(p);
+        }
+        // Last point of each half is the first of the other.
+        hull.pop();
+    }
+    hull
+}
+
+/// Jog commands tracing the outline: safe Z, then each corner and back to the first.
+/// Send `M5` before these; jogs cannot switch the spindle off.
+pub fn outline_jogs(
+    outline: &JobOutline,
+    options: &OutlineOptions,
+    config: &MotionConfig,
+) -> Vec<GrblCommand> {
+    let f = options.feed_mm_min;
+    let mut jogs = vec![GrblCommand::Jog(match options.safe_z {
+        SafeZ::Machine(z) => format!("G21G53Z{:.4}F{:.0}", z, f),
+        SafeZ::Work(z) => format!("G21G90Z{:.4}F{:.0}", z, f),
+    })];
+    let bed = config.bed_axis.to_ascii_uppercase();
+    let limit = config.gantry_y_limit_mm;
+    for p in outline.points.iter().chain(outline.points.first()) {
+        if !options.use_bed_axis {
+            jogs.push(GrblCommand::Jog(format!(
+                "G21G90X{:.4}Y{:.4}F{:.0}",
+                p[0], p[1], f
+            )));
+            continue;
+        }
+        let gantry_y = p[1].min(limit);
+        let bed_mm = (p[1] - limit).max(0.0);
+        jogs.push(GrblCommand::Jog(format!(
+            "G21G90X{:.4}Y{:.4}{}{:.4}F{:.0}",
+            p[0], gantry_y, bed, bed_mm, f
+        )));
+    }
+    jogs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const JOB: [&str; 5] = [
+        "G0 X-20 Y-20 Z5",
+        "G1 Z-1 F300",
+        "G1 X10 Y0",
+        "G1 X30 Y20",
+        "G1 X0 Y40",
+    ];
+
+    #[test]
+    fn test_bounding_box_ignores_rapids() {
+        let o = job_outline(&JOB, OutlineShape::BoundingBox, &MotionConfig::default()).unwrap();
+        assert_eq!(
+            o.points,
+            vec![[-20.0, -20.0], [30.0, -20.0], [30.0, 40.0], [-20.0, 40.0]]
+        );
+        // Rapid-only programs have nothing to outline.
+        assert!(job_outline(
+            &["G0 X100 Y100"],
+            OutlineShape::BoundingBox,
+            &MotionConfig::default()
+        )
+        .is_none());
+    }
+
+    #[test]
+    fn test_convex_hull() {
+        let lines = [
+            "G1 X0 Y0 F100",
+            "X10 Y0",
+            "X5 Y5",
+            "X10 Y10",
+            "X0 Y10",
+            "X5 Y0",
+        ];
+        let o = job_outline(&lines, OutlineShape::ConvexHull, &MotionConfig::default()).unwrap();
+        assert_eq!(
+            o.points,
+            vec![[0.0, 0.0], [10.0, 0.0], [10.0, 10.0], [0.0, 10.0]]
+        );
+    }
+
+    #[test]
+    fn test_jogs_split_bed_travel() {
+        let outline = JobOutline {
+            shape: OutlineShape::BoundingBox,
+            points: vec![[0.0, 0.0], [100.0, 0.0], [100.0, 700.0], [0.0, 700.0]],
+        };
+        let jogs: Vec<String> = outline_jogs(
+            &outline,
+            &OutlineOptions::default(),
+            &MotionConfig::default(),
+        )
+        .iter()
+        .map(|c| c.to_string())
+        .collect();
+        assert_eq!(jogs.len(), 6);
+        assert_eq!(jogs[0], "$J=G21G53Z-1.0000F2000");
+        assert_eq!(jogs[1], "$J=G21G90X0.0000Y0.0000A0.0000F2000");
+        assert_eq!(jogs[3], "$J=G21G90X100.0000Y609.6000A90.4000F2000");
+        assert_eq!(jogs[5], jogs[1]);
+    }
+
+    #[test]
+    fn test_jogs_without_bed_axis() {
+        let outline = JobOutline {
+            shape: OutlineShape::BoundingBox,
+            points: vec![[0.0, 0.0], [100.0, 0.0], [100.0, 50.0], [0.0, 50.0]],
+        };
+        let options = OutlineOptions {
+            use_bed_axis: false,
+            ..OutlineOptions::default()
+        };
+        let jogs = outline_jogs(&outline, &options, &MotionConfig::default());
+        assert_eq!(jogs[3].to_string(), "$J=G21G90X100.0000Y50.0000F2000");
+        assert!(!jogs.iter().any(|j| j.to_string().contains('A')));
+    }
+}
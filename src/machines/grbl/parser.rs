@@ -3,7 +3,10 @@
 //! No async, no I/O — only string/line parsing. Used by the poller and
 //! other tasks that receive data from the serial port.
 
+use super::gcode::Units;
 use super::state::*;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 use std::collections::HashMap;
 use std::time::Instant;
 use thiserror::Error;
@@ -19,6 +22,8 @@ pub enum ParseError {
     InvalidSettingsLine(String),
     #[error("invalid alarm message: {0}")]
     InvalidAlarm(String),
+    #[error("invalid probe report: {0}")]
+    InvalidProbe(String),
 }
 
 /// Parses a single real-time status line (response to `?`).
@@ -71,6 +76,7 @@ pub fn parse_status(line: &str, last_updated: Instant) -> Result<MachineStatus,
     Ok(MachineStatus {
         state,
         machine_pos,
+        part_pos: work_pos.clone(),
         work_pos,
         feed_rate,
         spindle_speed,
@@ -175,6 +181,101 @@ pub fn parse_alarm_code(s: &str) -> Result<AlarmCode, ParseError> {
     Ok(AlarmCode::from(n))
 }
 
+/// One line received in reply to a sent line.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Response {
+    /// `ok`: the line was accepted.
+    Ok,
+    /// `error:N`: the line was rejected. Holds the code (`"20"`).
+    Error(String),
+    /// `ALARM:N`. Holds the code.
+    Alarm(String),
+    /// Informational feedback (`[MSG:...]`, `[GC:...]`, `<...>` status, welcome banner).
+    /// Not a reply to the line; keep reading.
+    Feedback(String),
+}
+
+/// Classifies a received line. Anything that is not `ok`, `error:` or `ALARM:`
+/// is treated as feedback.
+pub fn parse_response(line: &str) -> Response {
+    let line = line.trim();
+    if line.eq_ignore_ascii_case("ok") {
+        return Response::Ok;
+    }
+    let code = |prefix: &str| {
+        line.get(..prefix.len())
+            .filter(|p| p.eq_ignore_ascii_case(prefix))
+            .map(|_| line[prefix.len()..].trim().to_string())
+    };
+    if let Some(c) = code("error:") {
+        Response::Error(c)
+    } else if let Some(c) = code("ALARM:") {
+        Response::Alarm(c)
+    } else {
+        Response::Feedback(line.to_string())
+    }
+}
+
+/// Active work coordinate system from a `$G` parser state report
+/// (`[GC:G0 G55 G17 ...]`), as its `G10` P number (G54 = 1 ... G59.3 = 9).
+pub fn parse_active_wcs(line: &str) -> Option<u8> {
+    let body = line.trim().strip_prefix("[GC:")?.strip_suffix(']')?;
+    body.split_whitespace().find_map(|word| match word {
+        "G54" => Some(1),
+        "G55" => Some(2),
+        "G56" => Some(3),
+        "G57" => Some(4),
+        "G58" => Some(5),
+        "G59" => Some(6),
+        "G59.1" => Some(7),
+        "G59.2" => Some(8),
+        "G59.3" => Some(9),
+        _ => None,
+    })
+}
+
+/// Active length units from a `$G` parser state reply.
+pub fn parse_units(line: &str) -> Option<Units> {
+    let body = line.trim().strip_prefix("[GC:")?.strip_suffix(']')?;
+    body.split_whitespace().find_map(|word| match word {
+        "G20" => Some(Units::Inch),
+        "G21" => Some(Units::Mm),
+        _ => None,
+    })
+}
+
+/// Active tool length offset (mm) from the `[TLO:z]` line of a `$#` reply.
+/// Controllers that offset several axes report `[TLO:x,y,z]`; Z is returned.
+pub fn parse_tool_length_offset(line: &str) -> Option<f64> {
+    let body = line.trim().strip_prefix("[TLO:")?.strip_suffix(']')?;
+    let values: Vec<&str> = body.split(',').collect();
+    values.get(2).unwrap_or(&values[0]).trim().parse().ok()
+}
+
+/// Result of a probing cycle (`[PRB:x,y,z:1]`), in machine coordinates.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ProbeReport {
+    pub position: Position,
+    /// False if the probe did not trigger (only with G38.3/G38.5).
+    pub success: bool,
+}
+
+/// Parses a probe report: `[PRB:x,y,z[,a]:flag]`.
+pub fn parse_probe_report(line: &str) -> Result<ProbeReport, ParseError> {
+    let s = line.trim();
+    let body = s
+        .strip_prefix("[PRB:")
+        .and_then(|b| b.strip_suffix(']'))
+        .ok_or_else(|| ParseError::InvalidProbe(s.to_string()))?;
+    let (coords, flag) = body
+        .rsplit_once(':')
+        .ok_or_else(|| ParseError::InvalidProbe(s.to_string()))?;
+    Ok(ProbeReport {
+        position: parse_position(coords)?,
+        success: flag.trim() == "1",
+    })
+}
+
 /// Parsed settings from a `$$` response: setting number -> value string.
 /// Values are kept as strings; callers may interpret as int/float/bool as needed.
 #[derive(Clone, Debug, Default)]
@@ -182,6 +283,25 @@ pub struct GrblSettings {
     pub raw: HashMap<u32, String>,
 }
 
+impl GrblSettings {
+    /// Setting `$n` parsed as a number, if present and numeric.
+    pub fn get_f64(&self, n: u32) -> Option<f64> {
+        self.raw.get(&n).and_then(|v| v.trim().parse().ok())
+    }
+
+    /// SHA-256 (hex) of all settings as sorted `$N=value` lines, to tell whether
+    /// two snapshots differ.
+    pub fn hash(&self) -> String {
+        let mut numbers: Vec<&u32> = self.raw.keys().collect();
+        numbers.sort();
+        let mut hasher = Sha256::new();
+        for n in numbers {
+            hasher.update(format!("${}={}\n", n, self.raw[n]).as_bytes());
+        }
+        format!("{:x}", hasher.finalize())
+    }
+}
+
 /// Parses the lines of a `$$` settings response.
 ///
 /// Each line should be `$N=value`. Empty lines and a trailing `ok` are
@@ -318,6 +438,7 @@ mod tests {
     fn test_parse_alarm_code_alarm_prefix() {
         let code = parse_alarm_code("ALARM:1").unwrap();
         assert_eq!(code, AlarmCode::HardLimit);
+        assert_eq!(code.number(), 1);
     }
 
     #[test]
@@ -330,6 +451,7 @@ mod tests {
     fn test_parse_alarm_code_unknown_number() {
         let code = parse_alarm_code("ALARM:99").unwrap();
         assert!(matches!(code, AlarmCode::Unknown(99)));
+        assert_eq!(code.number(), 99);
     }
 
     #[test]
@@ -338,6 +460,34 @@ mod tests {
         assert!(matches!(err, ParseError::InvalidAlarm(_)));
     }
 
+    #[test]
+    fn test_parse_response() {
+        assert_eq!(parse_response("ok\r"), Response::Ok);
+        assert_eq!(parse_response("error:20"), Response::Error("20".into()));
+        assert_eq!(parse_response("Error: 9"), Response::Error("9".into()));
+        assert_eq!(parse_response("ALARM:1"), Response::Alarm("1".into()));
+        assert_eq!(
+            parse_response("[MSG:Enabled]"),
+            Response::Feedback("[MSG:Enabled]".into())
+        );
+        assert!(matches!(parse_response("<Idle|MPos:0,0,0>"), Response::Feedback(_)));
+    }
+
+    #[test]
+    fn test_parse_probe_report() {
+        let r = parse_probe_report("[PRB:10.000,-5.500,-42.125:1]").unwrap();
+        assert_eq!(r.position.z, -42.125);
+        assert_eq!(r.position.y, -5.5);
+        assert!(r.success);
+        let r = parse_probe_report("[PRB:0,0,0,12.5:0]").unwrap();
+        assert_eq!(r.position.a, Some(12.5));
+        assert!(!r.success);
+        assert!(matches!(
+            parse_probe_report("[MSG:Pgm End]"),
+            Err(ParseError::InvalidProbe(_))
+        ));
+    }
+
     #[test]
     fn test_parse_settings() {
         let lines = "$0=10\n$1=25\n$21=0\nok\n";
@@ -346,6 +496,14 @@ mod tests {
         assert_eq!(settings.raw.get(&1), Some(&"25".to_string()));
         assert_eq!(settings.raw.get(&21), Some(&"0".to_string()));
         assert!(!settings.raw.contains_key(&99));
+        assert_eq!(settings.get_f64(1), Some(25.0));
+        assert_eq!(settings.get_f64(99), None);
+        let reordered = parse_settings("$21=0\n$0=10\n$1=25\n").unwrap();
+        assert_eq!(settings.hash(), reordered.hash());
+        assert_ne!(
+            settings.hash(),
+            parse_settings("$0=10\n$1=26\n$21=0").unwrap().hash()
+        );
     }
 
     #[test]
@@ -361,4 +519,34 @@ mod tests {
         let settings = parse_settings(lines).unwrap();
         assert_eq!(settings.raw.get(&340), Some(&"0".to_string()));
     }
+
+    #[test]
+    fn test_parse_active_wcs() {
+        assert_eq!(
+            parse_active_wcs("[GC:G0 G55 G17 G21 G90 G94 M5 M9 T0 F0 S0]"),
+            Some(2)
+        );
+        assert_eq!(parse_active_wcs("[GC:G1 G59.1 G17]"), Some(7));
+        assert_eq!(parse_active_wcs("[MSG:G54]"), None);
+    }
+
+    #[test]
+    fn test_parse_units() {
+        assert_eq!(
+            parse_units("[GC:G0 G54 G17 G20 G90 G94 M5 M9 T0 F0 S0]"),
+            Some(Units::Inch)
+        );
+        assert_eq!(parse_units("[GC:G1 G55 G21]"), Some(Units::Mm));
+        assert_eq!(parse_units("[GC:G0 G54]"), None);
+    }
+
+    #[test]
+    fn test_parse_tool_length_offset() {
+        assert_eq!(parse_tool_length_offset("[TLO:2.000]"), Some(2.0));
+        assert_eq!(
+            parse_tool_length_offset("[TLO:0.000,0.000,-1.500]"),
+            Some(-1.5)
+        );
+        assert_eq!(parse_tool_length_offset("[G54:0.000,0.000,0.000]"), None);
+    }
 }
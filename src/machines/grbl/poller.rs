@@ -1,13 +1,14 @@
 //! Status polling task for GRBL-HAL.
 //!
 //! Async task that sends `?` every 200 ms, parses the response with the parser,
-//! updates shared `Arc<Mutex<MachineStatus>>`, and broadcasts the new status.
+//! maps the work position into part space (bed extension), updates shared
+//! `Arc<Mutex<MachineStatus>>`, and broadcasts the new status.
 //! Port I/O runs in `spawn_blocking` so the async runtime is not blocked.
 //!
 //! # Example
 //!
 //! ```ignore
-//! use grbl_rs::machines::grbl::{Port, run_poller, PollerHandle, MachineStatus};
+//! use grbl_rs::machines::grbl::{Port, run_poller, PollerHandle, MachineStatus, MotionConfig};
 //! use std::sync::Arc;
 //! use std::time::Duration;
 //! use tokio::sync::{broadcast, Mutex};
@@ -18,6 +19,7 @@
 //!     port: Arc::new(Mutex::new(port)),
 //!     state: Arc::new(Mutex::new(MachineStatus::idle())),
 //!     tx,
+//!     motion_config: Arc::new(Mutex::new(MotionConfig::default())),
 //! };
 //! tokio::spawn(async move {
 //!     let _ = run_poller(
@@ -30,6 +32,7 @@
 
 #![cfg(feature = "serial")]
 
+use super::motion::{apply_part_position, MotionConfig};
 use super::parser::parse_status;
 use super::port::{Port, PortError};
 use super::state::MachineStatus;
@@ -52,6 +55,8 @@ pub struct PollerHandle {
     pub state: Arc<Mutex<MachineStatus>>,
     /// Broadcast sender for status updates (e.g. UI, session logger).
     pub tx: broadcast::Sender<MachineStatus>,
+    /// Bed extension config used to fill `MachineStatus::part_pos`.
+    pub motion_config: Arc<Mutex<MotionConfig>>,
 }
 
 /// Runs the poll loop. Sends `?` every `interval`, parses response, updates `state`, broadcasts.
@@ -81,7 +86,8 @@ pub async fn run_poller(
 
         let now = Instant::now();
         match parse_status(line.trim(), now) {
-            Ok(status) => {
+            Ok(mut status) => {
+                apply_part_position(&mut status, &*handle.motion_config.lock().await);
                 {
                     let mut state = handle.state.lock().await;
                     *state = status.clone();
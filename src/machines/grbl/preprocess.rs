@@ -0,0 +1,343 @@
+//! G-code preprocessing before streaming.
+//!
+//! Shrinks lines before they reach the controller's RX buffer: strips `( ... )`,
+//! `;` comments and `%` markers, drops whitespace and `N` line numbers, rounds
+//! coordinates to a fixed number of decimals, uppercases words and removes M-codes
+//! GRBL-HAL would reject. Each stage can be turned off. Runs on translated lines,
+//! so rounding never affects the bed-extension split.
+
+use super::gcode::is_supported_mcode;
+use super::motion::TranslatedLine;
+use serde::{Deserialize, Serialize};
+
+/// Which preprocessing stages run. All are on by default.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct PreprocessOptions {
+    /// Remove `( ... )` and `;` comments and `%` program markers.
+    pub strip_comments: bool,
+    /// Remove all whitespace (GRBL ignores it).
+    pub compress_whitespace: bool,
+    /// Round axis, feed and speed values to this many decimals (trailing zeros dropped).
+    pub round_decimals: Option<usize>,
+    /// Remove `N` line numbers.
+    pub remove_line_numbers: bool,
+    /// Remove M-codes GRBL-HAL does not support, with a warning. A line holding
+    /// only the M-code and its parameter words (`P`, `S`, ...) is dropped; on a
+    /// line with other commands only the M word goes. If parameter words could
+    /// belong to either, the line is kept as it is rather than guessed at.
+    pub drop_unsupported_mcodes: bool,
+    /// Uppercase word letters (comments are left alone).
+    pub uppercase: bool,
+}
+
+impl Default for PreprocessOptions {
+    fn default() -> Self {
+        Self {
+            strip_comments: true,
+            compress_whitespace: true,
+            round_decimals: Some(4),
+            remove_line_numbers: true,
+            drop_unsupported_mcodes: true,
+            uppercase: true,
+        }
+    }
+}
+
+/// Something preprocessing removed that the operator should know about.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct PreprocessWarning {
+    /// 1-based line number in the original program.
+    pub line: usize,
+    pub message: String,
+}
+
+/// Letters whose values are rounded (axes, arc offsets, radius, feed, speed).
+const ROUNDED: &str = "XYZABCUVWIJKRFS";
+
+/// Letters M-codes take as parameters.
+const MCODE_PARAMS: &str = "DEHLPQS";
+
+enum Token<'a> {
+    Word { letter: char, number: &'a str },
+    Comment(&'a str),
+    Space(&'a str),
+    Other(&'a str),
+}
+
+fn tokenize(line: &str) -> Vec<Token<'_>> {
+    let bytes = line.as_bytes();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < bytes.len() {
+        let c = bytes[i] as char;
+        let start = i;
+        if c == '(' {
+            i = line[i..].find(')').map_or(bytes.len(), |j| i + j + 1);
+            tokens.push(Token::Comment(&line[start..i]));
+        } else if c == ';' {
+            tokens.push(Token::Comment(&line[i..]));
+            i = bytes.len();
+        } else if c.is_ascii_whitespace() {
+            while i < bytes.len() && (bytes[i] as char).is_ascii_whitespace() {
+                i += 1;
+            }
+            tokens.push(Token::Space(&line[start..i]));
+        } else if c.is_ascii_alphabetic() {
+            let mut j = i + 1;
+            while j < bytes.len() && bytes[j] == b' ' {
+                j += 1;
+            }
+            let num_start = j;
+            while j < bytes.len() && (bytes[j].is_ascii_digit() || b"+-.".contains(&bytes[j])) {
+                j += 1;
+            }
+            if j > num_start {
+                tokens.push(Token::Word {
+                    letter: c,
+                    number: &line[num_start..j],
+                });
+                i = j;
+            } else {
+                tokens.push(Token::Other(&line[i..i + 1]));
+                i += 1;
+            }
+        } else {
+            let len = line[i..].chars().next().map_or(1, char::len_utf8);
+            tokens.push(Token::Other(&line[i..i + len]));
+            i += len;
+        }
+    }
+    tokens
+}
+
+/// Round to `decimals` and drop trailing zeros (`10.00000` -> `10`).
+fn round_number(number: &str, decimals: usize) -> Option<String> {
+    let v: f64 = number.parse().ok()?;
+    let s = format!("{:.*}", decimals, v);
+    let s = if s.contains('.') {
+        s.trim_end_matches('0').trim_end_matches('.')
+    } else {
+        &s
+    };
+    Some(if s == "-0" {
+        "0".to_string()
+    } else {
+        s.to_string()
+    })
+}
+
+/// Preprocess one line. Returns the new text (empty if nothing is left to send)
+/// and warnings about unsupported M-codes that were removed or kept.
+pub fn preprocess_line(line: &str, options: &PreprocessOptions) -> (String, Vec<String>) {
+    let trimmed = line.trim();
+    // System commands (`$H`, `$J=...`) are sent as they are.
+    if trimmed.starts_with('$') {
+        return (trimmed.to_string(), Vec::new());
+    }
+    if options.strip_comments && trimmed == "%" {
+        return (String::new(), Vec::new());
+    }
+    let mut tokens = tokenize(trimmed);
+    let mut warnings = Vec::new();
+    if options.drop_unsupported_mcodes {
+        let unsupported = |t: &Token| match t {
+            Token::Word { letter, number } if letter.eq_ignore_ascii_case(&'m') => !number
+                .parse::<f64>()
+                .is_ok_and(|v| is_supported_mcode((v * 10.0).round() as i32)),
+            _ => false,
+        };
+        let words = |params: bool| {
+            tokens.iter().any(|t| match t {
+                Token::Word { letter, .. } => {
+                    let upper = letter.to_ascii_uppercase();
+                    upper != 'N' && !unsupported(t) && MCODE_PARAMS.contains(upper) == params
+                }
+                _ => false,
+            })
+        };
+        let dropped: Vec<String> = tokens
+            .iter()
+            .filter(|t| unsupported(t))
+            .filter_map(|t| match t {
+                Token::Word { number, .. } => Some(format!("M{}", number)),
+                _ => None,
+            })
+            .collect();
+        if !dropped.is_empty() {
+            let (others, params) = (words(false), words(true));
+            if !others {
+                let warnings = dropped
+                    .iter()
+                    .map(|m| format!("dropped line with unsupported {}", m))
+                    .collect();
+                return (String::new(), warnings);
+            }
+            if params {
+                // Removing the M-code alone could leave its parameters to another
+                // command, and dropping the line would lose that command (maybe a
+                // motion). Send it as it is; the controller rejects it.
+                warnings.extend(dropped.iter().map(|m| {
+                    format!(
+                        "kept line with unsupported {}: its parameters cannot be told \
+                         apart from the other commands'",
+                        m
+                    )
+                }));
+            } else {
+                tokens.retain(|t| !unsupported(t));
+                warnings.extend(dropped.iter().map(|m| format!("removed unsupported {}", m)));
+            }
+        }
+    }
+    let mut out = String::with_capacity(trimmed.len());
+    for token in tokens {
+        match token {
+            Token::Word { letter, number } => {
+                let upper = letter.to_ascii_uppercase();
+                if options.remove_line_numbers && upper == 'N' {
+                    continue;
+                }
+                out.push(if options.uppercase { upper } else { letter });
+                match options.round_decimals {
+                    Some(n) if ROUNDED.contains(upper) => match round_number(number, n) {
+                        Some(r) => out.push_str(&r),
+                        None => out.push_str(number),
+                    },
+                    _ => out.push_str(number),
+                }
+            }
+            Token::Comment(text) => {
+                if !options.strip_comments {
+                    out.push_str(text);
+                }
+            }
+            Token::Space(text) => {
+                if !options.compress_whitespace {
+                    out.push_str(text);
+                }
+            }
+            Token::Other(text) => out.push_str(text),
+        }
+    }
+    (out.trim().to_string(), warnings)
+}
+
+/// Preprocess translated lines, dropping lines left empty. Warnings refer to the
+/// original program's line numbers.
+pub fn preprocess_lines(
+    lines: Vec<TranslatedLine>,
+    options: &PreprocessOptions,
+) -> (Vec<TranslatedLine>, Vec<PreprocessWarning>) {
+    let mut out = Vec::with_capacity(lines.len());
+    let mut warnings = Vec::new();
+    for line in lines {
+        let (text, messages) = preprocess_line(&line.text, options);
+        for message in messages {
+            warnings.push(PreprocessWarning {
+                line: line.source_line,
+                message: format!("{}: {}", message, line.text),
+            });
+        }
+        if !text.is_empty() {
+            out.push(TranslatedLine {
+                source_line: line.source_line,
+                text,
+            });
+        }
+    }
+    (out, warnings)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(line: &str) -> String {
+        preprocess_line(line, &PreprocessOptions::default()).0
+    }
+
+    #[test]
+    fn test_default_pipeline() {
+        assert_eq!(
+            run("n10 g1 x10.123456 y-0.00001 f1500.000 (cut) ; rest"),
+            "G1X10.1235Y0F1500"
+        );
+        assert_eq!(run("%"), "");
+        assert_eq!(run("(only a comment)"), "");
+        assert_eq!(run("  $J=G91 X10 F500  "), "$J=G91 X10 F500");
+        // G/M/P values are never rounded.
+        assert_eq!(run("G38.2 Z-5.123456 F50"), "G38.2Z-5.1235F50");
+        assert_eq!(run("G4 P0.123456"), "G4P0.123456");
+    }
+
+    #[test]
+    fn test_stages_toggle() {
+        let none = PreprocessOptions {
+            strip_comments: false,
+            compress_whitespace: false,
+            round_decimals: None,
+            remove_line_numbers: false,
+            drop_unsupported_mcodes: false,
+            uppercase: false,
+        };
+        let line = "N5 g1 x1.234567 (c) M98  ";
+        assert_eq!(preprocess_line(line, &none).0, "N5 g1 x1.234567 (c) M98");
+        let round_only = PreprocessOptions {
+            round_decimals: Some(2),
+            ..none
+        };
+        assert_eq!(preprocess_line(line, &round_only).0, "N5 g1 x1.23 (c) M98");
+    }
+
+    #[test]
+    fn test_unsupported_mcodes_dropped_with_warning() {
+        let lines = vec![
+            TranslatedLine {
+                source_line: 3,
+                text: "M98 P100".to_string(),
+            },
+            TranslatedLine {
+                source_line: 4,
+                text: "M3 S1000 M140".to_string(),
+            },
+        ];
+        let (out, warnings) = preprocess_lines(lines, &PreprocessOptions::default());
+        // S may be M140's bed temperature or M3's speed, so line 4 is sent as is.
+        assert_eq!(out.len(), 1);
+        assert_eq!(out[0].text, "M3S1000M140");
+        assert_eq!(
+            warnings,
+            vec![
+                PreprocessWarning {
+                    line: 3,
+                    message: "dropped line with unsupported M98: M98 P100".to_string()
+                },
+                PreprocessWarning {
+                    line: 4,
+                    message: "kept line with unsupported M140: its parameters cannot be told \
+                              apart from the other commands': M3 S1000 M140"
+                        .to_string()
+                },
+            ]
+        );
+        let (out, _) = preprocess_lines(
+            vec![TranslatedLine {
+                source_line: 1,
+                text: "m3 s1000".to_string(),
+            }],
+            &PreprocessOptions::default(),
+        );
+        assert_eq!(out[0].text, "M3S1000");
+    }
+
+    #[test]
+    fn test_unsupported_mcode_on_motion_line() {
+        let (text, warnings) = preprocess_line("N7 G1 X10 Y5 M100", &PreprocessOptions::default());
+        assert_eq!(text, "G1X10Y5");
+        assert_eq!(warnings, vec!["removed unsupported M100".to_string()]);
+        // A parameter word that the dwell could own keeps the whole line.
+        let (text, warnings) = preprocess_line("G4 P1 M100", &PreprocessOptions::default());
+        assert_eq!(text, "G4P1M100");
+        assert_eq!(warnings.len(), 1);
+    }
+}
@@ -0,0 +1,200 @@
+//! Runs [`ProbeRoutine`] steps over the port.
+//!
+//! Moves and touches are sent one line at a time; each touch waits for the
+//! `[PRB:...]` report of its fast and slow `G38.2`. Touches that return to their
+//! start read the start from a settled status report, then go back with `G53`.
+//! Routines send their lines in mm (`G21`); with `set_wcs` the result is
+//! written to the active WCS from the position the machine stopped at. They end
+//! in `G90` with the units read from `$G` before the routine. Height maps probe
+//! each grid point once with `G38.3` and keep going past misses.
+
+#![cfg(feature = "serial")]
+
+use super::commands::GrblCommand;
+use super::gcode::Units;
+use super::heightmap::{HeightMap, HeightMapOptions};
+use super::parser::{parse_probe_report, parse_units, ProbeReport};
+use super::port::Port;
+use super::probing::{
+    touch_lines, ProbeDirection, ProbeResult, ProbeRoutine, ProbeSettings, ProbeStep,
+};
+use super::state::{MachineStatus, Position};
+use super::streamer::{probe_contact, send_all, settled_status, StreamerError};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::Mutex;
+use tracing::{info, warn};
+
+fn xyz(p: &Position) -> [f64; 3] {
+    [p.x, p.y, p.z]
+}
+
+/// Timeout for a probe move of `distance_mm` at `feed_mm_min`.
+fn probe_timeout(distance_mm: f64, feed_mm_min: f64, timeout: Duration) -> Duration {
+    let secs = distance_mm.abs() / feed_mm_min.max(1.0) * 60.0 + 10.0;
+    timeout.max(Duration::from_secs_f64(secs))
+}
+
+/// Active length units, read with `$G`.
+pub(super) async fn active_units(
+    port: &Arc<Mutex<Port>>,
+    timeout: Duration,
+) -> Result<Units, StreamerError> {
+    let line = GrblCommand::ParserState.to_string();
+    let feedback = send_all(port, std::slice::from_ref(&line), timeout).await?;
+    feedback
+        .iter()
+        .find_map(|l| parse_units(l))
+        .ok_or(StreamerError::Rejected {
+            line,
+            code: "no [GC:] in reply".to_string(),
+        })
+}
+
+/// Line that leaves the controller in absolute mode with `units` after a routine.
+pub(super) fn restore_line(units: Units) -> String {
+    format!("G90 {}", units.word())
+}
+
+/// Run a routine and compute its result; sets the WCS zero if requested.
+pub(super) async fn run_probe_routine(
+    port: &Arc<Mutex<Port>>,
+    state: &Arc<Mutex<MachineStatus>>,
+    routine: &ProbeRoutine,
+    settings: &ProbeSettings,
+    timeout: Duration,
+) -> Result<ProbeResult, StreamerError> {
+    let units = active_units(port, timeout).await?;
+    let outcome = async {
+        let mut contacts = Vec::new();
+        run_steps(port, state, routine, settings, timeout, &mut contacts).await?;
+        let result = routine
+            .result(settings, &contacts)
+            .ok_or(StreamerError::Rejected {
+                line: format!("{:?}", routine),
+                code: "unexpected number of contacts".to_string(),
+            })?;
+        info!("probe: {:?} -> {:?}", routine, result.position);
+        if settings.set_wcs {
+            let current = settled_status(state, Instant::now()).await;
+            if let Some(line) = result.wcs_zero_line(xyz(&current.machine_pos)) {
+                send_all(port, &[line], timeout).await?;
+            }
+        }
+        Ok::<_, StreamerError>(result)
+    }
+    .await;
+    // Restore absolute mode and units even when a touch failed; report the
+    // touch's error first (after an alarm, the restore is refused too).
+    let restore = send_all(port, &[restore_line(units)], timeout).await;
+    let result = outcome?;
+    restore?;
+    Ok(result)
+}
+
+async fn run_steps(
+    port: &Arc<Mutex<Port>>,
+    state: &Arc<Mutex<MachineStatus>>,
+    routine: &ProbeRoutine,
+    settings: &ProbeSettings,
+    timeout: Duration,
+    contacts: &mut Vec<[f64; 3]>,
+) -> Result<(), StreamerError> {
+    for step in routine.steps(settings) {
+        match step {
+            ProbeStep::Move(line) => {
+                send_all(port, &[line], timeout).await?;
+            }
+            ProbeStep::Touch {
+                direction,
+                return_to_start,
+            } => {
+                let contact = touch(port, state, direction, return_to_start, settings, timeout);
+                contacts.push(contact.await?);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Fast and slow touch in `direction`; returns the slow contact (machine
+/// coordinates). Leaves the controller in `G91`.
+pub(super) async fn touch(
+    port: &Arc<Mutex<Port>>,
+    state: &Arc<Mutex<MachineStatus>>,
+    direction: ProbeDirection,
+    return_to_start: bool,
+    settings: &ProbeSettings,
+    timeout: Duration,
+) -> Result<[f64; 3], StreamerError> {
+    let start = if return_to_start {
+        let status = settled_status(state, Instant::now()).await;
+        Some(xyz(&status.machine_pos))
+    } else {
+        None
+    };
+    let [seek, back, latch, back_again] = touch_lines(direction, settings);
+    let seek_timeout = probe_timeout(settings.max_travel_mm, settings.seek_feed_mm_min, timeout);
+    probe_contact(port, &seek, seek_timeout).await?;
+    send_all(port, &[back], timeout).await?;
+    let latch_timeout = probe_timeout(
+        settings.retract_mm * 2.0,
+        settings.latch_feed_mm_min,
+        timeout,
+    );
+    let contact = xyz(&probe_contact(port, &latch, latch_timeout).await?);
+    send_all(port, &[back_again], timeout).await?;
+    if let Some(start) = start {
+        let i = direction.axis();
+        let line = format!("G21 G53 G0 {}{:.4}", ['X', 'Y', 'Z'][i], start[i]);
+        send_all(port, &[line], timeout).await?;
+    }
+    Ok(contact)
+}
+
+/// Probe the points of `map` (built from `options`) with `G38.3`. `on_point`
+/// gets each point's grid index and probe report (machine coordinates) as it is
+/// measured. Stops only on port errors or rejected lines, not on missed points.
+pub(super) async fn run_height_map(
+    port: &Arc<Mutex<Port>>,
+    state: &Arc<Mutex<MachineStatus>>,
+    options: &HeightMapOptions,
+    mut map: HeightMap,
+    timeout: Duration,
+    mut on_point: impl FnMut(usize, usize, &ProbeReport),
+) -> Result<HeightMap, StreamerError> {
+    let units = active_units(port, timeout).await?;
+    let start = settled_status(state, Instant::now()).await;
+    // Work Z = machine Z - offset; the offset does not change during the run.
+    let z_offset = start.machine_pos.z - start.work_pos.z;
+    let travel = options.clearance_z_mm - options.probe_depth_mm;
+    let probe_timeout = probe_timeout(travel, options.probe_feed_mm_min, timeout);
+    for (col, row) in map.probe_order() {
+        let [x, y] = map.point(col, row);
+        let [clear, over, probe] = options.point_lines(x, y);
+        send_all(port, &[clear, over], timeout).await?;
+        let feedback = send_all(port, &[probe], probe_timeout).await?;
+        let report = feedback.iter().find_map(|l| parse_probe_report(l).ok());
+        match &report {
+            Some(r) if r.success => map.set(col, row, Some(r.position.z - z_offset)),
+            _ => warn!("height map: no contact at X{:.3} Y{:.3}", x, y),
+        }
+        if let Some(r) = &report {
+            on_point(col, row, r);
+        }
+    }
+    send_all(
+        port,
+        &[options.retract_line(), restore_line(units)],
+        timeout,
+    )
+    .await?;
+    info!(
+        "height map: {}x{} points, {} failed, range {:?}",
+        map.cols,
+        map.rows,
+        map.failed(),
+        map.range()
+    );
+    Ok(map)
+}
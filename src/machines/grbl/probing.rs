@@ -0,0 +1,460 @@
+//! Probing routines: edge, XYZ outside corner, bore and boss center.
+//!
+//! Each routine is a fixed list of [`ProbeStep`]s: relative rapid moves and
+//! touches. A touch seeks fast with `G38.2`, backs off, re-touches slowly and
+//! backs off again; the slow contact is the measurement. The steps never depend
+//! on earlier contacts, so a routine is planned up front and the result is
+//! computed from the contacts afterwards. All positions are machine coordinates.
+//!
+//! Bore and boss centers come from one chord along X and one along Y through
+//! the start point: chord midpoints lie on the center lines wherever the start
+//! point is, and the diameter follows from the X chord and its offset from the
+//! center in Y. The start point only needs to be roughly centered.
+
+use serde::{Deserialize, Serialize};
+
+/// Direction of a touch.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ProbeDirection {
+    XPlus,
+    XMinus,
+    YPlus,
+    YMinus,
+    ZMinus,
+}
+
+impl ProbeDirection {
+    /// Axis index (X = 0, Y = 1, Z = 2).
+    pub fn axis(self) -> usize {
+        match self {
+            ProbeDirection::XPlus | ProbeDirection::XMinus => 0,
+            ProbeDirection::YPlus | ProbeDirection::YMinus => 1,
+            ProbeDirection::ZMinus => 2,
+        }
+    }
+
+    pub fn sign(self) -> f64 {
+        match self {
+            ProbeDirection::XPlus | ProbeDirection::YPlus => 1.0,
+            _ => -1.0,
+        }
+    }
+
+    fn letter(self) -> char {
+        ['X', 'Y', 'Z'][self.axis()]
+    }
+}
+
+/// Feeds, distances and probe geometry shared by all routines.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ProbeSettings {
+    /// Fast first touch (mm/min).
+    pub seek_feed_mm_min: f64,
+    /// Slow measuring touch (mm/min).
+    pub latch_feed_mm_min: f64,
+    /// Maximum travel of the fast touch (mm).
+    pub max_travel_mm: f64,
+    /// Back-off after each touch (mm).
+    pub retract_mm: f64,
+    /// Probe tip or tool diameter (mm), compensated on X/Y touches.
+    pub tip_diameter_mm: f64,
+    /// Touch plate thickness on top of the stock (mm); 0 when touching the stock.
+    pub plate_thickness_mm: f64,
+    /// Write the result into the active WCS (`G10 L20 P0`).
+    pub set_wcs: bool,
+}
+
+impl Default for ProbeSettings {
+    fn default() -> Self {
+        Self {
+            seek_feed_mm_min: 200.0,
+            latch_feed_mm_min: 25.0,
+            max_travel_mm: 20.0,
+            retract_mm: 2.0,
+            tip_diameter_mm: 0.0,
+            plate_thickness_mm: 0.0,
+            set_wcs: true,
+        }
+    }
+}
+
+/// Stock corner, seen from above with X to the right and Y away from the operator.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Corner {
+    #[default]
+    FrontLeft,
+    FrontRight,
+    BackLeft,
+    BackRight,
+}
+
+impl Corner {
+    /// Outward direction of the corner's X and Y faces (-1 or 1).
+    fn outward(self) -> [f64; 2] {
+        match self {
+            Corner::FrontLeft => [-1.0, -1.0],
+            Corner::FrontRight => [1.0, -1.0],
+            Corner::BackLeft => [-1.0, 1.0],
+            Corner::BackRight => [1.0, 1.0],
+        }
+    }
+}
+
+/// A probing routine and its geometry.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum ProbeRoutine {
+    /// Touch one edge. Start beside it, below the top, facing `direction`.
+    Edge { direction: ProbeDirection },
+    /// Touch the top (Z), then the X and Y faces of an outside corner. Start
+    /// above the stock (or plate), about `xy_clearance_mm / 2` inside both edges.
+    Corner {
+        corner: Corner,
+        /// Horizontal move from the start to beside each face (mm).
+        xy_clearance_mm: f64,
+        /// Depth below the top at which the faces are touched (mm).
+        z_depth_mm: f64,
+        /// Thickness of the plate lip against the side faces (mm); 0 when touching the stock.
+        plate_side_mm: f64,
+    },
+    /// Touch the wall of a bore in four directions. Start inside, below the top.
+    Bore,
+    /// Touch a boss from four sides. Start above its center.
+    Boss {
+        /// Approximate boss diameter (mm).
+        diameter_mm: f64,
+        /// Extra horizontal clearance beyond the boss (mm).
+        clearance_mm: f64,
+        /// Drop from the start height to the touch height (mm).
+        z_drop_mm: f64,
+    },
+}
+
+/// One step of a routine.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum ProbeStep {
+    /// Line to send as is (relative `G21 G91 G0` moves).
+    Move(String),
+    /// Fast and slow touch. With `return_to_start` the probe goes back to where
+    /// the touch began; otherwise it stays backed off from the contact.
+    Touch {
+        direction: ProbeDirection,
+        return_to_start: bool,
+    },
+}
+
+/// Lines for one touch: fast seek, back off, slow latch, back off. The first and
+/// third answer with a `[PRB:...]` report; the latch contact is the measurement.
+pub fn touch_lines(direction: ProbeDirection, settings: &ProbeSettings) -> [String; 4] {
+    let (a, s, r) = (direction.letter(), direction.sign(), settings.retract_mm);
+    [
+        format!(
+            "G21 G91 G38.2 {}{:.4} F{:.1}",
+            a,
+            s * settings.max_travel_mm,
+            settings.seek_feed_mm_min
+        ),
+        format!("G21 G91 G0 {}{:.4}", a, -s * r),
+        format!(
+            "G21 G91 G38.2 {}{:.4} F{:.1}",
+            a,
+            s * r * 2.0,
+            settings.latch_feed_mm_min
+        ),
+        format!("G21 G91 G0 {}{:.4}", a, -s * r),
+    ]
+}
+
+fn rapid(axis: char, mm: f64) -> ProbeStep {
+    ProbeStep::Move(format!("G21 G91 G0 {}{:.4}", axis, mm))
+}
+
+fn touch(direction: ProbeDirection, return_to_start: bool) -> ProbeStep {
+    ProbeStep::Touch {
+        direction,
+        return_to_start,
+    }
+}
+
+/// Result of a routine, in machine coordinates.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct ProbeResult {
+    /// Position found per axis (edge, corner, top or center); `None` for axes
+    /// the routine does not measure.
+    pub position: [Option<f64>; 3],
+    /// Measured bore or boss diameter (mm).
+    pub diameter_mm: Option<f64>,
+    /// Slow-touch contacts in routine order.
+    pub contacts: Vec<[f64; 3]>,
+}
+
+impl ProbeResult {
+    /// `G10 L20 P0` line that makes [`ProbeResult::position`] the active WCS zero,
+    /// given the machine position the line is sent at. `None` if nothing was found.
+    pub fn wcs_zero_line(&self, current: [f64; 3]) -> Option<String> {
+        let words: String = (0..3)
+            .filter_map(|i| {
+                self.position[i].map(|p| format!(" {}{:.4}", ['X', 'Y', 'Z'][i], current[i] - p))
+            })
+            .collect();
+        (!words.is_empty()).then(|| format!("G21 G10 L20 P0{}", words))
+    }
+}
+
+impl ProbeRoutine {
+    /// Steps to run, in order.
+    pub fn steps(&self, settings: &ProbeSettings) -> Vec<ProbeStep> {
+        match *self {
+            ProbeRoutine::Edge { direction } => vec![touch(direction, true)],
+            ProbeRoutine::Corner {
+                corner,
+                xy_clearance_mm,
+                z_depth_mm,
+                ..
+            } => {
+                let [ox, oy] = corner.outward();
+                // After the Z touch the tip is `retract` above the plate.
+                let drop = settings.retract_mm + settings.plate_thickness_mm + z_depth_mm;
+                let x_in = if ox < 0.0 {
+                    ProbeDirection::XPlus
+                } else {
+                    ProbeDirection::XMinus
+                };
+                let y_in = if oy < 0.0 {
+                    ProbeDirection::YPlus
+                } else {
+                    ProbeDirection::YMinus
+                };
+                vec![
+                    touch(ProbeDirection::ZMinus, false),
+                    rapid('X', ox * xy_clearance_mm),
+                    rapid('Z', -drop),
+                    touch(x_in, true),
+                    rapid('Z', drop),
+                    rapid('X', -ox * xy_clearance_mm),
+                    rapid('Y', oy * xy_clearance_mm),
+                    rapid('Z', -drop),
+                    touch(y_in, true),
+                    rapid('Z', drop),
+                    rapid('Y', -oy * xy_clearance_mm),
+                ]
+            }
+            ProbeRoutine::Bore => vec![
+                touch(ProbeDirection::XPlus, true),
+                touch(ProbeDirection::XMinus, true),
+                touch(ProbeDirection::YPlus, true),
+                touch(ProbeDirection::YMinus, true),
+            ],
+            ProbeRoutine::Boss {
+                diameter_mm,
+                clearance_mm,
+                z_drop_mm,
+            } => {
+                let out = diameter_mm / 2.0 + clearance_mm + settings.tip_diameter_mm / 2.0;
+                [
+                    ProbeDirection::XPlus,
+                    ProbeDirection::XMinus,
+                    ProbeDirection::YPlus,
+                    ProbeDirection::YMinus,
+                ]
+                .into_iter()
+                .flat_map(|d| {
+                    let a = d.letter();
+                    // Approach from the far side, touching toward the center.
+                    let away = -d.sign() * out;
+                    [
+                        rapid(a, away),
+                        rapid('Z', -z_drop_mm),
+                        touch(d, true),
+                        rapid('Z', z_drop_mm),
+                        rapid(a, -away),
+                    ]
+                })
+                .collect()
+            }
+        }
+    }
+
+    /// Compute the result from the slow-touch contacts, one per touch step.
+    /// `None` if the number of contacts does not match the routine.
+    pub fn result(&self, settings: &ProbeSettings, contacts: &[[f64; 3]]) -> Option<ProbeResult> {
+        let r = settings.tip_diameter_mm / 2.0;
+        let mut out = ProbeResult {
+            contacts: contacts.to_vec(),
+            ..ProbeResult::default()
+        };
+        match (self, contacts) {
+            (ProbeRoutine::Edge { direction }, [c]) => {
+                let i = direction.axis();
+                out.position[i] = Some(if i == 2 {
+                    c[2] - settings.plate_thickness_mm
+                } else {
+                    c[i] + direction.sign() * r
+                });
+            }
+            (ProbeRoutine::Corner { plate_side_mm, .. }, [z, x, y]) => {
+                let [ox, oy] = self.corner_outward();
+                out.position = [
+                    Some(x[0] - ox * (r + plate_side_mm)),
+                    Some(y[1] - oy * (r + plate_side_mm)),
+                    Some(z[2] - settings.plate_thickness_mm),
+                ];
+            }
+            (ProbeRoutine::Bore, [xp, xm, yp, ym]) => {
+                let cx = (xp[0] + xm[0]) / 2.0;
+                let cy = (yp[1] + ym[1]) / 2.0;
+                // Tip centers lie on a circle `r` inside the wall.
+                let half = (xp[0] - xm[0]) / 2.0;
+                out.position = [Some(cx), Some(cy), None];
+                out.diameter_mm = Some(2.0 * (half.hypot(xp[1] - cy) + r));
+            }
+            (ProbeRoutine::Boss { .. }, [xp, xm, yp, ym]) => {
+                // XPlus touches the boss's minus side.
+                let cx = (xp[0] + xm[0]) / 2.0;
+                let cy = (yp[1] + ym[1]) / 2.0;
+                let half = (xm[0] - xp[0]) / 2.0;
+                out.position = [Some(cx), Some(cy), None];
+                out.diameter_mm = Some(2.0 * (half.hypot(xp[1] - cy) - r));
+            }
+            _ => return None,
+        }
+        Some(out)
+    }
+
+    fn corner_outward(&self) -> [f64; 2] {
+        match self {
+            ProbeRoutine::Corner { corner, .. } => corner.outward(),
+            _ => [0.0, 0.0],
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn settings() -> ProbeSettings {
+        ProbeSettings {
+            tip_diameter_mm: 2.0,
+            plate_thickness_mm: 10.0,
+            ..ProbeSettings::default()
+        }
+    }
+
+    #[test]
+    fn test_touch_lines() {
+        assert_eq!(
+            touch_lines(ProbeDirection::XMinus, &ProbeSettings::default()),
+            [
+                "G21 G91 G38.2 X-20.0000 F200.0",
+                "G21 G91 G0 X2.0000",
+                "G21 G91 G38.2 X-4.0000 F25.0",
+                "G21 G91 G0 X2.0000",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_edge() {
+        let s = settings();
+        let edge = ProbeRoutine::Edge {
+            direction: ProbeDirection::YMinus,
+        };
+        let r = edge.result(&s, &[[0.0, 50.0, -10.0]]).unwrap();
+        assert_eq!(r.position, [None, Some(49.0), None]);
+        assert_eq!(
+            r.wcs_zero_line([0.0, 52.0, -5.0]).unwrap(),
+            "G21 G10 L20 P0 Y3.0000"
+        );
+        let z = ProbeRoutine::Edge {
+            direction: ProbeDirection::ZMinus,
+        };
+        let r = z.result(&s, &[[0.0, 0.0, -20.0]]).unwrap();
+        assert_eq!(r.position[2], Some(-30.0));
+        assert!(z.result(&s, &[]).is_none());
+    }
+
+    #[test]
+    fn test_corner() {
+        let s = settings();
+        let corner = ProbeRoutine::Corner {
+            corner: Corner::BackRight,
+            xy_clearance_mm: 15.0,
+            z_depth_mm: 5.0,
+            plate_side_mm: 0.0,
+        };
+        let steps = corner.steps(&s);
+        assert_eq!(steps.len(), 11);
+        assert_eq!(steps[1], ProbeStep::Move("G21 G91 G0 X15.0000".to_string()));
+        assert_eq!(
+            steps[2],
+            ProbeStep::Move("G21 G91 G0 Z-17.0000".to_string())
+        );
+        assert_eq!(
+            steps[3],
+            ProbeStep::Touch {
+                direction: ProbeDirection::XMinus,
+                return_to_start: true
+            }
+        );
+        let r = corner
+            .result(
+                &s,
+                &[
+                    [90.0, 90.0, -20.0],
+                    [101.0, 90.0, -37.0],
+                    [90.0, 101.0, -37.0],
+                ],
+            )
+            .unwrap();
+        assert_eq!(r.position, [Some(100.0), Some(100.0), Some(-30.0)]);
+    }
+
+    #[test]
+    fn test_bore_off_center_start() {
+        let s = ProbeSettings {
+            tip_diameter_mm: 2.0,
+            ..ProbeSettings::default()
+        };
+        // Bore of diameter 20 at (10, 20); start at y = 26, so the X chord is short.
+        let (cx, cy, wall) = (10.0, 20.0, 10.0 - 1.0);
+        let hx = (wall * wall - 36.0_f64).sqrt();
+        let contacts = [
+            [cx + hx, 26.0, 0.0],
+            [cx - hx, 26.0, 0.0],
+            [12.0, cy + (wall * wall - 4.0_f64).sqrt(), 0.0],
+            [12.0, cy - (wall * wall - 4.0_f64).sqrt(), 0.0],
+        ];
+        let r = ProbeRoutine::Bore.result(&s, &contacts).unwrap();
+        assert_eq!(r.position, [Some(10.0), Some(20.0), None]);
+        assert!((r.diameter_mm.unwrap() - 20.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_boss() {
+        let s = settings();
+        let boss = ProbeRoutine::Boss {
+            diameter_mm: 30.0,
+            clearance_mm: 5.0,
+            z_drop_mm: 8.0,
+        };
+        let steps = boss.steps(&s);
+        assert_eq!(steps.len(), 20);
+        assert_eq!(
+            steps[0],
+            ProbeStep::Move("G21 G91 G0 X-21.0000".to_string())
+        );
+        let r = boss
+            .result(
+                &s,
+                &[
+                    [-16.0, 0.0, 0.0],
+                    [16.0, 0.0, 0.0],
+                    [0.0, -16.0, 0.0],
+                    [0.0, 16.0, 0.0],
+                ],
+            )
+            .unwrap();
+        assert_eq!(r.position, [Some(0.0), Some(0.0), None]);
+        assert_eq!(r.diameter_mm, Some(30.0));
+    }
+}
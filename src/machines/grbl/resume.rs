@@ -0,0 +1,361 @@
+//! Start a job from an arbitrary line.
+//!
+//! Replays the program up to the chosen line through the G-code [`Interpreter`]
+//! and generates a preamble that restores the modal state (units, plane, WCS,
+//! tool, spindle, coolant, feed, distance mode), retracts to a safe Z, rapids to
+//! the start XY, and plunges at feed to where the skipped lines left the tool.
+//! The preamble is plain part-space G-code; it goes through the bed extension
+//! translator together with the rest of the program.
+
+use super::gcode::{
+    parse_words, DistanceMode, Interpreter, ModalState, MotionMode, Plane, Point, SpindleState,
+    Units,
+};
+use super::motion::{translate_lines, MotionConfig};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+/// Height to retract to before moving to the start XY.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum SafeZ {
+    /// Machine coordinate, sent as `G53 G0 Z..` (mm). Safe regardless of WCS.
+    Machine(f64),
+    /// Work coordinate in the program's frame (mm).
+    Work(f64),
+}
+
+/// Options for [`plan_start_from`].
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct StartFromOptions {
+    pub safe_z: SafeZ,
+    /// Plunge feed in mm/min; `None` uses the program's feed at the start line.
+    pub plunge_feed_mm_min: Option<f64>,
+    /// Rapid the bed axis back to 0 before positioning, so the translator's split
+    /// starts from a known bed position. `None` retracts it only if the program
+    /// (after bed extension translation) moves the bed axis.
+    pub retract_bed: Option<bool>,
+}
+
+impl Default for StartFromOptions {
+    fn default() -> Self {
+        Self {
+            safe_z: SafeZ::Machine(-1.0),
+            plunge_feed_mm_min: None,
+            retract_bed: None,
+        }
+    }
+}
+
+/// Errors from planning a start-from-line run.
+#[derive(Debug, Error, PartialEq)]
+pub enum StartFromError {
+    #[error("start line {line} is out of range (program has {count} lines)")]
+    LineOutOfRange { line: usize, count: usize },
+    #[error("no feed rate is set before line {line}; set a plunge feed")]
+    NoPlungeFeed { line: usize },
+}
+
+/// Preamble and state for starting a program at `start_line`.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct StartFromPlan {
+    /// 1-based line the program resumes at.
+    pub start_line: usize,
+    /// Lines to send before the program resumes (for operator review).
+    pub preamble: Vec<String>,
+    /// Modal state accumulated before the start line.
+    pub modal: ModalState,
+    /// Tool position (mm, work coordinates) when the start line begins.
+    pub start_position: Point,
+    /// Things the preamble cannot restore (e.g. G92 offsets).
+    pub warnings: Vec<String>,
+}
+
+impl StartFromPlan {
+    /// The program to stream: preamble followed by the original lines from `start_line` on.
+    pub fn program(&self, lines: &[impl AsRef<str>]) -> Vec<String> {
+        let mut out = self.preamble.clone();
+        out.extend(
+            lines
+                .iter()
+                .skip(self.start_line - 1)
+                .map(|l| l.as_ref().to_string()),
+        );
+        out
+    }
+}
+
+/// Format a mm value in the program's units.
+fn fmt(units: Units, mm: f64) -> String {
+    format!("{:.4}", units.from_mm(mm))
+}
+
+/// True if any line moves the bed axis once translated with `config`.
+fn moves_bed_axis(lines: &[impl AsRef<str>], config: &MotionConfig) -> bool {
+    let bed = config.bed_axis.to_ascii_uppercase();
+    translate_lines(lines, config)
+        .iter()
+        .any(|l| parse_words(l).is_ok_and(|words| words.iter().any(|w| w.letter == bed)))
+}
+
+/// Plan a run that starts at `start_line` (1-based).
+pub fn plan_start_from(
+    lines: &[impl AsRef<str>],
+    start_line: usize,
+    options: &StartFromOptions,
+    config: &MotionConfig,
+) -> Result<StartFromPlan, StartFromError> {
+    if start_line == 0 || start_line > lines.len() {
+        return Err(StartFromError::LineOutOfRange {
+            line: start_line,
+            count: lines.len(),
+        });
+    }
+    let mut interp = Interpreter::new();
+    for line in lines.iter().take(start_line - 1) {
+        // Lines the controller would reject do not change its state either.
+        let _ = interp.execute(line.as_ref());
+    }
+    let modal = interp.modal().clone();
+    let g92 = interp.g92_offset();
+    let pos = interp.position();
+    let program = Point::new(pos.x - g92.x, pos.y - g92.y, pos.z - g92.z);
+    let u = modal.units;
+    let plunge = options.plunge_feed_mm_min.unwrap_or(modal.feed_mm_min);
+    if plunge <= 0.0 {
+        return Err(StartFromError::NoPlungeFeed { line: start_line });
+    }
+    let mut warnings = Vec::new();
+    if g92 != Point::default() {
+        warnings.push(
+            "program sets a G92 offset before the start line; it is assumed to still be active"
+                .to_string(),
+        );
+    }
+
+    let mut pre = vec![
+        format!("{} G90", if u == Units::Inch { "G20" } else { "G21" }),
+        match modal.plane {
+            Plane::XY => "G17",
+            Plane::ZX => "G18",
+            Plane::YZ => "G19",
+        }
+        .to_string(),
+    ];
+    if !modal.arc_incremental {
+        pre.push("G90.1".to_string());
+    }
+    pre.push(super::commands::GrblCommand::ActivateWcs(modal.wcs).to_string());
+    match modal.tool_length_offset_mm {
+        Some(z) => pre.push(format!("G43.1 Z{}", fmt(u, z))),
+        None => pre.push("G49".to_string()),
+    }
+    if let Some(t) = modal.tool {
+        pre.push(format!("M61 Q{}", t));
+    }
+    match options.safe_z {
+        SafeZ::Machine(z) => pre.push(format!("G53 G0 Z{}", fmt(u, z))),
+        SafeZ::Work(z) => pre.push(format!("G0 Z{}", fmt(u, z))),
+    }
+    if options
+        .retract_bed
+        .unwrap_or_else(|| moves_bed_axis(lines, config))
+    {
+        pre.push(format!("G0 {}0", config.bed_axis.to_ascii_uppercase()));
+    }
+    match modal.spindle {
+        SpindleState::Off => pre.push("M5".to_string()),
+        SpindleState::Cw => pre.push(format!("M3 S{}", modal.spindle_speed)),
+        SpindleState::Ccw => pre.push(format!("M4 S{}", modal.spindle_speed)),
+    }
+    if modal.coolant.mist {
+        pre.push("M7".to_string());
+    }
+    if modal.coolant.flood {
+        pre.push("M8".to_string());
+    }
+    pre.push(format!("G0 X{} Y{}", fmt(u, program.x), fmt(u, program.y)));
+    pre.push(format!("G1 Z{} F{}", fmt(u, program.z), fmt(u, plunge)));
+    if plunge != modal.feed_mm_min && modal.feed_mm_min > 0.0 {
+        pre.push(format!("F{}", fmt(u, modal.feed_mm_min)));
+    }
+    if modal.distance == DistanceMode::Relative {
+        pre.push("G91".to_string());
+    }
+    match modal.motion {
+        // G0 and G80 are valid without axis words; G1 is already active.
+        MotionMode::Rapid | MotionMode::None => pre.push(modal.motion.word().to_string()),
+        MotionMode::ArcCw | MotionMode::ArcCcw => {
+            // A bare G2/G3 is rejected (error:26), so the start line has to bring
+            // its own motion word.
+            let start = lines[start_line - 1].as_ref();
+            let continues_arc = interp
+                .clone()
+                .execute(start)
+                .is_ok_and(|e| !e.explicit_motion && e.motion.is_some());
+            if continues_arc {
+                warnings.push(format!(
+                    "line {} continues a modal {} arc without its own motion word and would \
+                     run as G1; start from the line that begins the arc",
+                    start_line,
+                    modal.motion.word()
+                ));
+            }
+        }
+        MotionMode::Linear | MotionMode::Probe => {}
+    }
+
+    Ok(StartFromPlan {
+        start_line,
+        preamble: pre,
+        modal,
+        start_position: pos,
+        warnings,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PROGRAM: [&str; 10] = [
+        "G21 G90 G55",
+        "T2 M6",
+        "M3 S12000",
+        "M8",
+        "G0 X10 Y20 Z5",
+        "G1 Z-1 F300",
+        "G1 X50 F900",
+        "G91",
+        "G1 Y10",
+        "G1 Y10",
+    ];
+
+    fn config() -> MotionConfig {
+        MotionConfig::default()
+    }
+
+    #[test]
+    fn test_plan_restores_modal_state() {
+        let options = StartFromOptions {
+            retract_bed: Some(true),
+            ..StartFromOptions::default()
+        };
+        let plan = plan_start_from(&PROGRAM, 10, &options, &config()).unwrap();
+        assert_eq!(plan.start_line, 10);
+        assert_eq!(plan.start_position, Point::new(50.0, 30.0, -1.0));
+        assert_eq!(
+            plan.preamble,
+            vec![
+                "G21 G90",
+                "G17",
+                "G55",
+                "G49",
+                "M61 Q2",
+                "G53 G0 Z-1.0000",
+                "G0 A0",
+                "M3 S12000",
+                "M8",
+                "G0 X50.0000 Y30.0000",
+                "G1 Z-1.0000 F900.0000",
+                "G91",
+            ]
+        );
+        assert!(plan.warnings.is_empty());
+        let program = plan.program(&PROGRAM);
+        assert_eq!(program.len(), plan.preamble.len() + 1);
+        assert_eq!(program.last().unwrap(), "G1 Y10");
+    }
+
+    #[test]
+    fn test_plan_inch_and_plunge_feed() {
+        let lines = ["G20", "G0 X1 Y2", "G1 Z-0.1 F10", "G0 Z0.5", "X2"];
+        let options = StartFromOptions {
+            safe_z: SafeZ::Work(12.7),
+            plunge_feed_mm_min: Some(127.0),
+            retract_bed: Some(false),
+        };
+        let plan = plan_start_from(&lines, 5, &options, &config()).unwrap();
+        assert!(plan.preamble.contains(&"G20 G90".to_string()));
+        assert!(plan.preamble.contains(&"G0 Z0.5000".to_string()));
+        assert!(plan.preamble.contains(&"G1 Z0.5000 F5.0000".to_string()));
+        assert!(plan.preamble.contains(&"F10.0000".to_string()));
+        // Program's modal G0 is restored for the following "X2".
+        assert_eq!(plan.preamble.last().unwrap(), "G0");
+        assert!(!plan.preamble.iter().any(|l| l.contains('A')));
+    }
+
+    #[test]
+    fn test_plan_after_arc() {
+        let lines = [
+            "G0 X0 Y0 Z1",
+            "G1 Z-1 F300",
+            "G2 X10 Y0 I5 J0",
+            "X0 Y0 I-5 J0",
+            "G1 X5",
+        ];
+        let options = StartFromOptions {
+            retract_bed: Some(false),
+            ..StartFromOptions::default()
+        };
+        let plan = plan_start_from(&lines, 5, &options, &config()).unwrap();
+        assert!(!plan.preamble.iter().any(|l| l == "G2" || l == "G3"));
+        assert!(plan.warnings.is_empty());
+        assert_eq!(plan.preamble.last().unwrap(), "G1 Z-1.0000 F300.0000");
+
+        // Starting on a line that continues the arc modally cannot be resumed as is.
+        let plan = plan_start_from(&lines, 4, &options, &config()).unwrap();
+        assert!(!plan.preamble.iter().any(|l| l == "G2"));
+        assert_eq!(plan.warnings.len(), 1);
+        assert!(plan.warnings[0].contains("G2"));
+    }
+
+    #[test]
+    fn test_plan_out_of_range() {
+        let options = StartFromOptions::default();
+        let err = plan_start_from(&PROGRAM, 11, &options, &config()).unwrap_err();
+        assert_eq!(
+            err,
+            StartFromError::LineOutOfRange {
+                line: 11,
+                count: 10
+            }
+        );
+        assert!(plan_start_from(&PROGRAM, 0, &options, &config()).is_err());
+    }
+
+    #[test]
+    fn test_plan_without_feed_needs_plunge_feed() {
+        let lines = ["G0 X10 Y10", "G0 Z1", "G1 Z-1 F200"];
+        let options = StartFromOptions::default();
+        let err = plan_start_from(&lines, 3, &options, &config()).unwrap_err();
+        assert_eq!(err, StartFromError::NoPlungeFeed { line: 3 });
+
+        let options = StartFromOptions {
+            plunge_feed_mm_min: Some(100.0),
+            ..options
+        };
+        let plan = plan_start_from(&lines, 3, &options, &config()).unwrap();
+        assert!(plan.preamble.contains(&"G1 Z1.0000 F100.0000".to_string()));
+        assert!(!plan.preamble.iter().any(|l| l.starts_with("F0")));
+    }
+
+    #[test]
+    fn test_plan_retracts_bed_only_if_program_uses_it() {
+        let options = StartFromOptions::default();
+        // Stays within the gantry limit: the bed never moves.
+        let plan = plan_start_from(&PROGRAM, 10, &options, &config()).unwrap();
+        assert!(!plan.preamble.iter().any(|l| l.contains('A')));
+
+        let lines = ["G0 X0 Y0 Z5", "G1 Y700 F300", "G1 X10"];
+        let plan = plan_start_from(&lines, 3, &options, &config()).unwrap();
+        assert!(plan.preamble.contains(&"G0 A0".to_string()));
+    }
+
+    #[test]
+    fn test_plan_warns_about_g92() {
+        let lines = ["G1 X10 F100", "G92 X0", "G0 X5"];
+        let plan = plan_start_from(&lines, 3, &StartFromOptions::default(), &config()).unwrap();
+        assert_eq!(plan.warnings.len(), 1);
+        assert!(plan.preamble.contains(&"G0 X0.0000 Y0.0000".to_string()));
+    }
+}
@@ -84,6 +84,18 @@ impl From<u8> for AlarmCode {
     }
 }
 
+impl AlarmCode {
+    /// Numeric code as reported in `ALARM:N`.
+    pub fn number(&self) -> u8 {
+        match self {
+            AlarmCode::Unknown(n) => *n,
+            known => (1..=21)
+                .find(|&n| AlarmCode::from(n) == *known)
+                .unwrap_or(0),
+        }
+    }
+}
+
 /// Input pin state (limit switches, probe). GRBL-HAL reports these
 /// in status when configured; we use booleans for the PROVerXL layout
 /// (X, Y, Z limits + probe).
@@ -118,6 +130,9 @@ pub struct MachineStatus {
     pub state: MachineState,
     pub machine_pos: Position,
     pub work_pos: Position,
+    /// Work position in part space: gantry Y plus the bed-axis extension.
+    /// Equals `work_pos` until a bed mapping is applied (see `apply_part_position`).
+    pub part_pos: Position,
     pub feed_rate: f64,
     pub spindle_speed: f64,
     pub input_pins: PinState,
@@ -144,6 +159,12 @@ impl MachineStatus {
                 z: 0.0,
                 a: None,
             },
+            part_pos: Position {
+                x: 0.0,
+                y: 0.0,
+                z: 0.0,
+                a: None,
+            },
             feed_rate: 0.0,
             spindle_speed: 0.0,
             input_pins: PinState::default(),
@@ -152,6 +173,41 @@ impl MachineStatus {
     }
 }
 
+/// Program-flow event raised while streaming a job (for the UI and session log).
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum JobEvent {
+    /// `M0`, or `M1` with optional stop enabled, paused the job. Waiting for continue.
+    PauseRequested {
+        /// 1-based line number in the program.
+        line: usize,
+        /// True for `M1`.
+        optional: bool,
+    },
+    /// `M6` reached. Waiting for the operator to change the tool and continue.
+    ToolChangeRequested {
+        line: usize,
+        /// Tool from the last `T` word, if any.
+        tool: Option<u32>,
+    },
+    /// Guided tool change measured the new tool on the tool setter and applied
+    /// the compensation. Record the offset in the tool table.
+    ToolMeasured {
+        line: usize,
+        tool: Option<u32>,
+        /// Length relative to the reference tool (mm, positive = longer).
+        length_offset_mm: f64,
+    },
+    /// A line was rejected under `ErrorPolicy::Pause`; the machine is in feed
+    /// hold, waiting for the operator to continue or abort.
+    ErrorPaused {
+        line: usize,
+        /// GRBL error code (`"20"` for `error:20`).
+        code: String,
+    },
+    /// `M2`/`M30` ended the program; nothing after it is sent.
+    ProgramEnded { line: usize },
+}
+
 impl<'de> Deserialize<'de> for MachineStatus {
     fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
     where
@@ -163,6 +219,8 @@ impl<'de> Deserialize<'de> for MachineStatus {
             state: MachineState,
             machine_pos: Position,
             work_pos: Position,
+            #[serde(default)]
+            part_pos: Option<Position>,
             feed_rate: f64,
             spindle_speed: f64,
             input_pins: PinState,
@@ -171,6 +229,7 @@ impl<'de> Deserialize<'de> for MachineStatus {
         Ok(MachineStatus {
             state: dto.state,
             machine_pos: dto.machine_pos,
+            part_pos: dto.part_pos.unwrap_or_else(|| dto.work_pos.clone()),
             work_pos: dto.work_pos,
             feed_rate: dto.feed_rate,
             spindle_speed: dto.spindle_speed,
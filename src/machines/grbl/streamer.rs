@@ -25,17 +25,28 @@
 
 #![cfg(feature = "serial")]
 
+use super::commands::{GrblCommand, RealtimeCommand};
+use super::gcode::{parse_words, Interpreter, ModalState, ProgramFlow};
+use super::motion::TranslatedLine;
+use super::parser::{parse_probe_report, parse_response, parse_tool_length_offset, Response};
 use super::port::{Port, PortError};
-use super::state::{MachineState, MachineStatus};
+use super::state::{JobEvent, MachineState, MachineStatus, Position};
+use super::toolchange::ToolChangeSession;
+use crate::machines::session::{now_secs, SessionEvent, SessionRecorder};
+use serde::{Deserialize, Serialize};
 use std::path::Path;
 use std::sync::Arc;
-use std::time::Duration;
-use tokio::sync::Mutex;
+use std::time::{Duration, Instant};
+use tokio::sync::{broadcast, mpsc, Mutex};
 use tracing::{debug, info, warn};
 
 /// Default timeout when waiting for `ok`/`error` after sending a line (30 s).
 pub const LINE_RESPONSE_TIMEOUT_MS: u64 = 30_000;
 
+/// How long an `M0`/`M1` reply is read for before the line is treated as a
+/// program pause whose reply only comes after cycle start.
+const PAUSE_REPLY_WINDOW_MS: u64 = 250;
+
 /// Outcome of streaming a single line.
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub enum LineResult {
@@ -52,6 +63,35 @@ pub struct StreamResult {
     pub lines_ok: u32,
     /// First error response, if any (message only).
     pub first_error: Option<String>,
+    /// Every rejected line, in order.
+    pub errors: Vec<LineError>,
+}
+
+/// A line the controller rejected.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct LineError {
+    /// 1-based line number in the original program.
+    pub line: usize,
+    /// Text that was sent.
+    pub text: String,
+    /// GRBL error code (`"20"` for `error:20`), or `"ALARM:N"` if the line raised an alarm.
+    pub code: String,
+}
+
+/// Result of a check-mode (`$C`) run of a whole job.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct ValidationReport {
+    /// Lines sent to the controller.
+    pub lines_checked: u32,
+    /// Every rejected line, in order.
+    pub errors: Vec<LineError>,
+}
+
+impl ValidationReport {
+    /// True if the controller accepted every line.
+    pub fn is_valid(&self) -> bool {
+        self.errors.is_empty()
+    }
 }
 
 /// Errors from the streamer.
@@ -63,6 +103,8 @@ pub enum StreamerError {
     ReadFile(#[from] std::io::Error),
     #[error("task join: {0}")]
     Join(#[from] tokio::task::JoinError),
+    #[error("{line}: rejected ({code})")]
+    Rejected { line: String, code: String },
 }
 
 /// Returns true if the line should be sent (non-empty, not a comment).
@@ -72,6 +114,29 @@ fn is_sendable_line(line: &str) -> bool {
     !trimmed.is_empty() && !trimmed.starts_with(';')
 }
 
+/// What the streamer does when the controller rejects a line (`error:N`).
+/// Alarms always stop the stream.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ErrorPolicy {
+    /// Stop streaming.
+    #[default]
+    Stop,
+    /// Record the error and keep streaming.
+    Continue,
+    /// Feed hold and wait for an [`OperatorDecision`].
+    Pause,
+}
+
+/// Operator's answer to a stream waiting on a [`JobEvent`] or an error pause
+/// (see [`ErrorPolicy::Pause`]).
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum OperatorDecision {
+    /// Carry on: skip the rejected line, release the hold, or send the `M6`.
+    Continue,
+    /// Stop streaming. Any hold stays active.
+    Abort,
+}
+
 /// Stream a g-code file: read line by line, send with flow control, pause on Hold.
 ///
 /// Uses the shared port and state. For each sendable line: waits until state is
@@ -89,6 +154,8 @@ pub async fn stream_file(
 }
 
 /// Stream an iterator of g-code lines with the same flow control as `stream_file`.
+/// Stops on the first error; line numbers in [`StreamResult::errors`] count from
+/// the first item.
 pub async fn stream_lines<I, S>(
     port: Arc<Mutex<Port>>,
     state: Arc<Mutex<MachineStatus>>,
@@ -98,13 +165,306 @@ pub async fn stream_lines<I, S>(
 where
     I: Iterator<Item = S>,
     S: AsRef<str>,
+{
+    let lines = lines.enumerate().map(|(i, l)| TranslatedLine {
+        source_line: i + 1,
+        text: l.as_ref().to_string(),
+    });
+    stream_translated(
+        port,
+        state,
+        lines,
+        line_response_timeout,
+        ErrorPolicy::Stop,
+        None,
+    )
+    .await
+}
+
+/// Program-flow words on a line that the streamer reacts to.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+struct FlowWords {
+    pause: Option<ProgramFlow>,
+    end: bool,
+    tool_change: bool,
+    tool: Option<u32>,
+}
+
+fn flow_words(line: &str) -> FlowWords {
+    let mut flow = FlowWords::default();
+    for w in parse_words(line).unwrap_or_default() {
+        match (w.letter, w.code()) {
+            ('M', 0) => flow.pause = Some(ProgramFlow::Pause),
+            ('M', 10) => flow.pause = Some(ProgramFlow::OptionalStop),
+            ('M', 20) | ('M', 300) => flow.end = true,
+            ('M', 60) => flow.tool_change = true,
+            ('T', _) => flow.tool = Some(w.value as u32),
+            _ => {}
+        }
+    }
+    flow
+}
+
+/// Operator interaction for [`stream_translated`]: program pauses, tool changes
+/// and [`ErrorPolicy::Pause`] publish a [`JobEvent`] and wait for a decision.
+pub struct StreamHooks<'a> {
+    pub decisions: &'a mut mpsc::Receiver<OperatorDecision>,
+    pub events: broadcast::Sender<JobEvent>,
+    /// Run the guided tool change (park, probe, compensate) on `M6` instead of
+    /// only waiting for the operator.
+    pub tool_change: Option<&'a mut ToolChangeSession>,
+    /// Record lines sent, replies, alarms, feedback and real-time commands.
+    pub recorder: Option<Arc<Mutex<Option<SessionRecorder>>>>,
+}
+
+impl StreamHooks<'_> {
+    fn emit(&self, event: JobEvent) {
+        emit(&self.events, event);
+    }
+
+    async fn decide(&mut self) -> OperatorDecision {
+        decide(self.decisions).await
+    }
+
+    /// Report a line rejected under [`ErrorPolicy::Pause`] and wait for the
+    /// operator.
+    async fn error_paused(&mut self, line: usize, code: &str) -> OperatorDecision {
+        self.emit(JobEvent::ErrorPaused {
+            line,
+            code: code.to_string(),
+        });
+        self.decide().await
+    }
+}
+
+/// Append `event` to the session recorder, if one is attached. The write runs
+/// on the blocking pool so file I/O never stalls the runtime.
+pub(super) async fn record(
+    recorder: Option<&Arc<Mutex<Option<SessionRecorder>>>>,
+    event: SessionEvent,
+) {
+    let Some(recorder) = recorder.cloned() else {
+        return;
+    };
+    let written = tokio::task::spawn_blocking(move || match recorder.blocking_lock().as_mut() {
+        Some(rec) => rec.record(event),
+        None => Ok(()),
+    })
+    .await;
+    match written {
+        Ok(Err(e)) => warn!("session recorder: {}", e),
+        Err(e) => warn!("session recorder: {}", e),
+        Ok(Ok(())) => {}
+    }
+}
+
+fn emit(events: &broadcast::Sender<JobEvent>, event: JobEvent) {
+    // No subscribers is fine; the decision can still come from the API.
+    let _ = events.send(event);
+}
+
+async fn decide(decisions: &mut mpsc::Receiver<OperatorDecision>) -> OperatorDecision {
+    decisions.recv().await.unwrap_or(OperatorDecision::Abort)
+}
+
+/// Wait for a status report newer than `since` that shows the machine is no
+/// longer running, and return it.
+pub(super) async fn settled_status(
+    state: &Arc<Mutex<MachineStatus>>,
+    since: Instant,
+) -> MachineStatus {
+    loop {
+        {
+            let current = state.lock().await;
+            if current.last_updated > since
+                && !matches!(current.state, MachineState::Run | MachineState::Jog)
+            {
+                return current.clone();
+            }
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+}
+
+/// Send lines that must all be accepted; an error or alarm aborts with
+/// [`StreamerError::Rejected`]. Returns the feedback received for the last line.
+pub(super) async fn send_all(
+    port: &Arc<Mutex<Port>>,
+    lines: &[String],
+    timeout: Duration,
+) -> Result<Vec<String>, StreamerError> {
+    let mut feedback = Vec::new();
+    for line in lines {
+        let (reply, fb) = exchange_with_feedback(Arc::clone(port), line, timeout).await?;
+        match reply {
+            Response::Error(code) | Response::Alarm(code) => {
+                return Err(StreamerError::Rejected {
+                    line: line.clone(),
+                    code,
+                })
+            }
+            _ => feedback = fb,
+        }
+    }
+    Ok(feedback)
+}
+
+/// Probe the tool on the setter. Returns the trigger height (machine Z) and the
+/// work Z where the machine stopped.
+async fn probe_setter(
+    port: &Arc<Mutex<Port>>,
+    state: &Arc<Mutex<MachineStatus>>,
+    session: &ToolChangeSession,
+    timeout: Duration,
+) -> Result<(f64, f64), StreamerError> {
+    let c = &session.config;
+    let (probe, moves) = session
+        .probe_lines()
+        .split_last()
+        .map(|(p, m)| (p.clone(), m.to_vec()))
+        .unwrap_or_default();
+    send_all(port, &moves, timeout).await?;
+    // The reply only comes once the probe has triggered or run out of travel.
+    let probe_secs = c.probe_distance_mm / c.probe_feed_mm_min.max(1.0) * 60.0 + 10.0;
+    let probe_timeout = timeout.max(Duration::from_secs_f64(probe_secs));
+    let contact = probe_contact(port, &probe, probe_timeout).await?;
+    send_all(port, &["G90".to_string()], timeout).await?;
+    let status = settled_status(state, Instant::now()).await;
+    Ok((contact.z, status.work_pos.z))
+}
+
+/// Send a `G38.2` line and return the contact (machine coordinates) from its
+/// `[PRB:...]` report. Size `timeout` for the full probe travel.
+pub(super) async fn probe_contact(
+    port: &Arc<Mutex<Port>>,
+    line: &str,
+    timeout: Duration,
+) -> Result<Position, StreamerError> {
+    let feedback = send_all(port, &[line.to_string()], timeout).await?;
+    feedback
+        .iter()
+        .find_map(|l| parse_probe_report(l).ok())
+        .filter(|r| r.success)
+        .map(|r| r.position)
+        .ok_or(StreamerError::Rejected {
+            line: line.to_string(),
+            code: "no probe contact".to_string(),
+        })
+}
+
+/// The tool length offset active on the controller, from `$#`.
+async fn active_tool_length_offset(
+    port: &Arc<Mutex<Port>>,
+    timeout: Duration,
+) -> Result<f64, StreamerError> {
+    let line = GrblCommand::Parameters.to_string();
+    let feedback = send_all(port, std::slice::from_ref(&line), timeout).await?;
+    feedback
+        .iter()
+        .find_map(|l| parse_tool_length_offset(l))
+        .ok_or(StreamerError::Rejected {
+            line,
+            code: "no [TLO:] in reply".to_string(),
+        })
+}
+
+/// Guided tool change on `M6`: probe a reference if none is known, park, wait
+/// for the operator, probe the new tool and apply the compensation. The
+/// sequence runs in mm; `modal` (the program's state before the `M6`) has its
+/// units, distance mode and feed restored at the end. Returns false if the
+/// operator aborted.
+async fn guided_tool_change(
+    port: &Arc<Mutex<Port>>,
+    state: &Arc<Mutex<MachineStatus>>,
+    hooks: &mut StreamHooks<'_>,
+    line: usize,
+    tool: Option<u32>,
+    modal: &ModalState,
+    timeout: Duration,
+) -> Result<bool, StreamerError> {
+    let StreamHooks {
+        decisions,
+        events,
+        tool_change: Some(session),
+        ..
+    } = hooks
+    else {
+        return Ok(true);
+    };
+    if session.reference_z.is_none() {
+        info!("tool change: probing current tool as reference");
+        let (trigger_z, _) = probe_setter(port, state, session, timeout).await?;
+        session.set_reference(trigger_z);
+    }
+    send_all(port, &session.park_lines(), timeout).await?;
+    info!(
+        "tool change: parked for tool {:?}, waiting for operator",
+        tool
+    );
+    emit(events, JobEvent::ToolChangeRequested { line, tool });
+    if decide(decisions).await == OperatorDecision::Abort {
+        return Ok(false);
+    }
+    let (trigger_z, work_z) = probe_setter(port, state, session, timeout).await?;
+    let active_tlo = active_tool_length_offset(port, timeout).await?;
+    let Some(m) = session.measure(tool, trigger_z, work_z, active_tlo) else {
+        unreachable!("reference is set before parking");
+    };
+    send_all(
+        port,
+        &[
+            m.compensation_line.clone(),
+            session.retract_line(),
+            modal.restore_line(),
+        ],
+        timeout,
+    )
+    .await?;
+    info!(
+        "tool change: tool {:?} offset {:.4} mm ({})",
+        tool, m.length_offset_mm, m.compensation_line
+    );
+    emit(
+        events,
+        JobEvent::ToolMeasured {
+            line,
+            tool,
+            length_offset_mm: m.length_offset_mm,
+        },
+    );
+    Ok(true)
+}
+
+/// Stream translated lines, handling `error:` replies according to `policy`.
+/// Informational feedback (`[MSG:...]`) is logged and never stops the stream;
+/// `M2`/`M30` end it. With `hooks`, `M6` waits for the operator before it is
+/// sent, `M0`/`M1` wait for the operator once the machine holds and resume with
+/// cycle start, and [`ErrorPolicy::Pause`] is available (without hooks it
+/// behaves like `Stop`).
+pub async fn stream_translated<I>(
+    port: Arc<Mutex<Port>>,
+    state: Arc<Mutex<MachineStatus>>,
+    lines: I,
+    line_response_timeout: Duration,
+    policy: ErrorPolicy,
+    mut hooks: Option<StreamHooks<'_>>,
+) -> Result<StreamResult, StreamerError>
+where
+    I: Iterator<Item = TranslatedLine>,
 {
     let mut result = StreamResult::default();
+    let mut tool = None;
+    // Modal state of the accepted lines, restored after a guided tool change.
+    let mut interp = Interpreter::new();
+    let recorder = hooks.as_ref().and_then(|h| h.recorder.clone());
+    let recorder = recorder.as_ref();
     for line in lines {
-        let line = line.as_ref().trim();
-        if !is_sendable_line(line) {
+        let text = line.text.trim();
+        if !is_sendable_line(text) {
             continue;
         }
+        let flow = flow_words(text);
+        tool = flow.tool.or(tool);
 
         // Pause while machine is in Hold; resume when Idle (or Run).
         loop {
@@ -118,53 +478,313 @@ where
             }
         }
 
-        let line = line.to_string();
-        let port_clone = Arc::clone(&port);
-        let timeout = line_response_timeout;
-        let response = tokio::task::spawn_blocking(move || {
-            let mut port = port_clone.blocking_lock();
-            port.send_line(&line)?;
-            let response = port.read_line(timeout)?;
-            Ok::<_, PortError>(response)
-        })
-        .await
-        .map_err(StreamerError::Join)?
-        .map_err(StreamerError::Port)?;
+        if let (true, Some(h)) = (flow.tool_change, hooks.as_mut()) {
+            let proceed = if h.tool_change.is_some() {
+                let (timeout, modal) = (line_response_timeout, interp.modal());
+                guided_tool_change(&port, &state, h, line.source_line, tool, modal, timeout).await?
+            } else {
+                info!("streamer: tool change to {:?}, waiting for operator", tool);
+                h.emit(JobEvent::ToolChangeRequested {
+                    line: line.source_line,
+                    tool,
+                });
+                h.decide().await == OperatorDecision::Continue
+            };
+            if !proceed {
+                break;
+            }
+        }
 
-        let response = response.trim();
+        let event = SessionEvent::LineSent {
+            line: line.source_line,
+            text: text.to_string(),
+            ts_secs: now_secs(),
+        };
+        record(recorder, event).await;
+        let sent = match flow.pause {
+            Some(pause) => {
+                let timeout = line_response_timeout;
+                let h = hooks.as_mut();
+                send_pause_line(&port, &state, &line, pause, h, recorder, timeout).await?
+            }
+            None => {
+                Some(exchange_with_feedback(Arc::clone(&port), text, line_response_timeout).await?)
+            }
+        };
         result.lines_sent += 1;
+        let Some((reply, feedback)) = sent else {
+            info!("streamer: aborted at pause on line {}", line.source_line);
+            break;
+        };
+        for message in feedback {
+            let event = SessionEvent::Feedback {
+                message,
+                ts_secs: now_secs(),
+            };
+            record(recorder, event).await;
+        }
+        let event = SessionEvent::Reply {
+            line: line.source_line,
+            reply: match &reply {
+                Response::Ok => "ok".to_string(),
+                Response::Error(code) => format!("error:{}", code),
+                Response::Alarm(code) => format!("ALARM:{}", code),
+                Response::Feedback(_) => unreachable!("read_reply skips feedback"),
+            },
+            ts_secs: now_secs(),
+        };
+        record(recorder, event).await;
 
-        if response.eq_ignore_ascii_case("ok") {
-            result.lines_ok += 1;
-        } else if response.starts_with("error:") || response.starts_with("Error:") {
-            let msg = response
-                .strip_prefix("error:")
-                .or_else(|| response.strip_prefix("Error:"))
-                .map(str::trim)
-                .unwrap_or(response)
-                .to_string();
-            if result.first_error.is_none() {
-                result.first_error = Some(msg.clone());
+        let (code, alarm) = match reply {
+            Response::Ok => {
+                result.lines_ok += 1;
+                let _ = interp.execute(text);
+                if flow.end {
+                    info!("streamer: program end at line {}", line.source_line);
+                    if let Some(h) = &hooks {
+                        h.emit(JobEvent::ProgramEnded {
+                            line: line.source_line,
+                        });
+                    }
+                    break;
+                }
+                continue;
             }
-            warn!("streamer: error response: {}", msg);
-            break;
-        } else {
-            // Unexpected response (e.g. alarm message); treat as error and stop.
-            if result.first_error.is_none() {
-                result.first_error = Some(response.to_string());
+            Response::Error(code) => (code, false),
+            Response::Alarm(code) => {
+                let event = SessionEvent::Alarm {
+                    code: code.clone(),
+                    line: Some(line.source_line),
+                    ts_secs: now_secs(),
+                };
+                record(recorder, event).await;
+                (format!("ALARM:{}", code), true)
             }
-            warn!("streamer: unexpected response: {}", response);
+            Response::Feedback(_) => unreachable!("read_reply skips feedback"),
+        };
+        warn!("streamer: line {} ({}): {}", line.source_line, text, code);
+        if result.first_error.is_none() {
+            result.first_error = Some(code.clone());
+        }
+        result.errors.push(LineError {
+            line: line.source_line,
+            text: text.to_string(),
+            code,
+        });
+        if alarm {
             break;
         }
+        match (policy, hooks.as_mut()) {
+            (ErrorPolicy::Continue, _) => {}
+            (ErrorPolicy::Pause, Some(h)) => {
+                send_realtime(&port, RealtimeCommand::FeedHold, recorder).await?;
+                info!("streamer: feed hold, waiting for operator");
+                let code = &result.errors.last().expect("error just recorded").code;
+                if h.error_paused(line.source_line, code).await == OperatorDecision::Abort {
+                    break;
+                }
+                send_realtime(&port, RealtimeCommand::CycleStart, recorder).await?;
+            }
+            _ => break,
+        }
     }
 
     info!(
-        "streamer: done, sent={} ok={}",
-        result.lines_sent, result.lines_ok
+        "streamer: done, sent={} ok={} errors={}",
+        result.lines_sent,
+        result.lines_ok,
+        result.errors.len()
     );
     Ok(result)
 }
 
+async fn send_realtime(
+    port: &Arc<Mutex<Port>>,
+    cmd: RealtimeCommand,
+    recorder: Option<&Arc<Mutex<Option<SessionRecorder>>>>,
+) -> Result<(), StreamerError> {
+    let event = SessionEvent::Realtime {
+        command: format!("{:?}", cmd),
+        ts_secs: now_secs(),
+    };
+    record(recorder, event).await;
+    let port = Arc::clone(port);
+    tokio::task::spawn_blocking(move || port.blocking_lock().send_byte(cmd.as_byte())).await??;
+    Ok(())
+}
+
+/// Send an `M0`/`M1` line. The controller only answers it once the program
+/// pause is resumed, so the reply is not awaited while the machine holds (which
+/// would keep the port from the poller and run into the line timeout): once the
+/// machine settles in Hold, the pause is reported and, on `Continue`, resumed
+/// with cycle start before the reply is read. Without hooks it waits for the
+/// hold to be resumed some other way. Returns `None` if the operator aborted;
+/// the reply is then still outstanding.
+async fn send_pause_line(
+    port: &Arc<Mutex<Port>>,
+    state: &Arc<Mutex<MachineStatus>>,
+    line: &TranslatedLine,
+    pause: ProgramFlow,
+    hooks: Option<&mut StreamHooks<'_>>,
+    recorder: Option<&Arc<Mutex<Option<SessionRecorder>>>>,
+    timeout: Duration,
+) -> Result<Option<(Response, Vec<String>)>, StreamerError> {
+    let sent_at = Instant::now();
+    let text = line.text.trim().to_string();
+    let window = Duration::from_millis(PAUSE_REPLY_WINDOW_MS);
+    let p = Arc::clone(port);
+    let (reply, mut feedback) = tokio::task::spawn_blocking(move || {
+        let mut port = p.blocking_lock();
+        port.send_line(&text)?;
+        let mut feedback = Vec::new();
+        match read_reply(&mut port, window, &mut feedback) {
+            Ok(reply) => Ok((Some(reply), feedback)),
+            Err(PortError::Timeout(_)) => Ok((None, feedback)),
+            Err(e) => Err(e),
+        }
+    })
+    .await??;
+    if let Some(reply) = reply {
+        // M1 with optional stop disabled (or a rejected line).
+        return Ok(Some((reply, feedback)));
+    }
+
+    // M0/M1 waits for the planner to empty, then holds; no timeout applies.
+    if let MachineState::Hold(_) = settled_status(state, sent_at).await.state {
+        match hooks {
+            Some(h) => {
+                h.emit(JobEvent::PauseRequested {
+                    line: line.source_line,
+                    optional: pause == ProgramFlow::OptionalStop,
+                });
+                if h.decide().await == OperatorDecision::Abort {
+                    return Ok(None);
+                }
+                send_realtime(port, RealtimeCommand::CycleStart, recorder).await?;
+            }
+            None => {
+                info!("streamer: program paused, waiting for cycle start");
+                loop {
+                    let current = state.lock().await.state.clone();
+                    if !matches!(current, MachineState::Hold(_) | MachineState::Door) {
+                        break;
+                    }
+                    tokio::time::sleep(Duration::from_millis(100)).await;
+                }
+            }
+        }
+    }
+
+    let p = Arc::clone(port);
+    let (reply, more) = tokio::task::spawn_blocking(move || {
+        let mut port = p.blocking_lock();
+        let mut feedback = Vec::new();
+        let reply = read_reply(&mut port, timeout, &mut feedback)?;
+        Ok::<_, PortError>((reply, feedback))
+    })
+    .await??;
+    feedback.extend(more);
+    Ok(Some((reply, feedback)))
+}
+
+/// Read lines until the controller answers `ok`, `error:` or `ALARM:`, skipping
+/// feedback such as `[MSG:...]`.
+fn read_reply(
+    port: &mut Port,
+    timeout: Duration,
+    feedback: &mut Vec<String>,
+) -> Result<Response, PortError> {
+    loop {
+        match parse_response(&port.read_line(timeout)?) {
+            Response::Feedback(msg) => {
+                debug!("streamer: feedback: {}", msg);
+                feedback.push(msg);
+            }
+            reply => return Ok(reply),
+        }
+    }
+}
+
+/// Send one line and read its reply, keeping the feedback received before it
+/// (e.g. a `[PRB:...]` probe report).
+async fn exchange_with_feedback(
+    port: Arc<Mutex<Port>>,
+    line: &str,
+    timeout: Duration,
+) -> Result<(Response, Vec<String>), StreamerError> {
+    let line = line.to_string();
+    let reply = tokio::task::spawn_blocking(move || {
+        let mut port = port.blocking_lock();
+        port.send_line(&line)?;
+        let mut feedback = Vec::new();
+        let reply = read_reply(&mut port, timeout, &mut feedback)?;
+        Ok::<_, PortError>((reply, feedback))
+    })
+    .await??;
+    Ok(reply)
+}
+
+/// Send one line and read its reply.
+async fn exchange(
+    port: Arc<Mutex<Port>>,
+    line: &str,
+    timeout: Duration,
+) -> Result<Response, StreamerError> {
+    Ok(exchange_with_feedback(port, line, timeout).await?.0)
+}
+
+/// Send one line and wait for its reply. [`LineResult::Error`] holds the error
+/// or alarm code.
+pub async fn send_command(
+    port: Arc<Mutex<Port>>,
+    line: &str,
+    timeout: Duration,
+) -> Result<LineResult, StreamerError> {
+    Ok(match exchange(port, line, timeout).await? {
+        Response::Ok => LineResult::Ok,
+        Response::Error(code) | Response::Alarm(code) => LineResult::Error(code),
+        Response::Feedback(_) => unreachable!("read_reply skips feedback"),
+    })
+}
+
+/// Send every line of a job while the controller is in check mode, collecting
+/// every `error:N` instead of stopping at the first. Stops early only on an alarm.
+pub async fn check_lines(
+    port: Arc<Mutex<Port>>,
+    lines: Vec<TranslatedLine>,
+    line_response_timeout: Duration,
+) -> Result<ValidationReport, StreamerError> {
+    let mut report = ValidationReport::default();
+    for line in lines {
+        let text = line.text.trim();
+        if !is_sendable_line(text) {
+            continue;
+        }
+        let reply = exchange(Arc::clone(&port), text, line_response_timeout).await?;
+        report.lines_checked += 1;
+        let (code, alarm) = match reply {
+            Response::Error(code) => (code, false),
+            Response::Alarm(code) => (format!("ALARM:{}", code), true),
+            _ => continue,
+        };
+        warn!("check: line {}: {}", line.source_line, code);
+        report.errors.push(LineError {
+            line: line.source_line,
+            text: text.to_string(),
+            code,
+        });
+        if alarm {
+            break;
+        }
+    }
+    info!(
+        "check: done, checked={} errors={}",
+        report.lines_checked,
+        report.errors.len()
+    );
+    Ok(report)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -184,11 +804,63 @@ mod tests {
         assert_eq!(LineResult::Ok, LineResult::Ok);
     }
 
+    #[test]
+    fn test_validation_report_is_valid() {
+        let mut report = ValidationReport::default();
+        assert!(report.is_valid());
+        report.errors.push(LineError {
+            line: 3,
+            text: "G1X10".into(),
+            code: "22".into(),
+        });
+        assert!(!report.is_valid());
+    }
+
+    #[tokio::test]
+    async fn test_error_pause_emits_event() {
+        let (decision_tx, mut decisions) = mpsc::channel(1);
+        let (events, mut rx) = broadcast::channel(4);
+        let mut hooks = StreamHooks {
+            decisions: &mut decisions,
+            events,
+            tool_change: None,
+            recorder: None,
+        };
+        decision_tx.send(OperatorDecision::Abort).await.unwrap();
+        assert_eq!(hooks.error_paused(7, "20").await, OperatorDecision::Abort);
+        assert_eq!(
+            rx.recv().await.unwrap(),
+            JobEvent::ErrorPaused {
+                line: 7,
+                code: "20".to_string()
+            }
+        );
+    }
+
     #[test]
     fn test_stream_result_default() {
         let r = StreamResult::default();
         assert_eq!(r.lines_sent, 0);
         assert_eq!(r.lines_ok, 0);
         assert!(r.first_error.is_none());
+        assert!(r.errors.is_empty());
+    }
+
+    #[test]
+    fn test_flow_words() {
+        assert_eq!(flow_words("G1 X10"), FlowWords::default());
+        let f = flow_words("T3 M6");
+        assert!(f.tool_change);
+        assert_eq!(f.tool, Some(3));
+        assert_eq!(flow_words("M0").pause, Some(ProgramFlow::Pause));
+        assert_eq!(flow_words("M1").pause, Some(ProgramFlow::OptionalStop));
+        assert!(flow_words("M30").end);
+        assert!(flow_words("M2").end);
+        assert!(!flow_words("(M30 in a comment)").end);
+    }
+
+    #[test]
+    fn test_error_policy_default_is_stop() {
+        assert_eq!(ErrorPolicy::default(), ErrorPolicy::Stop);
     }
 }
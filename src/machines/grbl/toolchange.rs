@@ -0,0 +1,274 @@
+//! Guided manual tool change with tool length re-probe.
+//!
+//! When a job reaches `M6`, the streamer retracts to a safe Z, parks at the tool
+//! change position, waits for the operator, then probes the new tool on a fixed
+//! tool setter and compensates the length difference with `G43.1` or by shifting
+//! the active work offset's Z. This module holds the configuration, the lines
+//! each step sends and the offset math; the streamer drives the port. Every
+//! line with a length selects `G21`, so the sequence runs in mm whatever the
+//! program's units; the streamer restores the program's modes afterwards.
+//!
+//! Lengths are relative to a reference tool: the setter trigger height (machine
+//! Z) of a tool with zero length offset. If none is known when the first change
+//! happens, the tool in the spindle is probed first and becomes the reference
+//! (adjusted by its known offset, if any). The compensation itself is relative
+//! to the tool in the spindle: `G43.1` gets the controller's active offset plus
+//! the trigger height difference, so it holds whatever Z was zeroed with.
+
+use crate::machines::profiles::MachineProfile;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// How the measured length difference is applied.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum LengthCompensation {
+    /// `G43.1 Z<offset>` (dynamic tool length offset).
+    #[default]
+    ToolLengthOffset,
+    /// Shift the active work coordinate system's Z (`G10 L20 P0`).
+    WorkOffset,
+}
+
+/// Where the tool change happens and how the tool setter is probed. All
+/// positions are machine coordinates (mm).
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ToolChangeConfig {
+    /// Machine Z to retract to before any XY move.
+    pub safe_z_mm: f64,
+    /// Machine XY where the operator swaps the tool.
+    pub change_position: [f64; 2],
+    /// Machine XY of the fixed tool setter.
+    pub setter_position: [f64; 2],
+    /// Machine Z to rapid down to above the setter before probing.
+    pub setter_start_z_mm: f64,
+    /// Maximum probe travel below `setter_start_z_mm`.
+    pub probe_distance_mm: f64,
+    pub probe_feed_mm_min: f64,
+    pub compensation: LengthCompensation,
+}
+
+impl Default for ToolChangeConfig {
+    fn default() -> Self {
+        Self {
+            safe_z_mm: -1.0,
+            change_position: [0.0, 0.0],
+            setter_position: [0.0, 0.0],
+            setter_start_z_mm: -1.0,
+            probe_distance_mm: 100.0,
+            probe_feed_mm_min: 100.0,
+            compensation: LengthCompensation::ToolLengthOffset,
+        }
+    }
+}
+
+/// A tool measured during a change.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ToolMeasurement {
+    pub tool: Option<u32>,
+    /// Length relative to the reference tool (mm, positive = longer).
+    pub length_offset_mm: f64,
+    /// Line that applies the compensation.
+    pub compensation_line: String,
+}
+
+/// Tool change state carried across changes within a connection.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ToolChangeSession {
+    pub config: ToolChangeConfig,
+    /// Setter trigger height of a zero-offset tool (machine Z).
+    pub reference_z: Option<f64>,
+    /// Tool in the spindle, if known.
+    pub current_tool: Option<u32>,
+    /// Known length offsets by tool number (e.g. from the profile's tool table).
+    pub known_offsets: HashMap<u32, f64>,
+    /// Trigger height of the tool in the spindle, once probed.
+    current_tool_z: Option<f64>,
+}
+
+impl ToolChangeSession {
+    pub fn new(config: ToolChangeConfig) -> Self {
+        Self {
+            config,
+            reference_z: None,
+            current_tool: None,
+            known_offsets: HashMap::new(),
+            current_tool_z: None,
+        }
+    }
+
+    /// Session with `known_offsets` from the profile's tool table. Store the
+    /// measured lengths back with [`MachineProfile::record_job_event`].
+    pub fn for_profile(config: ToolChangeConfig, profile: &MachineProfile) -> Self {
+        Self {
+            known_offsets: profile.tool_offsets(),
+            ..Self::new(config)
+        }
+    }
+
+    /// Retract and park at the tool change position, spindle off. Ends with a
+    /// `G4 P0` so the reply arrives once the machine is there.
+    pub fn park_lines(&self) -> Vec<String> {
+        let c = &self.config;
+        vec![
+            "M5".to_string(),
+            format!("G21 G53 G0 Z{:.4}", c.safe_z_mm),
+            format!(
+                "G53 G0 X{:.4} Y{:.4}",
+                c.change_position[0], c.change_position[1]
+            ),
+            "G4 P0".to_string(),
+        ]
+    }
+
+    /// Move over the setter and probe down. The probe line (last) is incremental
+    /// and answered with a `[PRB:...]` report; send `G90` after it.
+    pub fn probe_lines(&self) -> Vec<String> {
+        let c = &self.config;
+        vec![
+            format!("G21 G53 G0 Z{:.4}", c.safe_z_mm),
+            format!(
+                "G53 G0 X{:.4} Y{:.4}",
+                c.setter_position[0], c.setter_position[1]
+            ),
+            format!("G53 G0 Z{:.4}", c.setter_start_z_mm),
+            format!(
+                "G21 G91 G38.2 Z-{:.4} F{:.1}",
+                c.probe_distance_mm, c.probe_feed_mm_min
+            ),
+        ]
+    }
+
+    /// Retract to the safe Z.
+    pub fn retract_line(&self) -> String {
+        format!("G21 G53 G0 Z{:.4}", self.config.safe_z_mm)
+    }
+
+    /// Record the trigger height of the tool currently in the spindle as the
+    /// reference, taking its known offset into account.
+    pub fn set_reference(&mut self, trigger_z: f64) {
+        let offset = self
+            .current_tool
+            .and_then(|t| self.known_offsets.get(&t))
+            .copied()
+            .unwrap_or(0.0);
+        self.reference_z = Some(trigger_z - offset);
+        self.current_tool_z = Some(trigger_z);
+    }
+
+    /// Measure the new tool from its trigger height. `work_z` is the work Z at the
+    /// machine's current position, where the compensation line will be sent (used
+    /// by [`LengthCompensation::WorkOffset`]); `active_tlo` is the tool length
+    /// offset active on the controller (`[TLO:]`, used by
+    /// [`LengthCompensation::ToolLengthOffset`]). Returns `None` if no reference
+    /// is known.
+    pub fn measure(
+        &mut self,
+        tool: Option<u32>,
+        trigger_z: f64,
+        work_z: f64,
+        active_tlo: f64,
+    ) -> Option<ToolMeasurement> {
+        let reference = self.reference_z?;
+        let length_offset_mm = trigger_z - reference;
+        let current = self.current_tool_z.unwrap_or_else(|| {
+            let offset = self.current_tool.and_then(|t| self.known_offsets.get(&t));
+            reference + offset.copied().unwrap_or(0.0)
+        });
+        // Positive when the new tool is longer than the one it replaces.
+        let change = trigger_z - current;
+        let compensation_line = match self.config.compensation {
+            LengthCompensation::ToolLengthOffset => {
+                format!("G21 G43.1 Z{:.4}", active_tlo + change)
+            }
+            LengthCompensation::WorkOffset => {
+                format!("G21 G10 L20 P0 Z{:.4}", work_z - change)
+            }
+        };
+        self.current_tool_z = Some(trigger_z);
+        self.current_tool = tool;
+        if let Some(t) = tool {
+            self.known_offsets.insert(t, length_offset_mm);
+        }
+        Some(ToolMeasurement {
+            tool,
+            length_offset_mm,
+            compensation_line,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tool_length_offset() {
+        let mut s = ToolChangeSession::new(ToolChangeConfig::default());
+        assert!(s.measure(Some(2), -50.0, 0.0, 0.0).is_none());
+        s.current_tool = Some(1);
+        s.known_offsets.insert(1, 2.0);
+        s.set_reference(-60.0);
+        assert_eq!(s.reference_z, Some(-62.0));
+        // Z was zeroed with tool 1 under G49: the new tool is 5 mm longer than
+        // tool 1, whatever the table says about tool 1.
+        let m = s.measure(Some(2), -55.0, 0.0, 0.0).unwrap();
+        assert_eq!(m.length_offset_mm, 7.0);
+        assert_eq!(m.compensation_line, "G21 G43.1 Z5.0000");
+        assert_eq!(s.current_tool, Some(2));
+        assert_eq!(s.known_offsets[&2], 7.0);
+        // The next change builds on the offset now active.
+        let m = s.measure(Some(3), -58.0, 0.0, 5.0).unwrap();
+        assert_eq!(m.compensation_line, "G21 G43.1 Z2.0000");
+        assert_eq!(m.length_offset_mm, 4.0);
+    }
+
+    #[test]
+    fn test_for_profile() {
+        let mut profile = MachineProfile::proverxl_4030();
+        profile.record_tool_length(1, 2.0);
+        let mut s = ToolChangeSession::for_profile(ToolChangeConfig::default(), &profile);
+        s.current_tool = Some(1);
+        s.set_reference(-60.0);
+        assert_eq!(s.reference_z, Some(-62.0));
+    }
+
+    #[test]
+    fn test_work_offset_compensation_is_incremental() {
+        let mut s = ToolChangeSession::new(ToolChangeConfig {
+            compensation: LengthCompensation::WorkOffset,
+            ..ToolChangeConfig::default()
+        });
+        s.set_reference(-60.0);
+        // New tool 5 mm longer: the surface that read 10 must still read 10.
+        let m = s.measure(Some(2), -55.0, 15.0, 0.0).unwrap();
+        assert_eq!(m.compensation_line, "G21 G10 L20 P0 Z10.0000");
+        // Next tool is 2 mm shorter than tool 2.
+        let m = s.measure(Some(3), -57.0, 8.0, 0.0).unwrap();
+        assert_eq!(m.compensation_line, "G21 G10 L20 P0 Z10.0000");
+        assert_eq!(m.length_offset_mm, 3.0);
+    }
+
+    #[test]
+    fn test_lines() {
+        let s = ToolChangeSession::new(ToolChangeConfig {
+            change_position: [100.0, 5.0],
+            setter_position: [-10.0, -20.0],
+            setter_start_z_mm: -40.0,
+            probe_distance_mm: 50.0,
+            probe_feed_mm_min: 200.0,
+            ..ToolChangeConfig::default()
+        });
+        assert_eq!(s.park_lines()[2], "G53 G0 X100.0000 Y5.0000");
+        assert_eq!(s.park_lines()[3], "G4 P0");
+        assert_eq!(
+            s.probe_lines(),
+            vec![
+                "G21 G53 G0 Z-1.0000",
+                "G53 G0 X-10.0000 Y-20.0000",
+                "G53 G0 Z-40.0000",
+                "G21 G91 G38.2 Z-50.0000 F200.0",
+            ]
+        );
+        assert_eq!(s.retract_line(), "G21 G53 G0 Z-1.0000");
+    }
+}
@@ -0,0 +1,306 @@
+//! Toolpath extraction for the 3D preview.
+//!
+//! Translates the program for the bed extension exactly as `run_file` does, replays
+//! the translated lines (gantry moves and bed-axis moves) and maps them back into
+//! part space, so the preview shows the path the tool will actually take on the
+//! part — including the dog-legs of a sequential split. Output is flat buffers
+//! that can be handed to WebGL without further conversion.
+
+use super::gcode::{DistanceMode, Interpreter, MotionKind, MotionMode, Point};
+use super::motion::{translate_lines_with_source, MotionConfig};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// Segment type, stored as `u8` in [`Toolpath::kinds`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[repr(u8)]
+pub enum SegmentKind {
+    Rapid = 0,
+    Feed = 1,
+    /// Chord of a linearized G2/G3 arc.
+    Arc = 2,
+}
+
+/// Options for [`extract_toolpath`].
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct ToolpathOptions {
+    /// Merge short consecutive segments until at most roughly this many remain.
+    /// `None` keeps every segment.
+    pub max_segments: Option<usize>,
+}
+
+/// Segment buffers in part-space work coordinates (mm). Segment `i` runs from
+/// `positions[6i..6i+3]` to `positions[6i+3..6i+6]`.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Toolpath {
+    /// Start and end XYZ per segment.
+    pub positions: Vec<f32>,
+    /// [`SegmentKind`] per segment.
+    pub kinds: Vec<u8>,
+    /// 1-based source line per segment.
+    pub lines: Vec<u32>,
+    /// Tool loaded (by `M6`) per segment; 0 if none.
+    pub tools: Vec<u32>,
+    /// Min XYZ over all segments, for framing the camera.
+    pub bounds_min: [f32; 3],
+    /// Max XYZ over all segments.
+    pub bounds_max: [f32; 3],
+}
+
+impl Toolpath {
+    /// Number of segments.
+    pub fn len(&self) -> usize {
+        self.kinds.len()
+    }
+
+    /// True if there are no segments.
+    pub fn is_empty(&self) -> bool {
+        self.kinds.is_empty()
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Segment {
+    start: Point,
+    end: Point,
+    kind: SegmentKind,
+    line: u32,
+    tool: u32,
+}
+
+/// Extract the toolpath of a program (untranslated lines, as loaded from the file).
+pub fn extract_toolpath(
+    lines: &[impl AsRef<str>],
+    config: &MotionConfig,
+    options: &ToolpathOptions,
+) -> Toolpath {
+    // Which source lines are arcs, so linearized chords can be labelled as such.
+    let mut source_interp = Interpreter::new();
+    let source_is_arc: Vec<bool> = lines
+        .iter()
+        .map(|l| {
+            source_interp
+                .execute(l.as_ref())
+                .ok()
+                .and_then(|e| e.motion)
+                .is_some_and(|m| m.is_arc())
+        })
+        .collect();
+
+    let bed_letter = config.bed_axis.to_ascii_uppercase();
+    let mut interp = Interpreter::new();
+    let mut bed = 0.0_f64;
+    let mut segments: Vec<Segment> = Vec::new();
+
+    for tl in translate_lines_with_source(lines, config) {
+        let Ok(exec) = interp.execute(&tl.text) else {
+            continue;
+        };
+        let modal = interp.modal();
+        let bed_from = bed;
+        if let Some(w) = exec.words.iter().find(|w| w.letter == bed_letter) {
+            let g53 = exec
+                .words
+                .iter()
+                .any(|w| w.letter == 'G' && w.code() == 530);
+            let v = modal.units.to_mm(w.value);
+            bed = if g53 || modal.distance == DistanceMode::Absolute {
+                v
+            } else {
+                bed + v
+            };
+        }
+        let is_arc_source = source_is_arc
+            .get(tl.source_line - 1)
+            .copied()
+            .unwrap_or(false);
+        let tool = modal.tool.unwrap_or(0);
+        let line = tl.source_line as u32;
+
+        let (kind, start, points) = match &exec.motion {
+            Some(m) => {
+                let kind = match m.kind {
+                    MotionKind::Rapid => SegmentKind::Rapid,
+                    MotionKind::ArcCw | MotionKind::ArcCcw => SegmentKind::Arc,
+                    _ if is_arc_source => SegmentKind::Arc,
+                    _ => SegmentKind::Feed,
+                };
+                (kind, m.start, m.points(config.arc_tolerance_mm))
+            }
+            // Pure bed-axis move: the tool moves in part-space Y with the gantry still.
+            None if bed != bed_from => {
+                let kind = match modal.motion {
+                    MotionMode::Rapid => SegmentKind::Rapid,
+                    _ if is_arc_source => SegmentKind::Arc,
+                    _ => SegmentKind::Feed,
+                };
+                let p = interp.position();
+                (kind, p, vec![p])
+            }
+            None => continue,
+        };
+
+        let n = points.len() as f64;
+        let mut from = Point {
+            y: start.y + bed_from,
+            ..start
+        };
+        for (i, p) in points.into_iter().enumerate() {
+            let b = bed_from + (bed - bed_from) * (i + 1) as f64 / n;
+            let to = Point { y: p.y + b, ..p };
+            segments.push(Segment {
+                start: from,
+                end: to,
+                kind,
+                line,
+                tool,
+            });
+            from = to;
+        }
+    }
+
+    if let Some(max) = options.max_segments {
+        segments = decimate(segments, max);
+    }
+    into_buffers(segments)
+}
+
+/// Merge runs of short, connected segments with the same kind and tool until
+/// each merged segment is at least `total_length / max` long.
+fn decimate(segments: Vec<Segment>, max: usize) -> Vec<Segment> {
+    if max == 0 || segments.len() <= max {
+        return segments;
+    }
+    let total: f64 = segments.iter().map(|s| s.start.distance(&s.end)).sum();
+    let min_len = total / max as f64;
+    let mut out: Vec<Segment> = Vec::with_capacity(max);
+    let mut run: Option<Segment> = None;
+    for s in segments {
+        run = match run {
+            Some(mut r)
+                if r.kind == s.kind
+                    && r.tool == s.tool
+                    && r.end == s.start
+                    && r.start.distance(&r.end) < min_len =>
+            {
+                r.end = s.end;
+                Some(r)
+            }
+            Some(r) => {
+                out.push(r);
+                Some(s)
+            }
+            None => Some(s),
+        };
+    }
+    out.extend(run);
+    out
+}
+
+fn into_buffers(segments: Vec<Segment>) -> Toolpath {
+    let mut tp = Toolpath {
+        positions: Vec::with_capacity(segments.len() * 6),
+        kinds: Vec::with_capacity(segments.len()),
+        lines: Vec::with_capacity(segments.len()),
+        tools: Vec::with_capacity(segments.len()),
+        bounds_min: [f32::MAX; 3],
+        bounds_max: [f32::MIN; 3],
+    };
+    for s in &segments {
+        for p in [s.start, s.end] {
+            let xyz = [p.x as f32, p.y as f32, p.z as f32];
+            for (i, v) in xyz.iter().enumerate() {
+                tp.bounds_min[i] = tp.bounds_min[i].min(*v);
+                tp.bounds_max[i] = tp.bounds_max[i].max(*v);
+            }
+            tp.positions.extend_from_slice(&xyz);
+        }
+        tp.kinds.push(s.kind as u8);
+        tp.lines.push(s.line);
+        tp.tools.push(s.tool);
+    }
+    if segments.is_empty() {
+        tp.bounds_min = [0.0; 3];
+        tp.bounds_max = [0.0; 3];
+    }
+    tp
+}
+
+/// Read a G-code file and extract its toolpath.
+pub fn toolpath_file(
+    path: &Path,
+    config: &MotionConfig,
+    options: &ToolpathOptions,
+) -> std::io::Result<Toolpath> {
+    let content = std::fs::read_to_string(path)?;
+    let lines: Vec<&str> = content.lines().collect();
+    Ok(extract_toolpath(&lines, config, options))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn segment(tp: &Toolpath, i: usize) -> ([f32; 3], [f32; 3]) {
+        let p = &tp.positions[i * 6..i * 6 + 6];
+        ([p[0], p[1], p[2]], [p[3], p[4], p[5]])
+    }
+
+    #[test]
+    fn test_extract_basic() {
+        let lines = ["T3 M6", "G0 X10 Y10", "G1 Z-1 F100", "G2 X20 Y10 I5 J0"];
+        let tp = extract_toolpath(
+            &lines,
+            &MotionConfig::default(),
+            &ToolpathOptions::default(),
+        );
+        assert!(tp.len() > 3);
+        assert_eq!(tp.kinds[0], SegmentKind::Rapid as u8);
+        assert_eq!(tp.kinds[1], SegmentKind::Feed as u8);
+        assert_eq!(tp.kinds[2], SegmentKind::Arc as u8);
+        assert_eq!(tp.lines[0], 2);
+        assert_eq!(*tp.lines.last().unwrap(), 4);
+        assert!(tp.tools.iter().all(|&t| t == 3));
+        assert_eq!(segment(&tp, 0), ([0.0, 0.0, 0.0], [10.0, 10.0, 0.0]));
+        assert_eq!(tp.bounds_min[2], -1.0);
+        assert_eq!(tp.bounds_max[0], 20.0);
+    }
+
+    #[test]
+    fn test_extract_maps_bed_moves_to_part_space() {
+        let lines = ["G1 X10 Y700 F300"];
+        let tp = extract_toolpath(
+            &lines,
+            &MotionConfig::default(),
+            &ToolpathOptions::default(),
+        );
+        // Sequential split: gantry to the limit with X, then pure bed move.
+        assert_eq!(tp.len(), 2);
+        let (_, end0) = segment(&tp, 0);
+        let (start1, end1) = segment(&tp, 1);
+        assert_eq!(end0, start1);
+        assert!((end0[1] - 609.6).abs() < 1e-3);
+        assert!((end1[1] - 700.0).abs() < 1e-3);
+        assert_eq!(end1[0], 10.0);
+        assert_eq!(tp.lines, vec![1, 1]);
+    }
+
+    #[test]
+    fn test_decimation_limits_segments() {
+        let mut lines = vec!["G1 F1000".to_string()];
+        for i in 1..=1000 {
+            lines.push(format!("X{}", i as f64 * 0.1));
+        }
+        lines.push("G0 Z5".to_string());
+        let options = ToolpathOptions {
+            max_segments: Some(50),
+        };
+        let tp = extract_toolpath(&lines, &MotionConfig::default(), &options);
+        assert!(tp.len() <= 52);
+        // Path still ends at the same place.
+        let (_, end) = segment(&tp, tp.len() - 1);
+        assert_eq!(end, [100.0, 0.0, 5.0]);
+        let (_, feed_end) = segment(&tp, tp.len() - 2);
+        assert!((feed_end[0] - 100.0).abs() < 1e-3);
+    }
+}
@@ -0,0 +1,316 @@
+//! Feeds and speeds calculator for the Tools menu.
+//!
+//! Proposes spindle RPM, feed, plunge, stepdown and stepover for a library tool
+//! in a material. RPM comes from the material's surface speed, feed from a chip
+//! load per tooth that scales with tool diameter. When the machine cannot feed
+//! fast enough, RPM is lowered first so the chip load holds (thin chips rub and
+//! burn); the `notes` say which limits applied.
+
+use super::{MachineProfile, ToolEntry, ToolFeeds, ToolType};
+use serde::{Deserialize, Serialize};
+use std::f64::consts::PI;
+use thiserror::Error;
+
+/// Spindle range assumed when the profile has none (typical 2.2 kW router spindle).
+pub const DEFAULT_SPINDLE_RPM: [f64; 2] = [8000.0, 24000.0];
+
+/// Cutting data for a material, for carbide tools.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Material {
+    pub name: String,
+    /// Cutting (surface) speed in m/min.
+    pub surface_speed_m_min: f64,
+    /// Chip load per tooth as a fraction of tool diameter (mm per mm).
+    pub chip_load_per_diameter: f64,
+    /// Stepdown as a fraction of tool diameter.
+    pub stepdown_per_diameter: f64,
+    /// Stepover as a fraction of tool diameter.
+    pub stepover_per_diameter: f64,
+    /// Plunge rate as a fraction of the feed rate.
+    pub plunge_fraction: f64,
+}
+
+impl Material {
+    fn new(name: &str, vc: f64, chip: f64, stepdown: f64, stepover: f64, plunge: f64) -> Self {
+        Self {
+            name: name.to_string(),
+            surface_speed_m_min: vc,
+            chip_load_per_diameter: chip,
+            stepdown_per_diameter: stepdown,
+            stepover_per_diameter: stepover,
+            plunge_fraction: plunge,
+        }
+    }
+}
+
+/// Built-in material table (conservative values for a hobby router).
+pub fn materials() -> Vec<Material> {
+    vec![
+        Material::new("Softwood", 600.0, 0.015, 1.0, 0.45, 0.5),
+        Material::new("Hardwood", 500.0, 0.012, 0.5, 0.4, 0.4),
+        Material::new("MDF", 500.0, 0.015, 1.0, 0.45, 0.5),
+        Material::new("Plywood", 500.0, 0.013, 0.75, 0.4, 0.4),
+        Material::new("Acrylic", 300.0, 0.010, 0.5, 0.4, 0.3),
+        Material::new("HDPE", 400.0, 0.015, 0.75, 0.45, 0.4),
+        Material::new("Aluminum", 250.0, 0.004, 0.2, 0.3, 0.3),
+        Material::new("Brass", 150.0, 0.004, 0.2, 0.3, 0.3),
+    ]
+}
+
+/// Look up a built-in material by name (case-insensitive).
+pub fn find_material(name: &str) -> Option<Material> {
+    materials()
+        .into_iter()
+        .find(|m| m.name.eq_ignore_ascii_case(name))
+}
+
+/// Errors from [`calculate_feeds`].
+#[derive(Debug, Error, PartialEq)]
+pub enum FeedsError {
+    #[error("tool {0} has no diameter")]
+    MissingDiameter(u8),
+    #[error("invalid spindle range {min}-{max} RPM")]
+    InvalidSpindleRange { min: f64, max: f64 },
+}
+
+/// Proposed cutting data with the reasoning behind it.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct FeedsAndSpeeds {
+    pub tool: u8,
+    pub material: String,
+    pub spindle_rpm: f64,
+    pub feed_mm_min: f64,
+    pub plunge_mm_min: f64,
+    pub stepdown_mm: f64,
+    pub stepover_mm: f64,
+    /// Chip load per tooth the material calls for (mm).
+    pub target_chip_load_mm: f64,
+    /// Chip load per tooth at the proposed RPM and feed (mm).
+    pub chip_load_mm: f64,
+    /// Surface speed at the proposed RPM (m/min).
+    pub surface_speed_m_min: f64,
+    /// Limits and assumptions that changed the result.
+    pub notes: Vec<String>,
+}
+
+impl FeedsAndSpeeds {
+    /// As recommended feeds for storing on the tool.
+    pub fn tool_feeds(&self) -> ToolFeeds {
+        ToolFeeds {
+            spindle_rpm: Some(self.spindle_rpm),
+            feed_mm_min: Some(self.feed_mm_min),
+            plunge_mm_min: Some(self.plunge_mm_min),
+            stepdown_mm: Some(self.stepdown_mm),
+            stepover_mm: Some(self.stepover_mm),
+        }
+    }
+}
+
+/// Propose feeds and speeds for `tool` cutting `material` on the machine in `profile`.
+pub fn calculate_feeds(
+    tool: &ToolEntry,
+    material: &Material,
+    profile: &MachineProfile,
+) -> Result<FeedsAndSpeeds, FeedsError> {
+    let d = tool
+        .diameter_mm
+        .filter(|&d| d > 0.0 && d.is_finite())
+        .ok_or(FeedsError::MissingDiameter(tool.number))?;
+    let mut notes = Vec::new();
+    let flutes = match tool.flutes {
+        Some(n) if n > 0 => n as f64,
+        _ => {
+            notes.push("flute count unknown; assuming 2".to_string());
+            2.0
+        }
+    };
+    let [min_rpm, max_rpm] = match &profile.spindle {
+        Some(s) => [s.min_rpm, s.max_rpm],
+        None => {
+            notes.push(format!(
+                "spindle range not set in profile; assuming {:.0}-{:.0} RPM",
+                DEFAULT_SPINDLE_RPM[0], DEFAULT_SPINDLE_RPM[1]
+            ));
+            DEFAULT_SPINDLE_RPM
+        }
+    };
+    if !(min_rpm >= 0.0 && max_rpm > 0.0 && min_rpm <= max_rpm && max_rpm.is_finite()) {
+        return Err(FeedsError::InvalidSpindleRange {
+            min: min_rpm,
+            max: max_rpm,
+        });
+    }
+    // Slowest of X and Y, since a cut can run along either.
+    let (max_feed, max_z_feed) = match &profile.limits {
+        Some(l) => (
+            l.max_rate_mm_min[0].min(l.max_rate_mm_min[1]),
+            l.max_rate_mm_min[2],
+        ),
+        None => {
+            notes.push("machine limits unknown; feed is not capped".to_string());
+            (f64::INFINITY, f64::INFINITY)
+        }
+    };
+
+    let target_chip = material.chip_load_per_diameter * d;
+    let ideal_rpm = material.surface_speed_m_min * 1000.0 / (PI * d);
+    let mut rpm = ideal_rpm.clamp(min_rpm, max_rpm);
+    if ideal_rpm > max_rpm {
+        notes.push(format!(
+            "ideal {:.0} RPM is above the spindle maximum",
+            ideal_rpm
+        ));
+    } else if ideal_rpm < min_rpm {
+        notes.push(format!(
+            "ideal {:.0} RPM is below the spindle minimum; surface speed will be high",
+            ideal_rpm
+        ));
+    }
+    let mut feed = rpm * flutes * target_chip;
+    if feed > max_feed {
+        // Keep the chip load by slowing the spindle rather than thinning chips.
+        rpm = (max_feed / (flutes * target_chip)).max(min_rpm);
+        feed = (rpm * flutes * target_chip).min(max_feed);
+        notes.push(format!(
+            "feed capped at machine maximum {:.0} mm/min; RPM lowered to {:.0} to hold chip load",
+            max_feed, rpm
+        ));
+    }
+    let chip = feed / (rpm * flutes);
+    if chip < target_chip * 0.5 {
+        notes.push(format!(
+            "chip load {:.4} mm is under half the target; expect rubbing and heat",
+            chip
+        ));
+    }
+    let mut plunge = feed * material.plunge_fraction;
+    if plunge > max_z_feed {
+        plunge = max_z_feed;
+        notes.push(format!(
+            "plunge capped at Z maximum {:.0} mm/min",
+            max_z_feed
+        ));
+    }
+    let stepdown = material.stepdown_per_diameter * d;
+    let stepover = match tool.tool_type {
+        // Scallop height dominates for finishing with a ball.
+        ToolType::BallNose => {
+            notes.push("ball nose: stepover set for finishing (10% of diameter)".to_string());
+            0.1 * d
+        }
+        ToolType::VBit { .. } => {
+            notes.push("V-bit: diameter is the widest cut; values apply at full depth".to_string());
+            material.stepover_per_diameter * d
+        }
+        ToolType::Drill => {
+            notes.push("drill: use plunge rate and peck at the stepdown".to_string());
+            0.0
+        }
+        _ => material.stepover_per_diameter * d,
+    };
+
+    Ok(FeedsAndSpeeds {
+        tool: tool.number,
+        material: material.name.clone(),
+        spindle_rpm: rpm.round(),
+        feed_mm_min: feed.round(),
+        plunge_mm_min: plunge.round(),
+        stepdown_mm: stepdown,
+        stepover_mm: stepover,
+        target_chip_load_mm: target_chip,
+        chip_load_mm: chip,
+        surface_speed_m_min: PI * d * rpm / 1000.0,
+        notes,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::machines::grbl::MachineLimits;
+    use crate::machines::profiles::SpindleRange;
+
+    fn endmill(d: f64, flutes: u32) -> ToolEntry {
+        ToolEntry {
+            number: 1,
+            diameter_mm: Some(d),
+            flutes: Some(flutes),
+            ..ToolEntry::default()
+        }
+    }
+
+    fn profile(max_feed: f64) -> MachineProfile {
+        let mut p = MachineProfile::proverxl_4030();
+        p.spindle = Some(SpindleRange {
+            min_rpm: 10000.0,
+            max_rpm: 24000.0,
+        });
+        p.limits = Some(MachineLimits {
+            max_rate_mm_min: [max_feed, max_feed, 1000.0, 1000.0],
+            ..MachineLimits::default()
+        });
+        p
+    }
+
+    #[test]
+    fn test_chip_load_feed() {
+        let mdf = find_material("mdf").unwrap();
+        let r = calculate_feeds(&endmill(6.0, 2), &mdf, &profile(10000.0)).unwrap();
+        // 500 m/min on 6 mm is ~26500 RPM: clamped to 24000.
+        assert_eq!(r.spindle_rpm, 24000.0);
+        assert!((r.chip_load_mm - 0.09).abs() < 1e-9);
+        assert_eq!(r.feed_mm_min, 4320.0);
+        assert_eq!(r.plunge_mm_min, 1000.0);
+        assert_eq!(r.stepdown_mm, 6.0);
+        assert_eq!(r.notes.len(), 2);
+    }
+
+    #[test]
+    fn test_slow_machine_lowers_rpm() {
+        let mdf = find_material("MDF").unwrap();
+        let r = calculate_feeds(&endmill(6.0, 2), &mdf, &profile(3000.0)).unwrap();
+        assert_eq!(r.feed_mm_min, 3000.0);
+        assert!(r.spindle_rpm < 24000.0 && r.spindle_rpm >= 10000.0);
+        assert!((r.chip_load_mm - r.target_chip_load_mm).abs() < 1e-9);
+        // At the spindle minimum the chip thins out.
+        let r = calculate_feeds(&endmill(6.0, 2), &mdf, &profile(500.0)).unwrap();
+        assert_eq!(r.spindle_rpm, 10000.0);
+        assert!(r.notes.iter().any(|n| n.contains("rubbing")));
+    }
+
+    #[test]
+    fn test_missing_data() {
+        let alu = find_material("Aluminum").unwrap();
+        let tool = ToolEntry {
+            number: 4,
+            ..ToolEntry::default()
+        };
+        assert_eq!(
+            calculate_feeds(&tool, &alu, &MachineProfile::proverxl_4030()),
+            Err(FeedsError::MissingDiameter(4))
+        );
+        let tool = ToolEntry {
+            flutes: None,
+            ..endmill(3.175, 1)
+        };
+        let r = calculate_feeds(&tool, &alu, &MachineProfile::proverxl_4030()).unwrap();
+        assert_eq!(r.notes.len(), 4);
+        assert!(r.feed_mm_min.is_finite());
+    }
+
+    #[test]
+    fn test_invalid_spindle_range() {
+        let mdf = find_material("MDF").unwrap();
+        for (min, max) in [(24000.0, 10000.0), (f64::NAN, 24000.0), (0.0, 0.0)] {
+            let mut p = profile(3000.0);
+            p.spindle = Some(SpindleRange {
+                min_rpm: min,
+                max_rpm: max,
+            });
+            assert!(matches!(
+                calculate_feeds(&endmill(6.0, 2), &mdf, &p),
+                Err(FeedsError::InvalidSpindleRange { .. })
+            ));
+        }
+    }
+}
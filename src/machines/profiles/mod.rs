@@ -1,9 +1,18 @@
-//! Per-machine configuration: steps/mm, work area, and optional tool library.
+//! Per-machine configuration: steps/mm, work area, planner limits, and tool library.
 //!
-//! Used by the app for bounds checks, motion config, and tool management.
+//! Used by the app for bounds checks, motion config, job time estimates, and tool management.
 //! Does not depend on the serial feature.
 
+use crate::machines::grbl::{JobEvent, MachineLimits};
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::Path;
+
+mod feeds;
+mod tools;
+
+pub use feeds::*;
+pub use tools::*;
 
 /// Work envelope in mm (X, Y, Z). Used for UI and sanity checks.
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
@@ -40,21 +49,27 @@ impl Default for StepsPerMm {
     }
 }
 
-/// Single tool entry for the tool library (e.g. for probe length offset).
+/// Spindle speed range in RPM (GRBL `$31` / `$30`).
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
-pub struct ToolEntry {
-    pub number: u8,
-    pub description: String,
-    /// Tool length offset in mm (e.g. from probe); used for Z compensation.
-    pub length_offset_mm: Option<f64>,
+pub struct SpindleRange {
+    pub min_rpm: f64,
+    pub max_rpm: f64,
 }
 
-/// Per-machine profile: work area, steps/mm, and optional tool list.
+/// Per-machine profile: work area, steps/mm, planner limits, and optional tool list.
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct MachineProfile {
     pub name: String,
     pub work_area: WorkArea,
     pub steps_per_mm: StepsPerMm,
+    /// Max rates, accelerations and junction deviation for job time estimates.
+    /// `None` means read them from the controller (`$$`).
+    #[serde(default)]
+    pub limits: Option<MachineLimits>,
+    /// Spindle range for the feeds and speeds calculator. `None` assumes
+    /// [`DEFAULT_SPINDLE_RPM`].
+    #[serde(default)]
+    pub spindle: Option<SpindleRange>,
     pub tools: Vec<ToolEntry>,
 }
 
@@ -65,6 +80,8 @@ impl MachineProfile {
             name: "PROVerXL 4030".to_string(),
             work_area: WorkArea::new(609.6, 609.6, 609.6), // 24" each axis
             steps_per_mm: StepsPerMm::default(),
+            limits: None,
+            spindle: None,
             tools: Vec::new(),
         }
     }
@@ -73,6 +90,83 @@ impl MachineProfile {
     pub fn tool(&self, number: u8) -> Option<&ToolEntry> {
         self.tools.iter().find(|t| t.number == number)
     }
+
+    /// Known tool length offsets by tool number, for seeding a tool change session.
+    pub fn tool_offsets(&self) -> HashMap<u32, f64> {
+        self.tools
+            .iter()
+            .filter_map(|t| Some((t.number as u32, t.length_offset_mm?)))
+            .collect()
+    }
+
+    /// Store a measured length offset, adding the tool if it is not in the list.
+    pub fn record_tool_length(&mut self, number: u8, length_offset_mm: f64) {
+        match self.tools.iter_mut().find(|t| t.number == number) {
+            Some(t) => t.length_offset_mm = Some(length_offset_mm),
+            None => self.tools.push(ToolEntry {
+                number,
+                description: format!("T{}", number),
+                length_offset_mm: Some(length_offset_mm),
+                ..ToolEntry::default()
+            }),
+        }
+    }
+
+    /// Store the length a guided tool change measured ([`JobEvent::ToolMeasured`])
+    /// in the tool table. Returns true if the profile changed.
+    pub fn record_job_event(&mut self, event: &JobEvent) -> bool {
+        let JobEvent::ToolMeasured {
+            tool: Some(tool),
+            length_offset_mm,
+            ..
+        } = event
+        else {
+            return false;
+        };
+        match u8::try_from(*tool) {
+            Ok(number) => {
+                self.record_tool_length(number, *length_offset_mm);
+                true
+            }
+            Err(_) => false,
+        }
+    }
+
+    /// Add a tool, or replace the one with the same number.
+    pub fn upsert_tool(&mut self, tool: ToolEntry) {
+        upsert_tool(&mut self.tools, tool);
+    }
+
+    /// Remove a tool by number. Returns it if it was in the list.
+    pub fn remove_tool(&mut self, number: u8) -> Option<ToolEntry> {
+        let i = self.tools.iter().position(|t| t.number == number)?;
+        Some(self.tools.remove(i))
+    }
+
+    /// Merge imported tools; imported entries replace same-numbered ones but keep
+    /// a measured length offset if the import has none.
+    pub fn merge_tools(&mut self, imported: Vec<ToolEntry>) {
+        merge_tools(&mut self.tools, imported);
+    }
+
+    /// Save as JSON. Writes a temporary file first so a crash never leaves a
+    /// truncated profile behind.
+    pub fn save(&self, path: &Path) -> std::io::Result<()> {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let json = serde_json::to_string_pretty(self).map_err(std::io::Error::other)?;
+        let tmp = path.with_extension("json.tmp");
+        std::fs::write(&tmp, json)?;
+        std::fs::rename(&tmp, path)
+    }
+
+    /// Load a profile saved with [`MachineProfile::save`].
+    pub fn load(path: &Path) -> std::io::Result<Self> {
+        let text = std::fs::read_to_string(path)?;
+        serde_json::from_str(&text)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))
+    }
 }
 
 #[cfg(test)]
@@ -88,6 +182,14 @@ mod tests {
         assert_eq!(p.work_area.x_mm, p2.work_area.x_mm);
     }
 
+    #[test]
+    fn test_profile_without_limits_deserializes() {
+        let json = r#"{"name":"Old","work_area":{"x_mm":1.0,"y_mm":1.0,"z_mm":1.0},
+            "steps_per_mm":{"x":80.0,"y":80.0,"z":80.0,"a":null},"tools":[]}"#;
+        let p: MachineProfile = serde_json::from_str(json).unwrap();
+        assert!(p.limits.is_none());
+    }
+
     #[test]
     fn test_tool_lookup() {
         let mut p = MachineProfile::proverxl_4030();
@@ -95,10 +197,70 @@ mod tests {
             number: 1,
             description: "6mm endmill".to_string(),
             length_offset_mm: Some(45.2),
+            ..ToolEntry::default()
         });
         let t = p.tool(1).unwrap();
         assert_eq!(t.description, "6mm endmill");
         assert_eq!(t.length_offset_mm, Some(45.2));
         assert!(p.tool(2).is_none());
     }
+
+    #[test]
+    fn test_record_tool_length() {
+        let mut p = MachineProfile::proverxl_4030();
+        p.record_tool_length(3, 1.5);
+        p.record_tool_length(3, 2.5);
+        p.record_tool_length(4, -0.5);
+        assert_eq!(p.tools.len(), 2);
+        assert_eq!(p.tool(3).unwrap().length_offset_mm, Some(2.5));
+        assert_eq!(p.tool_offsets()[&4], -0.5);
+    }
+
+    #[test]
+    fn test_record_job_event() {
+        let mut p = MachineProfile::proverxl_4030();
+        let measured = |tool| JobEvent::ToolMeasured {
+            line: 12,
+            tool,
+            length_offset_mm: 3.25,
+        };
+        assert!(p.record_job_event(&measured(Some(5))));
+        assert!(!p.record_job_event(&measured(None)));
+        assert!(!p.record_job_event(&measured(Some(300))));
+        assert!(!p.record_job_event(&JobEvent::ProgramEnded { line: 20 }));
+        assert_eq!(p.tool_offsets(), HashMap::from([(5, 3.25)]));
+
+        let path =
+            std::env::temp_dir().join(format!("grbl-rs-profile-{}.json", std::process::id()));
+        p.save(&path).unwrap();
+        let loaded = MachineProfile::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(loaded.tool(5), p.tool(5));
+    }
+
+    #[test]
+    fn test_merge_tools_keeps_measured_offset() {
+        let mut p = MachineProfile::proverxl_4030();
+        p.record_tool_length(2, 1.5);
+        p.merge_tools(vec![
+            ToolEntry {
+                number: 2,
+                description: "1/8 ball".to_string(),
+                tool_type: ToolType::BallNose,
+                ..ToolEntry::default()
+            },
+            ToolEntry {
+                number: 1,
+                ..ToolEntry::default()
+            },
+        ]);
+        assert_eq!(
+            p.tools.iter().map(|t| t.number).collect::<Vec<_>>(),
+            vec![1, 2]
+        );
+        assert_eq!(p.tool(2).unwrap().description, "1/8 ball");
+        assert_eq!(p.tool(2).unwrap().length_offset_mm, Some(1.5));
+        assert!(p.remove_tool(1).is_some());
+        assert!(p.remove_tool(1).is_none());
+    }
 }
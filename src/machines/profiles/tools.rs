@@ -0,0 +1,690 @@
+//! Tool library: tool geometry, recommended cutting data, and import/export.
+//!
+//! Tools are stored in the profile's `tools` list and can be saved on their own as
+//! a JSON library file. Import reads CSV, our JSON, Fusion 360 tool libraries
+//! (exported `.json` or the zipped `.tools` archive) and (with the `vectric`
+//! feature) Vectric `.vtdb` databases.
+//! Imported lengths and feeds are converted to mm and mm/min.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::io::Read;
+use std::path::Path;
+use thiserror::Error;
+
+const MM_PER_INCH: f64 = 25.4;
+
+/// Cutter shape.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum ToolType {
+    #[default]
+    Endmill,
+    BallNose,
+    /// V-bit or chamfer mill; `angle_deg` is the included angle.
+    VBit {
+        angle_deg: f64,
+    },
+    Drill,
+    Other,
+}
+
+/// Recommended cutting data for a tool (mm, mm/min, RPM).
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct ToolFeeds {
+    pub spindle_rpm: Option<f64>,
+    pub feed_mm_min: Option<f64>,
+    pub plunge_mm_min: Option<f64>,
+    pub stepdown_mm: Option<f64>,
+    pub stepover_mm: Option<f64>,
+}
+
+/// Single tool entry for the tool library.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct ToolEntry {
+    pub number: u8,
+    pub description: String,
+    /// Tool length offset in mm (e.g. from probe); used for Z compensation.
+    pub length_offset_mm: Option<f64>,
+    #[serde(default)]
+    pub diameter_mm: Option<f64>,
+    #[serde(default)]
+    pub flutes: Option<u32>,
+    #[serde(default)]
+    pub tool_type: ToolType,
+    /// Cutter material (e.g. "carbide", "HSS").
+    #[serde(default)]
+    pub material: Option<String>,
+    #[serde(default)]
+    pub feeds: Option<ToolFeeds>,
+}
+
+/// Errors from reading or writing tool libraries.
+#[derive(Debug, Error)]
+pub enum ToolLibraryError {
+    #[error("io: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("json: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("csv line {line}: {message}")]
+    Csv { line: usize, message: String },
+    #[error("fusion 360 library: {0}")]
+    Fusion(String),
+    #[error("fusion 360 archive: {0}")]
+    Archive(#[from] zip::result::ZipError),
+    #[error("tool number {number} of {description:?} is out of range (0-255)")]
+    NumberOutOfRange { number: i64, description: String },
+    #[error("unsupported tool library format: {0}")]
+    UnsupportedFormat(String),
+    #[cfg(feature = "vectric")]
+    #[error("vectric database: {0}")]
+    Vectric(#[from] rusqlite::Error),
+}
+
+fn tool_number(number: i64, description: &str) -> Result<u8, ToolLibraryError> {
+    u8::try_from(number).map_err(|_| ToolLibraryError::NumberOutOfRange {
+        number,
+        description: description.to_string(),
+    })
+}
+
+/// Add a tool to a library, or replace the one with the same number. Keeps the
+/// library sorted by number.
+pub fn upsert_tool(tools: &mut Vec<ToolEntry>, tool: ToolEntry) {
+    match tools.iter_mut().find(|t| t.number == tool.number) {
+        Some(t) => *t = tool,
+        None => {
+            tools.push(tool);
+            tools.sort_by_key(|t| t.number);
+        }
+    }
+}
+
+/// Merge imported tools into a library; imported entries replace same-numbered
+/// ones but keep a measured length offset if the import has none.
+pub fn merge_tools(tools: &mut Vec<ToolEntry>, imported: Vec<ToolEntry>) {
+    for mut tool in imported {
+        if tool.length_offset_mm.is_none() {
+            tool.length_offset_mm = tools
+                .iter()
+                .find(|t| t.number == tool.number)
+                .and_then(|t| t.length_offset_mm);
+        }
+        upsert_tool(tools, tool);
+    }
+}
+
+/// Load a saved tool library; a missing file is an empty library.
+pub fn load_tool_library(path: &Path) -> Result<Vec<ToolEntry>, ToolLibraryError> {
+    match std::fs::read_to_string(path) {
+        Ok(text) => import_tools_json(&text),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Vec::new()),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Save a tool library as JSON. Writes a temporary file first so a crash never
+/// leaves a truncated library behind.
+pub fn save_tool_library(path: &Path, tools: &[ToolEntry]) -> Result<(), ToolLibraryError> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    let tmp = path.with_extension("json.tmp");
+    std::fs::write(&tmp, export_tools_json(tools)?)?;
+    std::fs::rename(&tmp, path)?;
+    Ok(())
+}
+
+/// Our tool list or a Fusion 360 library, told apart by the content.
+fn import_any_json(text: &str) -> Result<Vec<ToolEntry>, ToolLibraryError> {
+    let value: Value = serde_json::from_str(text)?;
+    if value.get("data").is_some() {
+        import_fusion360(text)
+    } else {
+        Ok(serde_json::from_value(value)?)
+    }
+}
+
+/// The library JSON inside a Fusion 360 `.tools` file (a zip archive holding
+/// `tool.json`).
+fn read_fusion360_archive(path: &Path) -> Result<String, ToolLibraryError> {
+    let mut archive = zip::ZipArchive::new(std::fs::File::open(path)?)?;
+    let name = archive
+        .file_names()
+        .find(|n| n.to_ascii_lowercase().ends_with(".json"))
+        .map(str::to_string)
+        .ok_or_else(|| ToolLibraryError::Fusion("archive has no tool library JSON".to_string()))?;
+    let mut text = String::new();
+    archive.by_name(&name)?.read_to_string(&mut text)?;
+    Ok(text)
+}
+
+/// Import tools from a file, picking the format from the extension (`.csv`,
+/// `.json`, `.tools`, `.vtdb`) and, for JSON, the content (our list or a Fusion
+/// 360 library).
+pub fn import_tools_file(path: &Path) -> Result<Vec<ToolEntry>, ToolLibraryError> {
+    let ext = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .unwrap_or_default()
+        .to_ascii_lowercase();
+    match ext.as_str() {
+        "csv" => import_tools_csv(&std::fs::read_to_string(path)?),
+        "json" => import_any_json(&std::fs::read_to_string(path)?),
+        "tools" => import_fusion360(&read_fusion360_archive(path)?),
+        #[cfg(feature = "vectric")]
+        "vtdb" => import_vectric(path),
+        _ => Err(ToolLibraryError::UnsupportedFormat(
+            path.display().to_string(),
+        )),
+    }
+}
+
+/// Tools as pretty-printed JSON.
+pub fn export_tools_json(tools: &[ToolEntry]) -> Result<String, ToolLibraryError> {
+    Ok(serde_json::to_string_pretty(tools)?)
+}
+
+pub fn import_tools_json(text: &str) -> Result<Vec<ToolEntry>, ToolLibraryError> {
+    Ok(serde_json::from_str(text)?)
+}
+
+const CSV_HEADER: [&str; 13] = [
+    "number",
+    "description",
+    "type",
+    "angle_deg",
+    "diameter_mm",
+    "flutes",
+    "material",
+    "length_offset_mm",
+    "spindle_rpm",
+    "feed_mm_min",
+    "plunge_mm_min",
+    "stepdown_mm",
+    "stepover_mm",
+];
+
+fn csv_field(s: &str) -> String {
+    if s.contains([',', '"', '\n']) {
+        format!("\"{}\"", s.replace('"', "\"\""))
+    } else {
+        s.to_string()
+    }
+}
+
+fn opt<T: ToString>(v: Option<T>) -> String {
+    v.map(|v| v.to_string()).unwrap_or_default()
+}
+
+/// Tools as CSV with a header row (empty cells for unknown values).
+pub fn export_tools_csv(tools: &[ToolEntry]) -> String {
+    let mut out = CSV_HEADER.join(",");
+    out.push('\n');
+    for t in tools {
+        let (kind, angle) = match t.tool_type {
+            ToolType::Endmill => ("endmill", None),
+            ToolType::BallNose => ("ball", None),
+            ToolType::VBit { angle_deg } => ("vbit", Some(angle_deg)),
+            ToolType::Drill => ("drill", None),
+            ToolType::Other => ("other", None),
+        };
+        let feeds = t.feeds.clone().unwrap_or_default();
+        let row = [
+            t.number.to_string(),
+            csv_field(&t.description),
+            kind.to_string(),
+            opt(angle),
+            opt(t.diameter_mm),
+            opt(t.flutes),
+            csv_field(t.material.as_deref().unwrap_or_default()),
+            opt(t.length_offset_mm),
+            opt(feeds.spindle_rpm),
+            opt(feeds.feed_mm_min),
+            opt(feeds.plunge_mm_min),
+            opt(feeds.stepdown_mm),
+            opt(feeds.stepover_mm),
+        ];
+        out.push_str(&row.join(","));
+        out.push('\n');
+    }
+    out
+}
+
+/// Split CSV text into records, honouring `"..."` quoting with `""` escapes;
+/// quoted fields may span lines. Each record comes with the (1-based) line it
+/// starts on. Blank lines are skipped.
+fn csv_records(text: &str) -> Vec<(usize, Vec<String>)> {
+    let mut records = Vec::new();
+    let mut fields = vec![String::new()];
+    let (mut line, mut start) = (1, 1);
+    let mut quoted = false;
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match (c, quoted) {
+            ('"', true) if chars.peek() == Some(&'"') => {
+                chars.next();
+                fields.last_mut().unwrap().push('"');
+            }
+            ('"', _) => quoted = !quoted,
+            (',', false) => fields.push(String::new()),
+            ('\r', false) if chars.peek() == Some(&'\n') => {}
+            ('\n', false) => {
+                let record = std::mem::replace(&mut fields, vec![String::new()]);
+                if record.len() > 1 || !record[0].trim().is_empty() {
+                    records.push((start, record));
+                }
+                line += 1;
+                start = line;
+            }
+            ('\n', true) => {
+                line += 1;
+                fields.last_mut().unwrap().push(c);
+            }
+            _ => fields.last_mut().unwrap().push(c),
+        }
+    }
+    if fields.len() > 1 || !fields[0].trim().is_empty() {
+        records.push((start, fields));
+    }
+    records
+}
+
+/// Import tools from CSV. Columns are matched by header name, so any subset of
+/// [`export_tools_csv`]'s columns in any order works; `number` is required.
+pub fn import_tools_csv(text: &str) -> Result<Vec<ToolEntry>, ToolLibraryError> {
+    let mut records = csv_records(text).into_iter();
+    let Some((_, header)) = records.next() else {
+        return Ok(Vec::new());
+    };
+    let header: Vec<String> = header
+        .iter()
+        .map(|h| h.trim().to_ascii_lowercase())
+        .collect();
+    if !header.iter().any(|h| h == "number") {
+        return Err(ToolLibraryError::Csv {
+            line: 1,
+            message: "missing \"number\" column".to_string(),
+        });
+    }
+    let mut tools = Vec::new();
+    for (line_no, fields) in records {
+        let get = |name: &str| {
+            header
+                .iter()
+                .position(|h| h == name)
+                .and_then(|j| fields.get(j))
+                .map(|f| f.trim())
+                .filter(|f| !f.is_empty())
+        };
+        let num = |name: &str| -> Result<Option<f64>, ToolLibraryError> {
+            get(name)
+                .map(|v| {
+                    v.parse::<f64>().map_err(|_| ToolLibraryError::Csv {
+                        line: line_no,
+                        message: format!("{}: invalid number {:?}", name, v),
+                    })
+                })
+                .transpose()
+        };
+        let description = get("description").unwrap_or_default().to_string();
+        let number = num("number")?.ok_or(ToolLibraryError::Csv {
+            line: line_no,
+            message: "missing tool number".to_string(),
+        })?;
+        let tool_type = match get("type").map(|t| t.to_ascii_lowercase()).as_deref() {
+            None | Some("endmill") => ToolType::Endmill,
+            Some("ball") => ToolType::BallNose,
+            Some("vbit") => ToolType::VBit {
+                angle_deg: num("angle_deg")?.unwrap_or(90.0),
+            },
+            Some("drill") => ToolType::Drill,
+            Some(_) => ToolType::Other,
+        };
+        let feeds = ToolFeeds {
+            spindle_rpm: num("spindle_rpm")?,
+            feed_mm_min: num("feed_mm_min")?,
+            plunge_mm_min: num("plunge_mm_min")?,
+            stepdown_mm: num("stepdown_mm")?,
+            stepover_mm: num("stepover_mm")?,
+        };
+        tools.push(ToolEntry {
+            number: tool_number(number as i64, &description)?,
+            length_offset_mm: num("length_offset_mm")?,
+            diameter_mm: num("diameter_mm")?,
+            flutes: num("flutes")?.map(|f| f as u32),
+            tool_type,
+            material: get("material").map(str::to_string),
+            feeds: (feeds != ToolFeeds::default()).then_some(feeds),
+            description,
+        });
+    }
+    Ok(tools)
+}
+
+/// Give imported tools their numbers; tools without one (or with 0) are numbered
+/// after the highest numbered one.
+fn number_tools(rows: Vec<(Option<i64>, ToolEntry)>) -> Result<Vec<ToolEntry>, ToolLibraryError> {
+    let mut next = rows.iter().filter_map(|(n, _)| *n).max().unwrap_or(0) + 1;
+    let mut tools = Vec::with_capacity(rows.len());
+    for (number, mut entry) in rows {
+        let number = number.filter(|&n| n > 0).unwrap_or_else(|| {
+            next += 1;
+            next - 1
+        });
+        entry.number = tool_number(number, &entry.description)?;
+        tools.push(entry);
+    }
+    Ok(tools)
+}
+
+/// Import a Fusion 360 tool library (`.json` export). Holders, probes and other
+/// non-cutting entries are skipped; the first preset supplies the feeds. Tools
+/// without a post-process number are numbered after the highest numbered one.
+pub fn import_fusion360(text: &str) -> Result<Vec<ToolEntry>, ToolLibraryError> {
+    let root: Value = serde_json::from_str(text)?;
+    let data = root
+        .get("data")
+        .and_then(Value::as_array)
+        .ok_or_else(|| ToolLibraryError::Fusion("no \"data\" array".to_string()))?;
+    let mut tools = Vec::new();
+    for tool in data {
+        let kind = tool.get("type").and_then(Value::as_str).unwrap_or_default();
+        if matches!(kind, "holder" | "shaft" | "probe") {
+            continue;
+        }
+        let scale = match tool.get("unit").and_then(Value::as_str) {
+            Some("inches") => MM_PER_INCH,
+            _ => 1.0,
+        };
+        let geometry = tool.get("geometry");
+        let geo = |key: &str| geometry.and_then(|g| g.get(key)).and_then(Value::as_f64);
+        let tool_type = match kind {
+            "flat end mill" | "bull nose end mill" | "face mill" => ToolType::Endmill,
+            "ball end mill" | "lollipop mill" => ToolType::BallNose,
+            // TA is the half angle of the taper.
+            "chamfer mill" | "engrave" => ToolType::VBit {
+                angle_deg: geo("TA").map_or(90.0, |ta| ta * 2.0),
+            },
+            "drill" | "spot drill" | "center drill" => ToolType::Drill,
+            _ => ToolType::Other,
+        };
+        let description = tool
+            .get("description")
+            .and_then(Value::as_str)
+            .unwrap_or(kind)
+            .to_string();
+        let number = tool
+            .get("post-process")
+            .and_then(|p| p.get("number"))
+            .and_then(Value::as_i64);
+        let preset = tool
+            .get("start-values")
+            .and_then(|s| s.get("presets"))
+            .and_then(Value::as_array)
+            .and_then(|p| p.first());
+        let feeds = preset.map(|p| {
+            let val = |key: &str| p.get(key).and_then(Value::as_f64);
+            ToolFeeds {
+                spindle_rpm: val("n"),
+                feed_mm_min: val("v_f").map(|v| v * scale),
+                plunge_mm_min: val("v_f_plunge").map(|v| v * scale),
+                stepdown_mm: val("stepdown").map(|v| v * scale),
+                stepover_mm: val("stepover").map(|v| v * scale),
+            }
+        });
+        tools.push((
+            number,
+            ToolEntry {
+                number: 0,
+                length_offset_mm: None,
+                diameter_mm: geo("DC").map(|d| d * scale),
+                flutes: geo("NOF").map(|n| n as u32),
+                tool_type,
+                material: tool.get("BMC").and_then(Value::as_str).map(str::to_string),
+                feeds,
+                description,
+            },
+        ));
+    }
+    number_tools(tools)
+}
+
+/// Import a Vectric tool database (`.vtdb`, VCarve/Aspire 10 and later). Tools
+/// without a tool number are numbered after the highest numbered one.
+#[cfg(feature = "vectric")]
+pub fn import_vectric(path: &Path) -> Result<Vec<ToolEntry>, ToolLibraryError> {
+    use rusqlite::{Connection, OpenFlags};
+
+    let db = Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+    let mut stmt = db.prepare(
+        "SELECT g.name_format, g.tool_type, g.units, g.diameter, g.included_angle,
+                g.num_flutes, c.tool_number, c.rate_units, c.spindle_speed, c.feed_rate,
+                c.plunge_rate, c.stepdown, c.stepover
+         FROM tool_geometry g
+         LEFT JOIN tool_entity e ON e.tool_geometry_id = g.id
+         LEFT JOIN tool_cutting_data c ON c.id = e.tool_cutting_data_id
+         GROUP BY g.id
+         ORDER BY g.id",
+    )?;
+    let rows = stmt.query_map([], |r| {
+        let scale = if r.get::<_, Option<i64>>(2)? == Some(1) {
+            MM_PER_INCH
+        } else {
+            1.0
+        };
+        // rate_units: 0 mm/s, 1 mm/min, 2 m/min, 3 in/s, 4 in/min, 5 ft/min.
+        let rate = match r.get::<_, Option<i64>>(7)? {
+            Some(0) => 60.0,
+            Some(2) => 1000.0,
+            Some(3) => MM_PER_INCH * 60.0,
+            Some(4) => MM_PER_INCH,
+            Some(5) => MM_PER_INCH * 12.0,
+            _ => 1.0,
+        };
+        let mm =
+            |i: usize| Ok::<_, rusqlite::Error>(r.get::<_, Option<f64>>(i)?.map(|v| v * scale));
+        let feed =
+            |i: usize| Ok::<_, rusqlite::Error>(r.get::<_, Option<f64>>(i)?.map(|v| v * rate));
+        let tool_type = match r.get::<_, Option<i64>>(1)? {
+            Some(0) => ToolType::BallNose,
+            Some(1) | Some(2) => ToolType::Endmill,
+            Some(3) => ToolType::VBit {
+                angle_deg: r.get::<_, Option<f64>>(4)?.unwrap_or(90.0),
+            },
+            Some(6) => ToolType::Drill,
+            _ => ToolType::Other,
+        };
+        let feeds = ToolFeeds {
+            spindle_rpm: r.get(8)?,
+            feed_mm_min: feed(9)?,
+            plunge_mm_min: feed(10)?,
+            stepdown_mm: mm(11)?,
+            stepover_mm: mm(12)?,
+        };
+        let entry = ToolEntry {
+            number: 0,
+            description: r.get::<_, Option<String>>(0)?.unwrap_or_default(),
+            length_offset_mm: None,
+            diameter_mm: mm(3)?,
+            flutes: r.get::<_, Option<i64>>(5)?.map(|n| n as u32),
+            tool_type,
+            material: None,
+            feeds: (feeds != ToolFeeds::default()).then_some(feeds),
+        };
+        Ok((r.get::<_, Option<i64>>(6)?, entry))
+    })?;
+    number_tools(rows.collect::<Result<Vec<_>, _>>()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vbit() -> ToolEntry {
+        ToolEntry {
+            number: 3,
+            description: "60°, \"sharp\" V-bit".to_string(),
+            diameter_mm: Some(12.7),
+            flutes: Some(2),
+            tool_type: ToolType::VBit { angle_deg: 60.0 },
+            material: Some("carbide".to_string()),
+            feeds: Some(ToolFeeds {
+                spindle_rpm: Some(18000.0),
+                feed_mm_min: Some(1500.0),
+                ..ToolFeeds::default()
+            }),
+            ..ToolEntry::default()
+        }
+    }
+
+    #[test]
+    fn test_csv_roundtrip() {
+        let tools = vec![
+            vbit(),
+            ToolEntry {
+                number: 1,
+                description: "6mm endmill".to_string(),
+                length_offset_mm: Some(-1.25),
+                ..ToolEntry::default()
+            },
+        ];
+        let csv = export_tools_csv(&tools);
+        assert!(csv.starts_with("number,description,type,"));
+        assert_eq!(import_tools_csv(&csv).unwrap(), tools);
+    }
+
+    #[test]
+    fn test_csv_columns_by_name() {
+        let tools = import_tools_csv("Description,Number,type\n1/4 ball,5,ball\n").unwrap();
+        assert_eq!(tools[0].number, 5);
+        assert_eq!(tools[0].tool_type, ToolType::BallNose);
+        let err = import_tools_csv("number,flutes\n1,two\n").unwrap_err();
+        assert!(matches!(err, ToolLibraryError::Csv { line: 2, .. }));
+        assert!(import_tools_csv("number\n300\n").is_err());
+    }
+
+    #[test]
+    fn test_csv_multiline_description() {
+        let tools = vec![ToolEntry {
+            number: 2,
+            description: "1/8 \"ball\"\nshort, stubby".to_string(),
+            ..ToolEntry::default()
+        }];
+        let csv = export_tools_csv(&tools);
+        assert_eq!(import_tools_csv(&csv).unwrap(), tools);
+        // Line numbers in errors count the lines inside quoted fields.
+        let err = import_tools_csv("number,description\r\n1,\"a\nb\"\r\nx,c\r\n").unwrap_err();
+        assert!(matches!(err, ToolLibraryError::Csv { line: 4, .. }));
+    }
+
+    #[test]
+    fn test_json_roundtrip_and_old_entries() {
+        let tools = vec![vbit()];
+        let json = export_tools_json(&tools).unwrap();
+        assert_eq!(import_tools_json(&json).unwrap(), tools);
+        // Entries saved before the library had geometry still load.
+        let old = r#"[{"number":1,"description":"old","length_offset_mm":null}]"#;
+        let t = &import_tools_json(old).unwrap()[0];
+        assert_eq!(t.tool_type, ToolType::Endmill);
+        assert!(t.diameter_mm.is_none());
+    }
+
+    #[test]
+    fn test_fusion360_import() {
+        let lib = r#"{"data":[
+            {"type":"holder","description":"BT30"},
+            {"type":"flat end mill","unit":"inches","description":"1/4 endmill","BMC":"carbide",
+             "geometry":{"DC":0.25,"NOF":3},"post-process":{"number":4},
+             "start-values":{"presets":[{"n":18000,"v_f":60,"v_f_plunge":20,"stepdown":0.1}]}},
+            {"type":"chamfer mill","unit":"millimeters","description":"90 deg",
+             "geometry":{"DC":12,"TA":45},"post-process":{"number":7}}
+        ]}"#;
+        let tools = import_fusion360(lib).unwrap();
+        assert_eq!(tools.len(), 2);
+        assert_eq!(tools[0].number, 4);
+        assert_eq!(tools[0].diameter_mm, Some(6.35));
+        assert_eq!(tools[0].flutes, Some(3));
+        let feeds = tools[0].feeds.as_ref().unwrap();
+        assert_eq!(feeds.spindle_rpm, Some(18000.0));
+        assert_eq!(feeds.feed_mm_min, Some(1524.0));
+        assert_eq!(tools[1].tool_type, ToolType::VBit { angle_deg: 90.0 });
+        assert!(import_fusion360("[]").is_err());
+
+        let unnumbered = r#"{"data":[
+            {"type":"drill","description":"3mm drill"},
+            {"type":"flat end mill","description":"6mm","post-process":{"number":5}},
+            {"type":"ball end mill","description":"3mm ball"}
+        ]}"#;
+        let numbers: Vec<u8> = import_fusion360(unnumbered)
+            .unwrap()
+            .iter()
+            .map(|t| t.number)
+            .collect();
+        assert_eq!(numbers, vec![6, 5, 7]);
+    }
+
+    #[test]
+    fn test_library_file() {
+        let dir = std::env::temp_dir().join(format!("grbl-rs-tools-{}", std::process::id()));
+        let path = dir.join("tools.json");
+        assert!(load_tool_library(&path).unwrap().is_empty());
+        save_tool_library(&path, &[vbit()]).unwrap();
+        assert_eq!(load_tool_library(&path).unwrap(), vec![vbit()]);
+        assert_eq!(import_tools_file(&path).unwrap(), vec![vbit()]);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_fusion360_tools_archive() {
+        use std::io::Write;
+        let path = std::env::temp_dir().join(format!("grbl-rs-{}.tools", std::process::id()));
+        let mut zip = zip::ZipWriter::new(std::fs::File::create(&path).unwrap());
+        zip.start_file("tool.json", zip::write::SimpleFileOptions::default())
+            .unwrap();
+        zip.write_all(
+            br#"{"data":[{"type":"ball end mill","unit":"millimeters","description":"3mm ball",
+                "geometry":{"DC":3,"NOF":2},"post-process":{"number":9}}]}"#,
+        )
+        .unwrap();
+        zip.finish().unwrap();
+        let tools = import_tools_file(&path).unwrap();
+        // Plain JSON under the archive's extension is not a Fusion 360 archive.
+        std::fs::write(&path, "[]").unwrap();
+        let err = import_tools_file(&path).unwrap_err();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(tools.len(), 1);
+        assert_eq!(tools[0].number, 9);
+        assert_eq!(tools[0].tool_type, ToolType::BallNose);
+        assert!(matches!(err, ToolLibraryError::Archive(_)));
+    }
+
+    #[cfg(feature = "vectric")]
+    #[test]
+    fn test_vectric_import() {
+        let path = std::env::temp_dir().join(format!("grbl-rs-{}.vtdb", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let db = rusqlite::Connection::open(&path).unwrap();
+        db.execute_batch(
+            "CREATE TABLE tool_geometry (id INTEGER PRIMARY KEY, name_format TEXT, tool_type INTEGER,
+                 units INTEGER, diameter REAL, included_angle REAL, num_flutes INTEGER);
+             CREATE TABLE tool_cutting_data (id INTEGER PRIMARY KEY, tool_number INTEGER,
+                 rate_units INTEGER, spindle_speed REAL, feed_rate REAL, plunge_rate REAL,
+                 stepdown REAL, stepover REAL);
+             CREATE TABLE tool_entity (id INTEGER PRIMARY KEY, tool_geometry_id INTEGER,
+                 tool_cutting_data_id INTEGER);
+             INSERT INTO tool_geometry VALUES (1, 'End Mill (1/4\")', 1, 1, 0.25, NULL, 2);
+             INSERT INTO tool_geometry VALUES (2, 'V-Bit (60°)', 3, 0, 12.0, 60.0, 2);
+             INSERT INTO tool_cutting_data VALUES (1, 2, 4, 18000, 100, 30, 0.125, 0.1);
+             INSERT INTO tool_entity VALUES (1, 1, 1);",
+        )
+        .unwrap();
+        drop(db);
+        let tools = import_tools_file(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(tools[0].number, 2);
+        assert_eq!(tools[0].diameter_mm, Some(6.35));
+        assert_eq!(tools[0].feeds.as_ref().unwrap().feed_mm_min, Some(2540.0));
+        assert_eq!(tools[1].number, 3);
+        assert_eq!(tools[1].tool_type, ToolType::VBit { angle_deg: 60.0 });
+    }
+}
@@ -0,0 +1,50 @@
+//! Session header: what was run, on which machine and where, written as the
+//! first event of every session.
+
+use crate::machines::grbl::GrblSettings;
+use crate::machines::profiles::MachineProfile;
+use serde::{Deserialize, Serialize};
+
+/// Context of a session, recorded in its [`super::SessionEvent::Header`].
+/// [`SessionHeader::new`] fills in the library version and host name; the rest
+/// comes from the app or `GrblMachine::session_header`.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct SessionHeader {
+    /// Operator-provided job name; `{job}` in the file name template.
+    pub job_name: Option<String>,
+    pub machine_profile: Option<String>,
+    /// Build info lines from `$I` (`[VER:...]`, `[OPT:...]`, ...).
+    pub controller_info: Vec<String>,
+    /// [`GrblSettings::hash`] of the settings at session start.
+    pub settings_hash: Option<String>,
+    /// Version of this library.
+    pub version: String,
+    /// Name and version of the app, if it sets them.
+    pub app: Option<String>,
+    pub host: String,
+}
+
+impl SessionHeader {
+    pub fn new() -> Self {
+        Self {
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            host: gethostname::gethostname().to_string_lossy().into_owned(),
+            ..Self::default()
+        }
+    }
+
+    pub fn with_job_name(mut self, name: &str) -> Self {
+        self.job_name = Some(name.trim().to_string()).filter(|n| !n.is_empty());
+        self
+    }
+
+    pub fn with_profile(mut self, profile: &MachineProfile) -> Self {
+        self.machine_profile = Some(profile.name.clone());
+        self
+    }
+
+    pub fn with_settings(mut self, settings: &GrblSettings) -> Self {
+        self.settings_hash = Some(settings.hash());
+        self
+    }
+}
@@ -2,35 +2,78 @@
 //! multi-hour run defects. One JSONL file per session; app (or a subscriber task)
 //! feeds probe results and optional status snapshots.
 //!
+//! Attached to a `GrblMachine` with `set_session_recorder`, the machine also
+//! records the connection, job start/end (with file hash and stream result),
+//! every job line sent and its reply, alarms, controller feedback, real-time
+//! commands and setting changes, so a failed job can be reconstructed.
+//!
 //! # Example (with `serial` feature)
 //!
 //! **Probe path:** App calls `probe_z()`, then `get_status()`, then
 //! `session.record_probe(probe_result(success, work_pos, machine_pos))`.
+//! `zero_z_with_plate()` records its own contact when the recorder is attached
+//! with `machine.set_session_recorder(Some(recorder))`.
+//!
+//! **Reading back:** [`SessionReader`] streams the events of a log, filtered by
+//! time range and event type; [`replay_status`] re-broadcasts the recorded status
+//! like `subscribe_status()`, for developing UI and analysis against real runs.
+//!
+//! **Probe quality:** `machine.probe_repeatability()` probes one point N times and
+//! records every touch; [`analyze_session_probes`] computes the same statistics
+//! (mean, standard deviation, spread, drift) from the probe events of a log.
+//!
+//! **Status path:** `machine.start_status_recording(options)` writes a status at
+//! the configured interval and on every state change, alarm and position jump
+//! (see [`StatusThrottle`]), until `stop_status_recording()`. Without a machine,
+//! call `recorder.record_status(status_snapshot_from(&status))` directly.
 //!
-//! **Status path:** Use `machine.subscribe_status()` to get a `broadcast::Receiver<MachineStatus>`.
-//! Spawn a task that receives from it and calls `recorder.record_status(status_snapshot_from(&status))`
-//! at a chosen interval (e.g. every 1–5 s) or on state change.
+//! **Durability:** events are flushed as they are written and fsynced on an
+//! interval and after important events ([`FlushPolicy`]), so a crash keeps the
+//! tail of the log. [`SessionRecorder::start_session_with`] also rotates into
+//! numbered, optionally compressed segments indexed by a [`SessionManifest`], and
+//! removes old sessions ([`cleanup_sessions`]).
+//!
+//! **Header:** every session starts with a [`SessionHeader`] event (job name,
+//! machine profile, `$I` build info, settings hash, version, host, local start
+//! time); `machine.session_header()` gathers it from the controller. The file
+//! name comes from [`RecorderOptions::file_name`], e.g. `"{job}_{date}T{time}"`.
 //!
 //! ```ignore
-//! let mut recorder = SessionRecorder::start_session(log_dir)?;
-//! let mut rx = machine.subscribe_status();
-//! // ... in another task: while let Ok(status) = rx.recv().await { record_status(...); }
-//! machine.probe_z(10.0, 50.0).await?;
-//! let status = machine.get_status().await;
-//! recorder.record_probe(probe_result(true, status.work_pos, status.machine_pos))?;
-//! recorder.finish()?;
+//! let header = machine.session_header(Some("sign-back"), Some(&profile)).await?;
+//! let recorder = SessionRecorder::start_session_with(log_dir, options, header)?;
+//! machine.set_session_recorder(Some(recorder)).await;
+//! machine.start_status_recording(StatusRecordOptions::default()).await;
+//! machine.zero_z_with_plate(12.7, 100.0, 25.0, 2.0).await?;
+//! machine.run_file(Path::new("job.nc")).await?;
+//! machine.stop_status_recording().await;
+//! if let Some(recorder) = machine.set_session_recorder(None).await {
+//!     recorder.finish()?;
+//! }
 //! ```
 
 use crate::machines::grbl::Position;
-use serde::Serialize;
+use chrono::{Local, SecondsFormat};
+use serde::{Deserialize, Serialize};
 use std::fs::File;
 use std::io::{BufWriter, Write};
 use std::path::{Path, PathBuf};
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
 use thiserror::Error;
 
+mod header;
+mod reader;
+mod stats;
+mod storage;
+mod throttle;
+
+pub use header::*;
+pub use reader::*;
+pub use stats::*;
+pub use storage::*;
+pub use throttle::*;
+
 /// One probe cycle result: success/fail and position at probe time.
-#[derive(Clone, Debug, Serialize)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct ProbeResult {
     pub success: bool,
     pub work_pos: Position,
@@ -45,6 +88,8 @@ pub struct StatusSnapshot {
     pub state: String,
     pub work_pos: Position,
     pub machine_pos: Position,
+    /// Work position in part space (gantry Y + bed extension).
+    pub part_pos: Position,
     pub feed_rate: f64,
     pub spindle_speed: f64,
     /// Unix timestamp (seconds since epoch, fractional).
@@ -52,9 +97,16 @@ pub struct StatusSnapshot {
 }
 
 /// JSONL line variant: one of these per line in the log.
-#[derive(Serialize)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 #[serde(tag = "event", rename_all = "snake_case")]
 pub enum SessionEvent {
+    /// First event of every session.
+    Header {
+        header: SessionHeader,
+        /// Session start in local time, RFC 3339 with UTC offset.
+        local_time: String,
+        ts_secs: f64,
+    },
     Probe {
         success: bool,
         work_pos: Position,
@@ -65,10 +117,93 @@ pub enum SessionEvent {
         state: String,
         work_pos: Position,
         machine_pos: Position,
+        /// Absent in logs written before part-space positions were recorded.
+        #[serde(default)]
+        part_pos: Option<Position>,
         feed_rate: f64,
         spindle_speed: f64,
         ts_secs: f64,
     },
+    /// Serial connection to the controller is open (written when a recorder is
+    /// attached to a connected machine).
+    Connected { port: String, ts_secs: f64 },
+    Disconnected { port: String, ts_secs: f64 },
+    /// A job started streaming.
+    JobStarted {
+        file: String,
+        /// SHA-256 of the file contents (hex).
+        file_hash: String,
+        /// Lines in the file.
+        lines: usize,
+        /// First line streamed (1-based; greater than 1 for a start-from-line run).
+        start_line: usize,
+        ts_secs: f64,
+    },
+    /// A job stopped streaming: finished, stopped on an error, or aborted.
+    JobEnded {
+        file: String,
+        lines_sent: u32,
+        lines_ok: u32,
+        first_error: Option<String>,
+        /// Source lines the controller rejected.
+        error_lines: Vec<usize>,
+        /// Why streaming failed (port error, timeout), if it did.
+        failure: Option<String>,
+        ts_secs: f64,
+    },
+    /// A job line was sent. `line` is the 1-based line in the program.
+    LineSent {
+        line: usize,
+        text: String,
+        ts_secs: f64,
+    },
+    /// Reply to a sent line: `ok`, `error:N` or `ALARM:N`.
+    Reply {
+        line: usize,
+        reply: String,
+        ts_secs: f64,
+    },
+    /// Alarm raised by the controller; `line` is the job line that raised it, if any.
+    Alarm {
+        code: String,
+        line: Option<usize>,
+        ts_secs: f64,
+    },
+    /// Feedback message from the controller (`[MSG:...]`, `[PRB:...]`, ...).
+    Feedback { message: String, ts_secs: f64 },
+    /// Real-time command sent (hold, cycle start, overrides, reset).
+    Realtime { command: String, ts_secs: f64 },
+    /// Controller setting written with `$N=value`.
+    SettingChanged {
+        number: u16,
+        value: String,
+        ts_secs: f64,
+    },
+}
+
+impl From<ProbeResult> for SessionEvent {
+    fn from(result: ProbeResult) -> Self {
+        SessionEvent::Probe {
+            success: result.success,
+            work_pos: result.work_pos,
+            machine_pos: result.machine_pos,
+            ts_secs: result.ts_secs,
+        }
+    }
+}
+
+impl From<StatusSnapshot> for SessionEvent {
+    fn from(snapshot: StatusSnapshot) -> Self {
+        SessionEvent::Status {
+            state: snapshot.state,
+            work_pos: snapshot.work_pos,
+            machine_pos: snapshot.machine_pos,
+            part_pos: Some(snapshot.part_pos),
+            feed_rate: snapshot.feed_rate,
+            spindle_speed: snapshot.spindle_speed,
+            ts_secs: snapshot.ts_secs,
+        }
+    }
 }
 
 /// Errors from session recorder.
@@ -82,130 +217,249 @@ pub enum SessionError {
     },
     #[error("failed to write: {0}")]
     WriteFailed(#[from] std::io::Error),
+    #[error("failed to read: {0}")]
+    ReadFailed(std::io::Error),
+    #[error("invalid session line {line}: {message}")]
+    InvalidLine { line: usize, message: String },
 }
 
-fn now_secs() -> f64 {
+/// Current time as a Unix timestamp (seconds, fractional), as used in events.
+pub fn now_secs() -> f64 {
     SystemTime::now()
         .duration_since(UNIX_EPOCH)
         .unwrap()
         .as_secs_f64()
 }
 
-/// Session recorder: append-only JSONL log of probe results and status snapshots.
+/// Session recorder: append-only JSONL log of probe results and status snapshots,
+/// written in segments listed by a [`SessionManifest`] (see [`RecorderOptions`]
+/// for flushing, rotation, compression and retention).
 pub struct SessionRecorder {
     writer: BufWriter<File>,
     path: PathBuf,
+    dir: PathBuf,
+    options: RecorderOptions,
+    manifest: SessionManifest,
+    manifest_path: PathBuf,
+    segment_started: Instant,
+    last_sync: Instant,
+}
+
+/// Events worth an fsync of their own under [`FlushPolicy::sync_on_important`].
+fn is_important(event: &SessionEvent) -> bool {
+    matches!(
+        event,
+        SessionEvent::Header { .. }
+            | SessionEvent::Probe { .. }
+            | SessionEvent::Connected { .. }
+            | SessionEvent::Disconnected { .. }
+            | SessionEvent::JobStarted { .. }
+            | SessionEvent::JobEnded { .. }
+            | SessionEvent::Alarm { .. }
+            | SessionEvent::SettingChanged { .. }
+    )
+}
+
+fn open_append(path: &Path) -> Result<BufWriter<File>, SessionError> {
+    let file = File::options()
+        .create(true)
+        .append(true)
+        .open(path)
+        .map_err(|e| SessionError::OpenFailed {
+            path: path.to_path_buf(),
+            source: e,
+        })?;
+    Ok(BufWriter::new(file))
+}
+
+fn new_segment(file: String) -> SegmentInfo {
+    SegmentInfo {
+        file,
+        first_ts: None,
+        last_ts: None,
+        events: 0,
+        bytes: 0,
+        compression: Compression::None,
+        closed: false,
+    }
 }
 
 impl SessionRecorder {
-    /// Start a new session: create a timestamped log file in `log_dir`.
-    /// File name: `session_YYYY-MM-DDTHH-MM-SS.jsonl`.
+    /// Start a new session with default [`RecorderOptions`] and a header with
+    /// only version and host: create a timestamped log file in `log_dir`.
+    /// File name: `session_YYYY-MM-DDTHH-MM-SS.jsonl` (local time).
     pub fn start_session(log_dir: &Path) -> Result<Self, SessionError> {
-        let now = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap();
-        let secs = now.as_secs();
-        let (mins, s) = (secs / 60, secs % 60);
-        let (hours, m) = (mins / 60, mins % 60);
-        let (days, h) = (hours / 24, hours % 24);
-        let (y, m_d) = days_to_year_month_day(days as u32);
-        let name = format!(
-            "session_{:04}-{:02}-{:02}T{:02}-{:02}-{:02}.jsonl",
-            y,
-            m_d.0,
-            m_d.1,
-            h,
-            m,
-            s
-        );
-        let path = log_dir.join(name);
-        let file = File::options()
-            .create(true)
-            .append(true)
-            .open(&path)
-            .map_err(|e| SessionError::OpenFailed {
-                path: path.clone(),
-                source: e,
-            })?;
-        let writer = BufWriter::new(file);
-        Ok(SessionRecorder { writer, path })
+        Self::start_session_with(log_dir, RecorderOptions::default(), SessionHeader::new())
     }
 
-    /// Record one probe result (append one JSON line).
-    pub fn record_probe(&mut self, result: ProbeResult) -> Result<(), SessionError> {
-        let event = SessionEvent::Probe {
-            success: result.success,
-            work_pos: result.work_pos,
-            machine_pos: result.machine_pos,
-            ts_secs: result.ts_secs,
+    /// Start a new session in `log_dir` with `options`, named from
+    /// `options.file_name`, and write `header` as its first event. Old sessions
+    /// are removed first if `options.retention` is set.
+    pub fn start_session_with(
+        log_dir: &Path,
+        options: RecorderOptions,
+        header: SessionHeader,
+    ) -> Result<Self, SessionError> {
+        if let Some(retention) = &options.retention {
+            cleanup_sessions(log_dir, retention)?;
+        }
+        let started = Local::now();
+        let local_time = started.to_rfc3339_opts(SecondsFormat::Secs, false);
+        let ts_secs = started.timestamp_micros() as f64 / 1e6;
+        let name = render_file_name(&options.file_name, &header, &started);
+        // Two sessions with the same name get distinct ones.
+        let mut base = name.clone();
+        let mut n = 1;
+        while manifest_path(log_dir, &base).exists() {
+            n += 1;
+            base = format!("{}_{}", name, n);
+        }
+        let file = segment_file_name(&base, 0);
+        let path = log_dir.join(&file);
+        let writer = open_append(&path)?;
+        let manifest = SessionManifest {
+            session: base.clone(),
+            started_secs: ts_secs,
+            started_local: local_time.clone(),
+            segments: vec![new_segment(file)],
         };
+        let manifest_path = manifest_path(log_dir, &base);
+        manifest.save(&manifest_path)?;
+        let mut recorder = SessionRecorder {
+            writer,
+            path,
+            dir: log_dir.to_path_buf(),
+            options,
+            manifest,
+            manifest_path,
+            segment_started: Instant::now(),
+            last_sync: Instant::now(),
+        };
+        recorder.record(SessionEvent::Header {
+            header,
+            local_time,
+            ts_secs,
+        })?;
+        Ok(recorder)
+    }
+
+    /// Record one event (append one JSON line), rotating and flushing as
+    /// configured.
+    pub fn record(&mut self, event: SessionEvent) -> Result<(), SessionError> {
         let line = serde_json::to_string(&event).map_err(|e| {
             SessionError::WriteFailed(std::io::Error::new(std::io::ErrorKind::InvalidData, e))
         })?;
+        if self.rotation_due() {
+            self.rotate()?;
+        }
         writeln!(self.writer, "{}", line)?;
+        let ts = event.ts_secs();
+        let segment = self.segment_mut();
+        segment.first_ts.get_or_insert(ts);
+        segment.last_ts = Some(ts);
+        segment.events += 1;
+        segment.bytes += line.len() as u64 + 1;
+
+        let flush = &self.options.flush;
+        let sync = (flush.sync_on_important && is_important(&event))
+            || flush
+                .sync_interval
+                .is_some_and(|interval| self.last_sync.elapsed() >= interval);
+        if sync {
+            self.sync()?;
+        } else if flush.flush_every_event {
+            self.writer.flush()?;
+        }
         Ok(())
     }
 
+    /// Record one probe result (append one JSON line).
+    pub fn record_probe(&mut self, result: ProbeResult) -> Result<(), SessionError> {
+        self.record(result.into())
+    }
+
     /// Record one status snapshot (append one JSON line).
     pub fn record_status(&mut self, snapshot: StatusSnapshot) -> Result<(), SessionError> {
-        let event = SessionEvent::Status {
-            state: snapshot.state,
-            work_pos: snapshot.work_pos,
-            machine_pos: snapshot.machine_pos,
-            feed_rate: snapshot.feed_rate,
-            spindle_speed: snapshot.spindle_speed,
-            ts_secs: snapshot.ts_secs,
-        };
-        let line = serde_json::to_string(&event).map_err(|e| {
-            SessionError::WriteFailed(std::io::Error::new(std::io::ErrorKind::InvalidData, e))
-        })?;
-        writeln!(self.writer, "{}", line)?;
-        Ok(())
+        self.record(snapshot.into())
     }
 
-    /// Flush and close the log file.
-    pub fn finish(mut self) -> Result<(), SessionError> {
+    /// Flush buffered events, fsync the current segment and save the manifest
+    /// with its event count, size and last timestamp.
+    pub fn sync(&mut self) -> Result<(), SessionError> {
         self.writer.flush()?;
-        Ok(())
+        self.writer.get_ref().sync_data()?;
+        self.last_sync = Instant::now();
+        self.manifest.save(&self.manifest_path)
     }
 
-    /// Path to the current session log file.
+    /// Flush, sync and close the current segment (compressing it if configured)
+    /// and mark the session finished in the manifest.
+    pub fn finish(mut self) -> Result<(), SessionError> {
+        self.close_segment()
+    }
+
+    /// Path to the current session log file (the segment being written).
     pub fn path(&self) -> &Path {
         &self.path
     }
-}
 
-/// Convert days since Unix epoch to (year, (month, day)); simplified.
-fn days_to_year_month_day(days: u32) -> (u32, (u32, u32)) {
-    // 1970-01-01 = day 0
-    let d = days as i64;
-    let (y, m, day) = unix_days_to_ymd(d);
-    (y as u32, (m as u32, day as u32))
-}
+    /// Path to the session manifest; [`SessionReader::open`] on it reads all
+    /// segments in order.
+    pub fn manifest_path(&self) -> &Path {
+        &self.manifest_path
+    }
 
-fn unix_days_to_ymd(days: i64) -> (i32, i32, i32) {
-    const EPOCH: i64 = 719163; // days from year 0 to 1970-01-01 (proleptic Gregorian)
-    let d = days + EPOCH;
-    let (y, m, d) = date_from_ordinal(d);
-    (y, m, d)
-}
+    fn segment_mut(&mut self) -> &mut SegmentInfo {
+        self.manifest
+            .segments
+            .last_mut()
+            .expect("session has a segment")
+    }
 
-fn date_from_ordinal(n: i64) -> (i32, i32, i32) {
-    let n = n as i32;
-    let a = n + 68569;
-    let b = (4 * a) / 146097;
-    let c = a - (146097 * b + 3) / 4;
-    let d = (4000 * (c + 1)) / 1461001;
-    let e = c - (1461 * d) / 4 + 31;
-    let m = (80 * e) / 2447;
-    let day = e - (2447 * m) / 80;
-    let mut y = 100 * (b - 49) + d + e / 2447;
-    let month = m - (m / 14);
-    if month <= 0 {
-        y -= 1;
+    fn rotation_due(&self) -> bool {
+        let segment = self
+            .manifest
+            .segments
+            .last()
+            .expect("session has a segment");
+        let rotation = &self.options.rotation;
+        segment.events > 0
+            && (rotation.max_bytes.is_some_and(|max| segment.bytes >= max)
+                || rotation
+                    .max_age
+                    .is_some_and(|age| self.segment_started.elapsed() >= age))
+    }
+
+    /// Sync, compress if configured and mark the segment closed in the saved
+    /// manifest; the uncompressed file is removed only after that save.
+    fn close_segment(&mut self) -> Result<(), SessionError> {
+        self.sync()?;
+        let compression = self.options.compression;
+        let compressed = compress_segment(&self.path, compression)?;
+        let segment = self.segment_mut();
+        segment.compression = compression;
+        segment.file = compressed
+            .file_name()
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        segment.closed = true;
+        self.manifest.save(&self.manifest_path)?;
+        if compressed != self.path {
+            std::fs::remove_file(&self.path)?;
+        }
+        Ok(())
+    }
+
+    /// Close the current segment and continue in the next numbered one.
+    fn rotate(&mut self) -> Result<(), SessionError> {
+        self.close_segment()?;
+        let file = segment_file_name(&self.manifest.session, self.manifest.segments.len());
+        self.path = self.dir.join(&file);
+        self.writer = open_append(&self.path)?;
+        self.manifest.segments.push(new_segment(file));
+        self.segment_started = Instant::now();
+        self.manifest.save(&self.manifest_path)
     }
-    let month = if month <= 0 { month + 12 } else { month };
-    (y, month, day)
 }
 
 /// Build a ProbeResult from success and positions (e.g. after probe_z + get_status).
@@ -224,6 +478,7 @@ pub fn status_snapshot_from(status: &crate::machines::grbl::MachineStatus) -> St
         state: format!("{:?}", status.state),
         work_pos: status.work_pos.clone(),
         machine_pos: status.machine_pos.clone(),
+        part_pos: status.part_pos.clone(),
         feed_rate: status.feed_rate,
         spindle_speed: status.spindle_speed,
         ts_secs: now_secs(),
@@ -277,7 +532,8 @@ mod tests {
         rec.record_status(StatusSnapshot {
             state: "Idle".to_string(),
             work_pos: pos.clone(),
-            machine_pos: pos,
+            machine_pos: pos.clone(),
+            part_pos: pos,
             feed_rate: 0.0,
             spindle_speed: 0.0,
             ts_secs: 2000.0,
@@ -285,4 +541,110 @@ mod tests {
         .unwrap();
         rec.finish().unwrap();
     }
+
+    #[test]
+    fn test_job_events_round_trip() {
+        let events = vec![
+            SessionEvent::JobStarted {
+                file: "job.nc".to_string(),
+                file_hash: "ab12".to_string(),
+                lines: 3,
+                start_line: 1,
+                ts_secs: 1.0,
+            },
+            SessionEvent::LineSent {
+                line: 2,
+                text: "G1 X10".to_string(),
+                ts_secs: 2.0,
+            },
+            SessionEvent::Reply {
+                line: 2,
+                reply: "error:20".to_string(),
+                ts_secs: 2.5,
+            },
+            SessionEvent::JobEnded {
+                file: "job.nc".to_string(),
+                lines_sent: 1,
+                lines_ok: 0,
+                first_error: Some("20".to_string()),
+                error_lines: vec![2],
+                failure: None,
+                ts_secs: 3.0,
+            },
+        ];
+        let json: Vec<String> = events
+            .iter()
+            .map(|e| serde_json::to_string(e).unwrap())
+            .collect();
+        assert!(json[1].contains("\"event\":\"line_sent\""));
+        for (line, event) in json.iter().zip(&events) {
+            let back: SessionEvent = serde_json::from_str(line).unwrap();
+            assert_eq!(&back, event);
+        }
+        assert_eq!(events[3].kind(), EventKind::JobEnded);
+        assert_eq!(events[2].ts_secs(), 2.5);
+    }
+
+    #[test]
+    fn test_rotation_compression_and_manifest() {
+        let dir = std::env::temp_dir().join(format!("grbl_rs_rotation_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let options = RecorderOptions {
+            rotation: RotationPolicy {
+                max_bytes: Some(150),
+                max_age: None,
+            },
+            compression: Compression::Gzip,
+            ..Default::default()
+        };
+        let header = SessionHeader::new().with_job_name("rotation");
+        let mut rec = SessionRecorder::start_session_with(&dir, options, header).unwrap();
+        let manifest_path = rec.manifest_path().to_path_buf();
+        for i in 0..6 {
+            rec.record(SessionEvent::Feedback {
+                message: format!("[MSG:step {}]", i),
+                ts_secs: i as f64,
+            })
+            .unwrap();
+            // Flushed after every event, so the log is complete on disk even
+            // without finish().
+            let written = std::fs::read_to_string(rec.path()).unwrap();
+            assert!(written.ends_with(&format!("step {}]\",\"ts_secs\":{}.0}}\n", i, i)));
+        }
+        rec.sync().unwrap();
+        // The saved manifest follows the open segment at every sync.
+        let manifest = SessionManifest::load(&manifest_path).unwrap();
+        let open = manifest.segments.last().unwrap();
+        assert!(!open.closed);
+        assert_eq!(open.last_ts, Some(5.0));
+        assert_eq!(manifest.segments.iter().map(|s| s.events).sum::<u64>(), 7);
+        rec.finish().unwrap();
+
+        let manifest = SessionManifest::load(&manifest_path).unwrap();
+        assert!(manifest.segments.len() > 1);
+        assert_eq!(
+            std::fs::read_dir(&dir).unwrap().count(),
+            manifest.segments.len() + 1
+        );
+        assert!(manifest
+            .segments
+            .iter()
+            .all(|s| s.closed && s.compression == Compression::Gzip && s.file.ends_with(".gz")));
+        assert_eq!(manifest.segments.iter().map(|s| s.events).sum::<u64>(), 7);
+        assert!(manifest.started_local.len() >= 25);
+        let events: Vec<SessionEvent> = SessionReader::open(&manifest_path)
+            .unwrap()
+            .map(|e| e.unwrap())
+            .collect();
+        std::fs::remove_dir_all(&dir).unwrap();
+        match &events[0] {
+            SessionEvent::Header { header, .. } => {
+                assert_eq!(header.job_name.as_deref(), Some("rotation"));
+                assert_eq!(header.version, env!("CARGO_PKG_VERSION"));
+            }
+            other => panic!("expected header, got {:?}", other),
+        }
+        let ts: Vec<f64> = events[1..].iter().map(|e| e.ts_secs()).collect();
+        assert_eq!(ts, vec![0.0, 1.0, 2.0, 3.0, 4.0, 5.0]);
+    }
 }
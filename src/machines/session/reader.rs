@@ -0,0 +1,518 @@
+//! Reading session logs back: stream events with a filter, or replay the
+//! recorded status as if it came from the poller.
+//!
+//! A log cut off by a crash can end in a partly written line; an invalid last
+//! line of the last segment is skipped with a warning instead of failing.
+
+use super::{open_segment, SessionError, SessionEvent, SessionManifest};
+use crate::machines::grbl::{AlarmCode, HoldReason, MachineState, MachineStatus, PinState};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::io::{BufRead, Lines};
+use std::iter::Peekable;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+use tokio::sync::broadcast;
+use tracing::warn;
+
+/// Statuses [`replay_status`] queues ahead of the slowest receiver.
+const REPLAY_CAPACITY: usize = 256;
+
+/// Event type, as in the `event` field of a log line.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EventKind {
+    Header,
+    Probe,
+    Status,
+    Connected,
+    Disconnected,
+    JobStarted,
+    JobEnded,
+    LineSent,
+    Reply,
+    Alarm,
+    Feedback,
+    Realtime,
+    SettingChanged,
+}
+
+impl SessionEvent {
+    pub fn kind(&self) -> EventKind {
+        match self {
+            SessionEvent::Header { .. } => EventKind::Header,
+            SessionEvent::Probe { .. } => EventKind::Probe,
+            SessionEvent::Status { .. } => EventKind::Status,
+            SessionEvent::Connected { .. } => EventKind::Connected,
+            SessionEvent::Disconnected { .. } => EventKind::Disconnected,
+            SessionEvent::JobStarted { .. } => EventKind::JobStarted,
+            SessionEvent::JobEnded { .. } => EventKind::JobEnded,
+            SessionEvent::LineSent { .. } => EventKind::LineSent,
+            SessionEvent::Reply { .. } => EventKind::Reply,
+            SessionEvent::Alarm { .. } => EventKind::Alarm,
+            SessionEvent::Feedback { .. } => EventKind::Feedback,
+            SessionEvent::Realtime { .. } => EventKind::Realtime,
+            SessionEvent::SettingChanged { .. } => EventKind::SettingChanged,
+        }
+    }
+
+    /// Unix timestamp of the event.
+    pub fn ts_secs(&self) -> f64 {
+        match self {
+            SessionEvent::Header { ts_secs, .. }
+            | SessionEvent::Probe { ts_secs, .. }
+            | SessionEvent::Status { ts_secs, .. }
+            | SessionEvent::Connected { ts_secs, .. }
+            | SessionEvent::Disconnected { ts_secs, .. }
+            | SessionEvent::JobStarted { ts_secs, .. }
+            | SessionEvent::JobEnded { ts_secs, .. }
+            | SessionEvent::LineSent { ts_secs, .. }
+            | SessionEvent::Reply { ts_secs, .. }
+            | SessionEvent::Alarm { ts_secs, .. }
+            | SessionEvent::Feedback { ts_secs, .. }
+            | SessionEvent::Realtime { ts_secs, .. }
+            | SessionEvent::SettingChanged { ts_secs, .. } => *ts_secs,
+        }
+    }
+
+    /// The recorded status, for `Status` events. Pin state is not recorded and
+    /// reads as all clear; `last_updated` is now.
+    pub fn machine_status(&self) -> Option<MachineStatus> {
+        match self {
+            SessionEvent::Status {
+                state,
+                work_pos,
+                machine_pos,
+                part_pos,
+                feed_rate,
+                spindle_speed,
+                ..
+            } => Some(MachineStatus {
+                state: parse_state(state),
+                machine_pos: machine_pos.clone(),
+                work_pos: work_pos.clone(),
+                // Without a recorded part position there was no bed extension.
+                part_pos: part_pos.clone().unwrap_or_else(|| work_pos.clone()),
+                feed_rate: *feed_rate,
+                spindle_speed: *spindle_speed,
+                input_pins: PinState::default(),
+                last_updated: Instant::now(),
+            }),
+            _ => None,
+        }
+    }
+}
+
+/// Machine state from its recorded form (`{:?}` of [`MachineState`]). Anything
+/// unrecognized becomes `MachineState::Unknown`.
+fn parse_state(s: &str) -> MachineState {
+    let inner = |prefix: &str| s.strip_prefix(prefix).and_then(|r| r.strip_suffix(')'));
+    match s {
+        "Idle" => return MachineState::Idle,
+        "Run" => return MachineState::Run,
+        "Jog" => return MachineState::Jog,
+        "Door" => return MachineState::Door,
+        "Check" => return MachineState::Check,
+        "Home" => return MachineState::Home,
+        "Sleep" => return MachineState::Sleep,
+        _ => {}
+    }
+    if let Some(reason) = inner("Hold(") {
+        let reason = match reason {
+            "FeedHold" => HoldReason::FeedHold,
+            "SafetyDoor" => HoldReason::SafetyDoor,
+            other => HoldReason::Other(
+                other
+                    .strip_prefix("Other(\"")
+                    .and_then(|r| r.strip_suffix("\")"))
+                    .unwrap_or(other)
+                    .to_string(),
+            ),
+        };
+        return MachineState::Hold(reason);
+    }
+    if let Some(alarm) = inner("Alarm(") {
+        let known = (1..=21u8)
+            .map(AlarmCode::from)
+            .find(|code| format!("{:?}", code) == alarm);
+        let unknown = alarm
+            .strip_prefix("Unknown(")
+            .and_then(|r| r.strip_suffix(')'))
+            .and_then(|n| n.parse::<u8>().ok())
+            .map(AlarmCode::Unknown);
+        if let Some(code) = known.or(unknown) {
+            return MachineState::Alarm(code);
+        }
+    }
+    let unknown = s
+        .strip_prefix("Unknown(\"")
+        .and_then(|r| r.strip_suffix("\")"))
+        .unwrap_or(s);
+    MachineState::Unknown(unknown.to_string())
+}
+
+/// Which events [`SessionReader`] yields.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SessionFilter {
+    /// Earliest timestamp (Unix seconds, inclusive).
+    pub from_secs: Option<f64>,
+    /// Latest timestamp (Unix seconds, inclusive).
+    pub to_secs: Option<f64>,
+    /// Event types to keep; empty keeps all.
+    pub kinds: Vec<EventKind>,
+}
+
+impl SessionFilter {
+    /// Only events of these types.
+    pub fn kinds(kinds: &[EventKind]) -> Self {
+        Self {
+            kinds: kinds.to_vec(),
+            ..Self::default()
+        }
+    }
+
+    pub fn matches(&self, event: &SessionEvent) -> bool {
+        let ts = event.ts_secs();
+        self.from_secs.is_none_or(|from| ts >= from)
+            && self.to_secs.is_none_or(|to| ts <= to)
+            && (self.kinds.is_empty() || self.kinds.contains(&event.kind()))
+    }
+}
+
+/// Streams events from a session log, one line at a time.
+pub struct SessionReader {
+    lines: Option<Peekable<Lines<Box<dyn BufRead + Send>>>>,
+    /// Segments still to read after the current one.
+    pending: VecDeque<PathBuf>,
+    line: usize,
+    filter: SessionFilter,
+}
+
+impl SessionReader {
+    /// Open a log written by [`super::SessionRecorder`]: one segment (`.jsonl`,
+    /// `.jsonl.gz`, `.jsonl.zst`) or a `.manifest.json`, which reads all segments
+    /// of the session in order.
+    pub fn open(path: &Path) -> Result<Self, SessionError> {
+        let is_manifest = path
+            .file_name()
+            .is_some_and(|n| n.to_string_lossy().ends_with(".manifest.json"));
+        let mut pending: VecDeque<PathBuf> = if is_manifest {
+            let dir = path.parent().unwrap_or(Path::new(""));
+            SessionManifest::load(path)?.segment_paths(dir).into()
+        } else {
+            VecDeque::from([path.to_path_buf()])
+        };
+        let first = pending.pop_front();
+        let lines = match first {
+            Some(first) => Some(open_segment(&first)?.lines().peekable()),
+            None => None,
+        };
+        Ok(Self {
+            lines,
+            pending,
+            line: 0,
+            filter: SessionFilter::default(),
+        })
+    }
+
+    /// Only yield events matching `filter`.
+    pub fn with_filter(mut self, filter: SessionFilter) -> Self {
+        self.filter = filter;
+        self
+    }
+}
+
+impl Iterator for SessionReader {
+    /// An event, or the error for an unreadable or invalid line (reading can
+    /// continue past it).
+    type Item = Result<SessionEvent, SessionError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let Some(lines) = self.lines.as_mut() else {
+                // Next segment; line numbers continue across segments.
+                match open_segment(&self.pending.pop_front()?) {
+                    Ok(reader) => self.lines = Some(reader.lines().peekable()),
+                    Err(e) => return Some(Err(e)),
+                }
+                continue;
+            };
+            let line = match lines.next() {
+                Some(Ok(line)) => line,
+                Some(Err(e)) => return Some(Err(SessionError::ReadFailed(e))),
+                None => {
+                    self.lines = None;
+                    continue;
+                }
+            };
+            self.line += 1;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let event: SessionEvent = match serde_json::from_str(&line) {
+                Ok(event) => event,
+                Err(e) if self.pending.is_empty() && lines.peek().is_none() => {
+                    warn!(
+                        "session log: skipping invalid last line {} (cut off?): {}",
+                        self.line, e
+                    );
+                    continue;
+                }
+                Err(e) => {
+                    return Some(Err(SessionError::InvalidLine {
+                        line: self.line,
+                        message: e.to_string(),
+                    }))
+                }
+            };
+            if self.filter.matches(&event) {
+                return Some(Ok(event));
+            }
+        }
+    }
+}
+
+/// Replay the `Status` events of `reader` on a broadcast channel, like
+/// `GrblMachine::subscribe_status`. Events are spaced by their recorded
+/// timestamps divided by `speed` (1.0 = real time, 10.0 = ten times faster);
+/// `speed` of 0 or infinity sends them as fast as the slowest receiver takes
+/// them. The channel closes after the last event, or replay stops early once
+/// every receiver is dropped.
+///
+/// The log is read as the replay goes; invalid lines are skipped with a warning
+/// and a read error ends the replay. Must be called within a Tokio runtime.
+pub fn replay_status(reader: SessionReader, speed: f64) -> broadcast::Receiver<MachineStatus> {
+    let (tx, rx) = broadcast::channel(REPLAY_CAPACITY);
+    let realtime = speed > 0.0 && speed.is_finite();
+    tokio::spawn(async move {
+        let mut prev: Option<f64> = None;
+        for event in reader {
+            let event = match event {
+                Ok(event) => event,
+                Err(e @ SessionError::InvalidLine { .. }) => {
+                    warn!("session replay: {}", e);
+                    continue;
+                }
+                Err(e) => {
+                    warn!("session replay stopped: {}", e);
+                    break;
+                }
+            };
+            let Some(mut status) = event.machine_status() else {
+                continue;
+            };
+            let ts = event.ts_secs();
+            if let (true, Some(prev)) = (realtime, prev) {
+                let delay = ((ts - prev) / speed).max(0.0);
+                tokio::time::sleep(Duration::from_secs_f64(delay)).await;
+            }
+            prev = Some(ts);
+            // Never queue more than a receiver can hold, so none of them lags.
+            while tx.len() >= REPLAY_CAPACITY && tx.receiver_count() > 0 {
+                tokio::time::sleep(Duration::from_millis(1)).await;
+            }
+            status.last_updated = Instant::now();
+            if tx.send(status).is_err() {
+                break;
+            }
+        }
+    });
+    rx
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::machines::grbl::Position;
+    use crate::machines::session::{ProbeResult, SessionRecorder, StatusSnapshot};
+    use std::path::PathBuf;
+
+    fn pos(x: f64) -> Position {
+        Position {
+            x,
+            y: 0.0,
+            z: 0.0,
+            a: None,
+        }
+    }
+
+    fn write_log(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("grbl_rs_{}_{}", name, std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let mut rec = SessionRecorder::start_session(&dir).unwrap();
+        let path = rec.path().to_path_buf();
+        for (i, state) in ["Idle", "Run", "Hold(FeedHold)", "Alarm(HardLimit)"]
+            .iter()
+            .enumerate()
+        {
+            rec.record_status(StatusSnapshot {
+                state: state.to_string(),
+                work_pos: pos(i as f64),
+                machine_pos: pos(i as f64),
+                part_pos: pos(i as f64),
+                feed_rate: 100.0,
+                spindle_speed: 0.0,
+                ts_secs: 1000.0 + i as f64 * 0.01,
+            })
+            .unwrap();
+        }
+        rec.record_probe(ProbeResult {
+            success: true,
+            work_pos: pos(9.0),
+            machine_pos: pos(9.0),
+            ts_secs: 1000.015,
+        })
+        .unwrap();
+        rec.finish().unwrap();
+        path
+    }
+
+    #[test]
+    fn test_read_baseline_status_lines() {
+        // Status lines as written before part positions were recorded.
+        let dir = std::env::temp_dir().join(format!("grbl_rs_baseline_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("session_2024-01-01T00-00-00.jsonl");
+        let pos = r#"{"x":1.0,"y":2.0,"z":-3.0,"a":null}"#;
+        let line = format!(
+            r#"{{"event":"status","state":"Run","work_pos":{pos},"machine_pos":{pos},"feed_rate":500.0,"spindle_speed":12000.0,"ts_secs":1000.0}}"#
+        );
+        std::fs::write(
+            &path,
+            format!("{}\n{}\n", line, line.replace("1000.0}", "1001.0}")),
+        )
+        .unwrap();
+        let events: Vec<SessionEvent> = SessionReader::open(&path)
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(events.len(), 2);
+        let status = events[0].machine_status().unwrap();
+        assert_eq!(status.state, MachineState::Run);
+        assert_eq!(status.part_pos, status.work_pos);
+        assert_eq!(events[1].ts_secs(), 1001.0);
+    }
+
+    #[test]
+    fn test_parse_state() {
+        for state in [
+            MachineState::Idle,
+            MachineState::Hold(HoldReason::SafetyDoor),
+            MachineState::Hold(HoldReason::Other("x".to_string())),
+            MachineState::Alarm(AlarmCode::ProbeFailContact),
+            MachineState::Alarm(AlarmCode::Unknown(40)),
+            MachineState::Unknown("Tool".to_string()),
+        ] {
+            assert_eq!(parse_state(&format!("{:?}", state)), state);
+        }
+    }
+
+    #[test]
+    fn test_read_and_filter() {
+        let path = write_log("reader");
+        let all: Vec<SessionEvent> = SessionReader::open(&path)
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        // Header, four statuses and a probe.
+        assert_eq!(all.len(), 6);
+        assert_eq!(all[0].kind(), EventKind::Header);
+        let filter = SessionFilter {
+            from_secs: Some(1000.005),
+            to_secs: Some(1000.025),
+            kinds: vec![EventKind::Status],
+        };
+        let some: Vec<SessionEvent> = SessionReader::open(&path)
+            .unwrap()
+            .with_filter(filter)
+            .collect::<Result<_, _>>()
+            .unwrap();
+        std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+        assert_eq!(some.len(), 2);
+        assert_eq!(
+            some[1].machine_status().unwrap().state,
+            MachineState::Hold(HoldReason::FeedHold)
+        );
+    }
+
+    #[test]
+    fn test_read_skips_cut_off_last_line() {
+        let path = write_log("cut_off");
+        let mut log = std::fs::read_to_string(&path).unwrap();
+        log.push_str(r#"{"event":"status","state":"Ru"#);
+        std::fs::write(&path, &log).unwrap();
+        let events: Vec<SessionEvent> = SessionReader::open(&path)
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(events.len(), 6);
+
+        // Anywhere else an invalid line is still an error.
+        log.push_str("\n{\"event\":\"connected\",\"port\":\"x\",\"ts_secs\":1.0}\n");
+        std::fs::write(&path, &log).unwrap();
+        let results: Vec<_> = SessionReader::open(&path).unwrap().collect();
+        std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+        assert_eq!(results.len(), 8);
+        assert!(matches!(
+            results[6],
+            Err(SessionError::InvalidLine { line: 7, .. })
+        ));
+        assert!(results[7].is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_replay_more_than_capacity_without_delay() {
+        let dir = std::env::temp_dir().join(format!("grbl_rs_replay_many_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let mut rec = SessionRecorder::start_session(&dir).unwrap();
+        let path = rec.path().to_path_buf();
+        let count = REPLAY_CAPACITY * 3;
+        for i in 0..count {
+            rec.record_status(StatusSnapshot {
+                state: "Run".to_string(),
+                work_pos: pos(i as f64),
+                machine_pos: pos(i as f64),
+                part_pos: pos(i as f64),
+                feed_rate: 100.0,
+                spindle_speed: 0.0,
+                ts_secs: 1000.0 + i as f64,
+            })
+            .unwrap();
+        }
+        rec.finish().unwrap();
+        let mut rx = replay_status(SessionReader::open(&path).unwrap(), 0.0);
+        std::fs::remove_dir_all(&dir).unwrap();
+        let mut received = 0;
+        loop {
+            match rx.recv().await {
+                Ok(status) => {
+                    assert_eq!(status.machine_pos.x, received as f64);
+                    received += 1;
+                }
+                Err(broadcast::error::RecvError::Closed) => break,
+                Err(e) => panic!("replay: {}", e),
+            }
+            if received % 100 == 0 {
+                tokio::time::sleep(Duration::from_millis(2)).await;
+            }
+        }
+        assert_eq!(received, count);
+    }
+
+    #[tokio::test]
+    async fn test_replay() {
+        let path = write_log("replay");
+        let started = Instant::now();
+        let mut rx = replay_status(SessionReader::open(&path).unwrap(), 2.0);
+        std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+        let mut states = Vec::new();
+        while let Ok(status) = rx.recv().await {
+            states.push(status.state);
+        }
+        // 30 ms recorded, replayed at double speed.
+        assert!(started.elapsed() >= Duration::from_millis(14));
+        assert_eq!(states.len(), 4);
+        assert_eq!(states[3], MachineState::Alarm(AlarmCode::HardLimit));
+    }
+}
@@ -0,0 +1,205 @@
+//! Probe repeatability statistics.
+//!
+//! Computed over the machine positions of successful probes: per-axis mean,
+//! sample standard deviation, min/max spread and drift (least-squares slope
+//! against probe number and against time). Failed probes are only counted.
+//! Meaningful for repeated probes of one point, e.g. from
+//! `GrblMachine::probe_repeatability` or a session log of such a test.
+
+use super::{EventKind, ProbeResult, SessionError, SessionEvent, SessionFilter, SessionReader};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// Statistics per axis (X, Y, Z), in mm.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ProbeStats {
+    /// Successful probes the statistics are computed from.
+    pub count: usize,
+    pub failed: usize,
+    pub mean: [f64; 3],
+    /// Sample standard deviation (0 for a single probe).
+    pub std_dev: [f64; 3],
+    pub min: [f64; 3],
+    pub max: [f64; 3],
+    /// `max - min`.
+    pub spread: [f64; 3],
+    /// Trend in mm per probe; `None` with fewer than two probes.
+    pub drift_per_probe: Option<[f64; 3]>,
+    /// Trend in mm per hour; `None` if all probes have the same timestamp.
+    pub drift_per_hour: Option<[f64; 3]>,
+}
+
+/// Touches of a repeatability test and their statistics.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RepeatabilityReport {
+    pub results: Vec<ProbeResult>,
+    /// `None` if no touch succeeded.
+    pub stats: Option<ProbeStats>,
+}
+
+/// Least-squares slope of `ys` against `xs`; `None` if `xs` do not vary.
+fn slope(xs: &[f64], ys: &[f64]) -> Option<f64> {
+    let n = xs.len() as f64;
+    let mx = xs.iter().sum::<f64>() / n;
+    let my = ys.iter().sum::<f64>() / n;
+    let sxx: f64 = xs.iter().map(|x| (x - mx).powi(2)).sum();
+    if sxx < 1e-12 {
+        return None;
+    }
+    let sxy: f64 = xs.iter().zip(ys).map(|(x, y)| (x - mx) * (y - my)).sum();
+    Some(sxy / sxx)
+}
+
+/// Statistics over `results`; `None` if no probe succeeded.
+pub fn probe_stats(results: &[ProbeResult]) -> Option<ProbeStats> {
+    let ok: Vec<&ProbeResult> = results.iter().filter(|r| r.success).collect();
+    if ok.is_empty() {
+        return None;
+    }
+    let n = ok.len();
+    let axis = |i: usize| -> Vec<f64> {
+        ok.iter()
+            .map(|r| match i {
+                0 => r.machine_pos.x,
+                1 => r.machine_pos.y,
+                _ => r.machine_pos.z,
+            })
+            .collect()
+    };
+    let axes = [axis(0), axis(1), axis(2)];
+    let index: Vec<f64> = (0..n).map(|i| i as f64).collect();
+    let hours: Vec<f64> = ok.iter().map(|r| r.ts_secs / 3600.0).collect();
+    let mut stats = ProbeStats {
+        count: n,
+        failed: results.len() - n,
+        mean: [0.0; 3],
+        std_dev: [0.0; 3],
+        min: [0.0; 3],
+        max: [0.0; 3],
+        spread: [0.0; 3],
+        drift_per_probe: None,
+        drift_per_hour: None,
+    };
+    let mut per_probe = [0.0; 3];
+    let mut per_hour = [0.0; 3];
+    for (i, v) in axes.iter().enumerate() {
+        let mean = v.iter().sum::<f64>() / n as f64;
+        stats.mean[i] = mean;
+        if n > 1 {
+            let var = v.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / (n - 1) as f64;
+            stats.std_dev[i] = var.sqrt();
+        }
+        stats.min[i] = v.iter().copied().fold(f64::INFINITY, f64::min);
+        stats.max[i] = v.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+        stats.spread[i] = stats.max[i] - stats.min[i];
+        per_probe[i] = slope(&index, v).unwrap_or(0.0);
+        per_hour[i] = slope(&hours, v).unwrap_or(0.0);
+    }
+    if n > 1 {
+        stats.drift_per_probe = Some(per_probe);
+    }
+    if slope(&hours, &hours).is_some() {
+        stats.drift_per_hour = Some(per_hour);
+    }
+    Some(stats)
+}
+
+/// Probe events of a session log, in order. Other events are skipped.
+pub fn read_probe_events(path: &Path) -> Result<Vec<ProbeResult>, SessionError> {
+    let reader = SessionReader::open(path)?.with_filter(SessionFilter::kinds(&[EventKind::Probe]));
+    let mut results = Vec::new();
+    for event in reader {
+        if let SessionEvent::Probe {
+            success,
+            work_pos,
+            machine_pos,
+            ts_secs,
+        } = event?
+        {
+            results.push(ProbeResult {
+                success,
+                work_pos,
+                machine_pos,
+                ts_secs,
+            });
+        }
+    }
+    Ok(results)
+}
+
+/// [`probe_stats`] over the probe events of a session log.
+pub fn analyze_session_probes(path: &Path) -> Result<Option<ProbeStats>, SessionError> {
+    Ok(probe_stats(&read_probe_events(path)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::machines::grbl::Position;
+    use crate::machines::session::SessionRecorder;
+
+    fn probe(success: bool, z: f64, ts_secs: f64) -> ProbeResult {
+        let pos = Position {
+            x: 10.0,
+            y: 20.0,
+            z,
+            a: None,
+        };
+        ProbeResult {
+            success,
+            work_pos: pos.clone(),
+            machine_pos: pos,
+            ts_secs,
+        }
+    }
+
+    #[test]
+    fn test_stats() {
+        let results = vec![
+            probe(true, -10.000, 0.0),
+            probe(true, -10.002, 1800.0),
+            probe(false, -30.0, 2000.0),
+            probe(true, -10.004, 3600.0),
+        ];
+        let s = probe_stats(&results).unwrap();
+        assert_eq!((s.count, s.failed), (3, 1));
+        assert!((s.mean[2] + 10.002).abs() < 1e-9);
+        assert!((s.std_dev[2] - 0.002).abs() < 1e-9);
+        assert_eq!(s.std_dev[0], 0.0);
+        assert!((s.spread[2] - 0.004).abs() < 1e-9);
+        assert!((s.drift_per_probe.unwrap()[2] + 0.002).abs() < 1e-9);
+        assert!((s.drift_per_hour.unwrap()[2] + 0.004).abs() < 1e-9);
+
+        let single = probe_stats(&[probe(true, -1.0, 5.0)]).unwrap();
+        assert_eq!(single.drift_per_probe, None);
+        assert_eq!(single.drift_per_hour, None);
+        assert!(probe_stats(&[probe(false, 0.0, 0.0)]).is_none());
+    }
+
+    #[test]
+    fn test_analyze_session() {
+        let dir = std::env::temp_dir().join(format!("grbl_rs_stats_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let mut rec = SessionRecorder::start_session(&dir).unwrap();
+        let path = rec.path().to_path_buf();
+        rec.record_probe(probe(true, -5.0, 100.0)).unwrap();
+        rec.record_status(crate::machines::session::StatusSnapshot {
+            state: "Idle".to_string(),
+            work_pos: probe(true, 0.0, 0.0).work_pos,
+            machine_pos: probe(true, 0.0, 0.0).machine_pos,
+            part_pos: probe(true, 0.0, 0.0).work_pos,
+            feed_rate: 0.0,
+            spindle_speed: 0.0,
+            ts_secs: 150.0,
+        })
+        .unwrap();
+        rec.record_probe(probe(true, -5.01, 200.0)).unwrap();
+        rec.finish().unwrap();
+        let events = read_probe_events(&path).unwrap();
+        let stats = analyze_session_probes(&path).unwrap().unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(events.len(), 2);
+        assert_eq!(stats.count, 2);
+        assert!((stats.spread[2] - 0.01).abs() < 1e-9);
+    }
+}
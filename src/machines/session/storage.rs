@@ -0,0 +1,451 @@
+//! On-disk layout of a session: numbered segments, the manifest, compression and
+//! retention.
+//!
+//! A session `<base>` is written to `<base>.jsonl`, then (after each rotation)
+//! `<base>.1.jsonl`, `<base>.2.jsonl`, ... Closed segments may be compressed to
+//! `.jsonl.gz` / `.jsonl.zst`. `<base>.manifest.json` lists the segments in order
+//! and is rewritten (atomically) at start, on every fsync of the log, on rotation
+//! and on finish; a session whose last segment is not `closed` was not finished
+//! cleanly. A compressed segment's original is only removed once the manifest
+//! names the compressed file, so a crash in between leaves both, never neither.
+//!
+//! `<base>` comes from [`RecorderOptions::file_name`], a template with the job
+//! name and local start time.
+
+use super::{SessionError, SessionHeader};
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+/// When the recorder pushes buffered events to disk.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct FlushPolicy {
+    /// Flush to the OS after every event (survives an app crash, not power loss).
+    pub flush_every_event: bool,
+    /// fsync at most this long after the last one (bounds what a power loss
+    /// can take). `None`: only on important events and finish.
+    pub sync_interval: Option<Duration>,
+    /// fsync after job start/end, alarms, probes, setting changes and
+    /// disconnects.
+    pub sync_on_important: bool,
+}
+
+impl Default for FlushPolicy {
+    fn default() -> Self {
+        Self {
+            flush_every_event: true,
+            sync_interval: Some(Duration::from_secs(5)),
+            sync_on_important: true,
+        }
+    }
+}
+
+/// When the recorder starts a new segment. Both limits may be set; the first
+/// one reached rotates.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct RotationPolicy {
+    /// Rotate once a segment has this many bytes.
+    pub max_bytes: Option<u64>,
+    /// Rotate once a segment is this old.
+    pub max_age: Option<Duration>,
+}
+
+/// Compression applied to closed segments.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Compression {
+    #[default]
+    None,
+    Gzip,
+    /// Requires the `zstd` feature.
+    Zstd,
+}
+
+impl Compression {
+    fn extension(self) -> &'static str {
+        match self {
+            Compression::None => "",
+            Compression::Gzip => ".gz",
+            Compression::Zstd => ".zst",
+        }
+    }
+}
+
+/// Which old sessions [`cleanup_sessions`] removes. Unset limits do not apply.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct RetentionPolicy {
+    /// Keep at most this many sessions (newest first).
+    pub max_sessions: Option<usize>,
+    /// Remove sessions last written longer ago than this.
+    pub max_age: Option<Duration>,
+    /// Remove the oldest sessions until all together are at most this size.
+    pub max_total_bytes: Option<u64>,
+}
+
+/// Default [`RecorderOptions::file_name`]: `session_YYYY-MM-DDTHH-MM-SS`.
+pub const DEFAULT_FILE_NAME: &str = "session_{date}T{time}";
+
+/// Options for [`super::SessionRecorder::start_session_with`].
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct RecorderOptions {
+    /// Session base name template (see [`render_file_name`]).
+    pub file_name: String,
+    pub flush: FlushPolicy,
+    pub rotation: RotationPolicy,
+    pub compression: Compression,
+    /// Applied to `log_dir` before the new session is created.
+    pub retention: Option<RetentionPolicy>,
+}
+
+impl Default for RecorderOptions {
+    fn default() -> Self {
+        Self {
+            file_name: DEFAULT_FILE_NAME.to_string(),
+            flush: FlushPolicy::default(),
+            rotation: RotationPolicy::default(),
+            compression: Compression::default(),
+            retention: None,
+        }
+    }
+}
+
+/// Keep letters, digits, `-` and `_`; anything else becomes `_`.
+fn sanitize(s: &str) -> String {
+    s.chars()
+        .map(|c| {
+            if c.is_alphanumeric() || c == '-' || c == '_' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+/// Session base name from `template`. Placeholders: `{job}` (job name, empty if
+/// none), `{date}` (`YYYY-MM-DD`), `{time}` (`HH-MM-SS`), both in local time at
+/// `started`, and `{host}`. The result only contains letters, digits, `-` and
+/// `_` (no dots, so segment and manifest names stay unambiguous); separators left
+/// dangling by an empty placeholder are trimmed, and an empty result becomes
+/// `session`.
+pub fn render_file_name(
+    template: &str,
+    header: &SessionHeader,
+    started: &DateTime<Local>,
+) -> String {
+    let name = template
+        .replace("{job}", header.job_name.as_deref().unwrap_or(""))
+        .replace("{date}", &started.format("%Y-%m-%d").to_string())
+        .replace("{time}", &started.format("%H-%M-%S").to_string())
+        .replace("{host}", &header.host);
+    let name = sanitize(&name);
+    let name = name.trim_matches(|c| c == '_' || c == '-');
+    if name.is_empty() {
+        "session".to_string()
+    } else {
+        name.to_string()
+    }
+}
+
+/// One segment in a [`SessionManifest`].
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SegmentInfo {
+    /// File name, relative to the manifest.
+    pub file: String,
+    pub first_ts: Option<f64>,
+    pub last_ts: Option<f64>,
+    pub events: u64,
+    /// Uncompressed size.
+    pub bytes: u64,
+    pub compression: Compression,
+    /// Finished writing (by rotation or finish).
+    pub closed: bool,
+}
+
+/// Index of the segments of one session.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SessionManifest {
+    /// Base name shared by the session's files.
+    pub session: String,
+    pub started_secs: f64,
+    /// Start in local time, RFC 3339 with UTC offset.
+    #[serde(default)]
+    pub started_local: String,
+    pub segments: Vec<SegmentInfo>,
+}
+
+impl SessionManifest {
+    pub fn load(path: &Path) -> Result<Self, SessionError> {
+        let content = fs::read_to_string(path).map_err(|e| SessionError::OpenFailed {
+            path: path.to_path_buf(),
+            source: e,
+        })?;
+        serde_json::from_str(&content).map_err(|e| SessionError::InvalidLine {
+            line: 0,
+            message: e.to_string(),
+        })
+    }
+
+    /// Write to a temporary file, sync it and rename it over `path`.
+    pub fn save(&self, path: &Path) -> Result<(), SessionError> {
+        let tmp = path.with_extension("json.tmp");
+        let json = serde_json::to_string_pretty(self).map_err(|e| {
+            SessionError::WriteFailed(io::Error::new(io::ErrorKind::InvalidData, e))
+        })?;
+        let mut file = File::create(&tmp)?;
+        file.write_all(json.as_bytes())?;
+        file.sync_all()?;
+        fs::rename(&tmp, path)?;
+        Ok(())
+    }
+
+    /// Segment paths in order, resolved against `dir`.
+    pub fn segment_paths(&self, dir: &Path) -> Vec<PathBuf> {
+        self.segments.iter().map(|s| dir.join(&s.file)).collect()
+    }
+}
+
+/// `<base>.manifest.json` in `dir`.
+pub fn manifest_path(dir: &Path, base: &str) -> PathBuf {
+    dir.join(format!("{}.manifest.json", base))
+}
+
+/// File name of (uncompressed) segment `index` of `base`.
+pub(super) fn segment_file_name(base: &str, index: usize) -> String {
+    if index == 0 {
+        format!("{}.jsonl", base)
+    } else {
+        format!("{}.{}.jsonl", base, index)
+    }
+}
+
+/// Compress `path` next to itself. Returns the new path (`path` unchanged for
+/// [`Compression::None`]). The original is kept; remove it once the manifest
+/// names the compressed file.
+pub fn compress_segment(path: &Path, compression: Compression) -> Result<PathBuf, SessionError> {
+    if compression == Compression::None {
+        return Ok(path.to_path_buf());
+    }
+    let mut target = path.as_os_str().to_owned();
+    target.push(compression.extension());
+    let target = PathBuf::from(target);
+    let mut input = File::open(path).map_err(|e| SessionError::OpenFailed {
+        path: path.to_path_buf(),
+        source: e,
+    })?;
+    let output = BufWriter::new(File::create(&target)?);
+    let file = match compression {
+        Compression::Gzip => {
+            let mut enc = flate2::write::GzEncoder::new(output, flate2::Compression::default());
+            io::copy(&mut input, &mut enc)?;
+            enc.finish()?.into_inner().map_err(|e| e.into_error())?
+        }
+        #[cfg(feature = "zstd")]
+        Compression::Zstd => {
+            let mut enc = zstd::stream::write::Encoder::new(output, 0)?;
+            io::copy(&mut input, &mut enc)?;
+            enc.finish()?.into_inner().map_err(|e| e.into_error())?
+        }
+        #[cfg(not(feature = "zstd"))]
+        Compression::Zstd => {
+            drop(output);
+            let _ = fs::remove_file(&target);
+            return Err(SessionError::WriteFailed(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "zstd compression requires the `zstd` feature",
+            )));
+        }
+        Compression::None => unreachable!(),
+    };
+    file.sync_all()?;
+    Ok(target)
+}
+
+/// Open a segment for reading, decompressing `.gz` / `.zst` by extension.
+pub(super) fn open_segment(path: &Path) -> Result<Box<dyn BufRead + Send>, SessionError> {
+    let file = File::open(path).map_err(|e| SessionError::OpenFailed {
+        path: path.to_path_buf(),
+        source: e,
+    })?;
+    match path.extension().and_then(|e| e.to_str()) {
+        Some("gz") => Ok(Box::new(BufReader::new(flate2::read::MultiGzDecoder::new(
+            file,
+        )))),
+        #[cfg(feature = "zstd")]
+        Some("zst") => Ok(Box::new(BufReader::new(
+            zstd::stream::read::Decoder::new(file).map_err(SessionError::ReadFailed)?,
+        ))),
+        #[cfg(not(feature = "zstd"))]
+        Some("zst") => Err(SessionError::ReadFailed(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "zstd logs require the `zstd` feature",
+        ))),
+        _ => Ok(Box::new(BufReader::new(file))),
+    }
+}
+
+/// Files of one session in a log directory.
+#[derive(Clone, Debug)]
+struct StoredSession {
+    files: Vec<PathBuf>,
+    bytes: u64,
+    modified: SystemTime,
+}
+
+/// Session base name a file in a log directory would belong to (up to the
+/// first dot).
+fn session_base(name: &str) -> &str {
+    name.split('.').next().unwrap_or(name)
+}
+/// Remove old sessions from `log_dir` according to `policy`. Sessions are
+/// grouped by base name (all segments, compressed or not, and the manifest) and
+/// ordered by their most recent modification. Only bases with a manifest, or
+/// named `session_...` (logs from before manifests), count as sessions; other
+/// files are left alone. Returns the removed files.
+pub fn cleanup_sessions(
+    log_dir: &Path,
+    policy: &RetentionPolicy,
+) -> Result<Vec<PathBuf>, SessionError> {
+    let mut sessions: std::collections::BTreeMap<String, StoredSession> = Default::default();
+    for entry in fs::read_dir(log_dir).map_err(SessionError::ReadFailed)? {
+        let entry = entry.map_err(SessionError::ReadFailed)?;
+        let name = entry.file_name().to_string_lossy().into_owned();
+        let base = session_base(&name);
+        let meta = entry.metadata().map_err(SessionError::ReadFailed)?;
+        if !meta.is_file() {
+            continue;
+        }
+        let modified = meta.modified().unwrap_or(SystemTime::UNIX_EPOCH);
+        let session = sessions
+            .entry(base.to_string())
+            .or_insert_with(|| StoredSession {
+                files: Vec::new(),
+                bytes: 0,
+                modified,
+            });
+        session.files.push(entry.path());
+        session.bytes += meta.len();
+        session.modified = session.modified.max(modified);
+    }
+    let mut sessions: Vec<StoredSession> = sessions
+        .into_iter()
+        .filter(|(base, _)| base.starts_with("session_") || manifest_path(log_dir, base).exists())
+        .map(|(_, session)| session)
+        .collect();
+    sessions.sort_by_key(|s| std::cmp::Reverse(s.modified));
+
+    let now = SystemTime::now();
+    let mut total = 0u64;
+    let mut removed = Vec::new();
+    for (i, session) in sessions.into_iter().enumerate() {
+        total += session.bytes;
+        let too_many = policy.max_sessions.is_some_and(|max| i >= max);
+        let too_old = policy.max_age.is_some_and(|age| {
+            now.duration_since(session.modified)
+                .is_ok_and(|elapsed| elapsed > age)
+        });
+        let too_big = policy.max_total_bytes.is_some_and(|max| total > max);
+        if too_many || too_old || too_big {
+            for file in session.files {
+                fs::remove_file(&file)?;
+                removed.push(file);
+            }
+            total -= session.bytes;
+        }
+    }
+    Ok(removed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use std::io::Read;
+
+    #[test]
+    fn test_compress_and_open() {
+        let dir = std::env::temp_dir().join(format!("grbl_rs_storage_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("session_x.1.jsonl");
+        fs::write(&path, "{\"a\":1}\n{\"a\":2}\n").unwrap();
+        let gz = compress_segment(&path, Compression::Gzip).unwrap();
+        assert!(path.exists());
+        assert_eq!(gz, dir.join("session_x.1.jsonl.gz"));
+        let mut s = String::new();
+        open_segment(&gz).unwrap().read_to_string(&mut s).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(s, "{\"a\":1}\n{\"a\":2}\n");
+    }
+
+    #[test]
+    fn test_render_file_name() {
+        let started = Local.with_ymd_and_hms(2026, 3, 7, 9, 5, 2).unwrap();
+        let header = SessionHeader {
+            host: "shop".to_string(),
+            ..Default::default()
+        };
+        assert_eq!(
+            render_file_name(DEFAULT_FILE_NAME, &header, &started),
+            "session_2026-03-07T09-05-02"
+        );
+        assert_eq!(
+            render_file_name("{job}_{date}", &header, &started),
+            "2026-03-07"
+        );
+        let header = header.with_job_name("Sign v2.1/back");
+        assert_eq!(
+            render_file_name("{job}_{host}_{time}", &header, &started),
+            "Sign_v2_1_back_shop_09-05-02"
+        );
+        assert_eq!(render_file_name("...", &header, &started), "session");
+    }
+
+    #[test]
+    fn test_cleanup_sessions() {
+        let dir = std::env::temp_dir().join(format!("grbl_rs_retention_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        for (i, base) in ["session_a", "session_b", "session_c"].iter().enumerate() {
+            for name in [
+                format!("{}.jsonl", base),
+                format!("{}.1.jsonl.gz", base),
+                format!("{}.manifest.json", base),
+            ] {
+                let file = File::create(dir.join(name)).unwrap();
+                file.set_modified(SystemTime::now() - Duration::from_secs(3600 * (3 - i as u64)))
+                    .unwrap();
+            }
+        }
+        fs::write(dir.join("notes.txt"), "keep").unwrap();
+
+        let removed = cleanup_sessions(
+            &dir,
+            &RetentionPolicy {
+                max_sessions: Some(2),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        assert_eq!(removed.len(), 3);
+        assert!(!dir.join("session_a.jsonl").exists());
+        assert!(dir.join("session_b.jsonl").exists());
+
+        let removed = cleanup_sessions(
+            &dir,
+            &RetentionPolicy {
+                max_age: Some(Duration::from_secs(5400)),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        let left: Vec<_> = fs::read_dir(&dir)
+            .unwrap()
+            .map(|e| e.unwrap().file_name())
+            .collect();
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(removed.len(), 3);
+        assert_eq!(left.len(), 4);
+    }
+}
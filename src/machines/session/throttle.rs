@@ -0,0 +1,173 @@
+//! Which status reports the automatic session recording writes.
+//!
+//! The poller reports several times a second; the log only needs one status per
+//! interval, plus every report where something happened: a state transition
+//! (including alarms) or a position jump between consecutive reports (lost
+//! steps, a reset, or a bad report). A jump is travel beyond what the reported
+//! feed rate covers in the time between the reports.
+
+use crate::machines::grbl::{MachineState, MachineStatus, Position};
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, Instant};
+
+/// Settings for the automatic status recording.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct StatusRecordOptions {
+    /// Write at most one routine status per interval.
+    pub interval: Duration,
+    /// Machine position change between consecutive reports (mm), beyond the
+    /// travel at the reported feed rate, that is always written.
+    pub position_jump_mm: f64,
+}
+
+impl Default for StatusRecordOptions {
+    fn default() -> Self {
+        Self {
+            interval: Duration::from_secs(1),
+            position_jump_mm: 5.0,
+        }
+    }
+}
+
+/// Why a status is written.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RecordReason {
+    /// First status, or the interval has passed.
+    Interval,
+    StateChange,
+    /// Changed into an alarm state.
+    Alarm,
+    PositionJump,
+}
+
+/// Decides per status report whether to write it.
+#[derive(Clone, Debug)]
+pub struct StatusThrottle {
+    options: StatusRecordOptions,
+    last_written: Option<Instant>,
+    last_state: Option<MachineState>,
+    last_pos: Option<Position>,
+    last_feed: f64,
+    last_seen: Option<Instant>,
+}
+
+fn distance(a: &Position, b: &Position) -> f64 {
+    let da = a.a.unwrap_or(0.0) - b.a.unwrap_or(0.0);
+    ((a.x - b.x).powi(2) + (a.y - b.y).powi(2) + (a.z - b.z).powi(2) + da.powi(2)).sqrt()
+}
+
+impl StatusThrottle {
+    pub fn new(options: StatusRecordOptions) -> Self {
+        Self {
+            options,
+            last_written: None,
+            last_state: None,
+            last_pos: None,
+            last_feed: 0.0,
+            last_seen: None,
+        }
+    }
+
+    /// Reason to write `status`, received at `now`, or `None` to skip it.
+    pub fn check(&mut self, status: &MachineStatus, now: Instant) -> Option<RecordReason> {
+        let changed = self.last_state.as_ref() != Some(&status.state);
+        let jumped = self.last_pos.as_ref().is_some_and(|p| {
+            // Either report may be mid-acceleration; allow the faster of the two.
+            let feed_mm_s = self.last_feed.max(status.feed_rate) / 60.0;
+            let elapsed = self
+                .last_seen
+                .map_or(0.0, |t| now.duration_since(t).as_secs_f64());
+            distance(p, &status.machine_pos) > feed_mm_s * elapsed + self.options.position_jump_mm
+        });
+        let due = self
+            .last_written
+            .is_none_or(|t| now.duration_since(t) >= self.options.interval);
+        let first = self.last_state.is_none();
+        self.last_state = Some(status.state.clone());
+        self.last_pos = Some(status.machine_pos.clone());
+        self.last_feed = status.feed_rate;
+        self.last_seen = Some(now);
+        let reason = if changed && !first && matches!(status.state, MachineState::Alarm(_)) {
+            RecordReason::Alarm
+        } else if changed && !first {
+            RecordReason::StateChange
+        } else if jumped {
+            RecordReason::PositionJump
+        } else if due {
+            RecordReason::Interval
+        } else {
+            return None;
+        };
+        self.last_written = Some(now);
+        Some(reason)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::machines::grbl::AlarmCode;
+
+    fn status(state: MachineState, x: f64) -> MachineStatus {
+        let mut s = MachineStatus::idle();
+        s.state = state;
+        s.machine_pos.x = x;
+        s
+    }
+
+    #[test]
+    fn test_throttle() {
+        let mut t = StatusThrottle::new(StatusRecordOptions::default());
+        let t0 = Instant::now();
+        let ms = |n: u64| t0 + Duration::from_millis(n);
+        assert_eq!(
+            t.check(&status(MachineState::Idle, 0.0), t0),
+            Some(RecordReason::Interval)
+        );
+        assert_eq!(t.check(&status(MachineState::Idle, 0.0), ms(200)), None);
+        assert_eq!(
+            t.check(&status(MachineState::Run, 1.0), ms(400)),
+            Some(RecordReason::StateChange)
+        );
+        assert_eq!(t.check(&status(MachineState::Run, 2.0), ms(600)), None);
+        assert_eq!(
+            t.check(&status(MachineState::Run, 20.0), ms(800)),
+            Some(RecordReason::PositionJump)
+        );
+        assert_eq!(
+            t.check(&status(MachineState::Run, 21.0), ms(1800)),
+            Some(RecordReason::Interval)
+        );
+        let alarm = MachineState::Alarm(AlarmCode::HardLimit);
+        assert_eq!(
+            t.check(&status(alarm, 21.0), ms(1900)),
+            Some(RecordReason::Alarm)
+        );
+    }
+
+    #[test]
+    fn test_throttle_fast_move_is_not_a_jump() {
+        let mut t = StatusThrottle::new(StatusRecordOptions::default());
+        let t0 = Instant::now();
+        let ms = |n: u64| t0 + Duration::from_millis(n);
+        let moving = |x: f64| MachineStatus {
+            feed_rate: 6000.0,
+            ..status(MachineState::Run, x)
+        };
+        assert!(t.check(&moving(0.0), t0).is_some());
+        // 100 mm/s with a report every 200 ms: 20 mm per report.
+        for i in 1..5 {
+            let reason = t.check(&moving(20.0 * i as f64), ms(200 * i));
+            assert_eq!(reason, None);
+        }
+        // The same travel without feed is a jump, and so is one on A.
+        assert_eq!(t.check(&status(MachineState::Run, 80.0), ms(900)), None);
+        assert_eq!(
+            t.check(&status(MachineState::Run, 100.0), ms(950)),
+            Some(RecordReason::PositionJump)
+        );
+        let mut bed = status(MachineState::Run, 100.0);
+        bed.machine_pos.a = Some(50.0);
+        assert_eq!(t.check(&bed, ms(1000)), Some(RecordReason::PositionJump));
+    }
+}
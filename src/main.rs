@@ -1,10 +1,24 @@
 //! Minimal binary: parses a hard-coded GRBL-HAL status string (no serial port).
 //! Confirms the parser and state types are wired correctly.
+//!
+//! With a file argument (`grbl-rs job.nc`), prints the job statistics as JSON instead.
 
-use grbl_rs::machines::grbl::{parse_status, MachineState};
+use grbl_rs::machines::grbl::{analyze_file, parse_status, MachineState, MotionConfig};
+use std::path::Path;
 use std::time::Instant;
 
 fn main() {
+    if let Some(path) = std::env::args().nth(1) {
+        match analyze_file(Path::new(&path), &MotionConfig::default()) {
+            Ok(stats) => println!("{}", serde_json::to_string_pretty(&stats).unwrap()),
+            Err(e) => {
+                eprintln!("{}: {}", path, e);
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+
     let line = "<Idle|MPos:0.000,0.000,0.000|WPos:0.000,0.000,0.000|FS:0,0>";
     match parse_status(line, Instant::now()) {
         Ok(status) => {